@@ -0,0 +1,179 @@
+/***
+ * Accessible mode
+ *
+ * Line-based alternative to the TUI for terminal screen readers. Nothing is
+ * drawn: moving the selection prints the selected entry as a line of text,
+ * and confirmations are plain prompts, so everything ends up in the normal
+ * scrollback.
+ ***/
+use std::io::Result;
+
+use ratatui::crossterm::{
+    event::{self, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    terminal::{disable_raw_mode, enable_raw_mode},
+};
+
+use crate::{
+    config::Config, confirm, confirm_protected, delete_entry_wrapper, efibootmgr::format_id, get_boot_targets, i18n::tr, keys::Action, prompt, reboot_to, safety, set_next_boot_wrapper,
+    verify, BootTarget, BootTargets, Exit,
+};
+
+/// Entries skipped by the page up and down keys
+const PAGE: usize = 10;
+
+/// Wait for a single key press, leaving the terminal in normal mode otherwise
+fn read_key() -> Result<KeyEvent> {
+    enable_raw_mode()?;
+    let key = loop {
+        match event::read() {
+            Ok(event::Event::Key(key)) if key.kind == KeyEventKind::Press => break Ok(key),
+            Ok(_) => continue,
+            Err(e) => break Err(e),
+        }
+    };
+    disable_raw_mode()?;
+
+    key
+}
+
+/// Sentence describing an entry and its position in the list
+fn describe(targets: &BootTargets, index: usize, target: &BootTarget) -> String {
+    let mut text = tr!("{} of {}: {}", index + 1, targets.targets.len(), target.label());
+
+    if targets.next == Some(target.id) {
+        text.push_str(tr!(", next boot"));
+    }
+    if targets.current == Some(target.id) {
+        text.push_str(tr!(", currently booted"));
+    }
+    if !target.active {
+        text.push_str(tr!(", inactive"));
+    }
+    if target.loader.needs_attention() {
+        text.push_str(&tr!(", {}", target.loader.describe()));
+    }
+    text
+}
+
+fn reload(targets: &mut BootTargets, config: &Config) {
+    *targets = get_boot_targets();
+    verify::verify_targets(targets, !config.keep_names);
+}
+
+/// Reboot to the entry after the same safety checks as on the command line
+fn reboot(target: &BootTarget, assume_yes: bool, config: &Config) -> Option<Exit> {
+    if !confirm_protected(target, config) {
+        return None;
+    }
+
+    if !assume_yes {
+        if let Some(warning) = safety::loader_warning(target) {
+            println!("{}", warning);
+            if !confirm(tr!("Reboot anyway?")) {
+                return None;
+            }
+        }
+
+        let blockers = safety::reboot_blockers();
+        if !blockers.is_empty() {
+            blockers.iter().for_each(|blocker| println!("{}", blocker));
+            println!("{}", tr!("Refusing to reboot"));
+            return None;
+        }
+
+        let warnings = safety::reboot_warnings();
+        if !warnings.is_empty() {
+            warnings.iter().for_each(|warning| println!("{}", warning));
+            if !confirm(tr!("Reboot anyway?")) {
+                return None;
+            }
+        }
+    }
+
+    println!("{}", tr!("Rebooting to \"{}\"...", target.name));
+    Some(reboot_to(target))
+}
+
+/// Delete the entry after typing its name or ID, returning whether it was deleted
+fn delete(target: &BootTarget) -> bool {
+    println!("{}", tr!("About to delete boot entry {} \"{}\". This cannot be undone.", format_id(target.id), target.name));
+    let confirmed = prompt(tr!("Type the name or ID of the entry to confirm:"))
+        .is_some_and(|input| target.confirmed_by(&input));
+    if !confirmed {
+        println!("{}", tr!("Confirmation did not match, not deleting"));
+        return false;
+    }
+
+    delete_entry_wrapper(target) == Exit::Success
+}
+
+/// Let the user pick an entry with the configured keys, announcing each move
+pub fn run(mut targets: BootTargets, assume_yes: bool, config: &Config) -> Result<Exit> {
+    let keys = &config.keys;
+    println!("{}", tr!("{} boot entries. {} and {} select, {} reboots, {} sets the next boot, {} deletes, {} refreshes, {} quits.",
+        targets.targets.len(), keys.label(Action::Up), keys.label(Action::Down), keys.label(Action::Reboot), keys.label(Action::SetNext),
+        keys.label(Action::Delete), keys.label(Action::Refresh), keys.label(Action::Quit)));
+
+    let mut selected = 0;
+    let mut announce = true;
+    loop {
+        if targets.targets.is_empty() {
+            println!("{}", tr!("No UEFI boot entries found"));
+            return Ok(Exit::Failure);
+        }
+        selected = selected.min(targets.targets.len() - 1);
+        if announce {
+            println!("{}", describe(&targets, selected, &targets.targets[selected]));
+        }
+        announce = true;
+
+        let key = read_key()?;
+        let target = &targets.targets[selected];
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return Ok(Exit::Cancelled);
+        }
+
+        match keys.action(&key) {
+            // At either end of the list the same entry is announced again
+            Some(Action::Up) => selected = selected.saturating_sub(1),
+            Some(Action::Down) => selected = (selected + 1).min(targets.targets.len() - 1),
+            Some(Action::First) => selected = 0,
+            Some(Action::Last) => selected = targets.targets.len() - 1,
+            Some(Action::PageUp) => selected = selected.saturating_sub(PAGE),
+            Some(Action::PageDown) => selected = (selected + PAGE).min(targets.targets.len() - 1),
+            Some(Action::Reboot) => {
+                if let Some(exit) = reboot(target, assume_yes, config) {
+                    return Ok(exit);
+                }
+            }
+            Some(Action::SetNext) => {
+                if !confirm_protected(target, config) {
+                    continue;
+                }
+                if let Some(warning) = safety::loader_warning(target).filter(|_| !assume_yes) {
+                    println!("{}", warning);
+                    if !confirm(tr!("Continue anyway?")) {
+                        continue;
+                    }
+                }
+                let exit = set_next_boot_wrapper(target);
+                if exit == Exit::Success {
+                    println!("{}", tr!("Next boot set to \"{}\"", target.name));
+                }
+                return Ok(exit);
+            }
+            Some(Action::Delete) => {
+                if delete(target) {
+                    reload(&mut targets, config);
+                }
+            }
+            Some(Action::Refresh) => {
+                reload(&mut targets, config);
+                println!("{}", tr!("Refreshed, {} boot entries", targets.targets.len()));
+            }
+            Some(Action::Quit) => return Ok(Exit::Success),
+            // Stay quiet on other keys, and actions only the TUI has, rather than repeat the entry
+            Some(Action::SetDefault | Action::ClearNext | Action::ToggleActive | Action::Undo | Action::RemoveDuplicates | Action::NextTab | Action::PreviousTab | Action::ToggleCommands | Action::ToggleNetwork) | None => announce = false,
+        }
+    }
+}
@@ -0,0 +1,46 @@
+/***
+ * Boot configuration backup
+ *
+ * Dumps all boot entries, BootOrder and the timeout to a JSON file, so the
+ * configuration can be restored after the firmware wipes NVRAM.
+ ***/
+use std::{fs, io, path::Path, time::{SystemTime, UNIX_EPOCH}};
+
+use crate::{efivar::read_efivar, format_id, json::Value, parser::DevicePath, BootTargets};
+
+/// Version of the backup file format
+pub const BACKUP_VERSION: u64 = 1;
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Build the backup document for the given boot configuration
+pub fn create(targets: &BootTargets) -> Value {
+    let created = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+
+    let entries = targets.targets.iter().map(|target| {
+        Value::object([
+            ("id", format_id(target.id).into()),
+            ("name", target.name.as_str().into()),
+            ("device_path", target.device_path.as_ref().map(DevicePath::as_str).into()),
+            ("raw", read_efivar(&format!("Boot{}", format_id(target.id))).map(|raw| to_hex(&raw)).into()),
+        ])
+    }).collect();
+
+    Value::object([
+        ("version", BACKUP_VERSION.into()),
+        ("created", created.into()),
+        ("timeout", targets.timeout.into()),
+        ("boot_current", targets.current.map(format_id).into()),
+        ("boot_order", Value::Array(targets.order.iter().map(|id| format_id(*id).into()).collect())),
+        ("entries", Value::Array(entries)),
+    ])
+}
+
+/// Write a backup of the boot configuration to `path`
+pub fn write(targets: &BootTargets, path: &Path) -> io::Result<()> {
+    let mut document = create(targets).to_pretty();
+    document.push('\n');
+    fs::write(path, document)
+}
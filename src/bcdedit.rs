@@ -0,0 +1,203 @@
+/***
+ * Windows backend
+ *
+ * Windows has no efibootmgr, so entries are listed with `bcdedit /enum firmware`
+ * and the one-time boot entry is set through the firmware boot manager's
+ * bootsequence. bcdedit identifies entries by GUID, the IDs shown instead are
+ * positions in its output. bcdedit translates its keys, so only English
+ * output is understood.
+ ***/
+#[cfg(windows)]
+use std::{io::Result, process::{Command, Output}, sync::Mutex};
+
+use crate::{verify::LoaderStatus, BootTarget, BootTargets};
+#[cfg(windows)]
+use crate::{process, sudo};
+
+/// Identifier of the firmware boot manager, which holds the order and BootNext
+const FIRMWARE_BOOT_MANAGER: &str = "{fwbootmgr}";
+
+/// Identifier of the Windows Boot Manager, which is what booted Windows
+const WINDOWS_BOOT_MANAGER: &str = "{bootmgr}";
+
+/// bcdedit identifiers of the listed entries, indexed by ID
+#[cfg(windows)]
+static IDENTIFIERS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+/// Section of bcdedit output, as key and values. Lists continue on indented lines.
+type Section = Vec<(String, Vec<String>)>;
+
+fn sections(raw: &str) -> Vec<Section> {
+    let mut sections = vec![];
+    let mut section: Section = vec![];
+
+    for line in raw.lines() {
+        if line.trim().is_empty() {
+            if !section.is_empty() {
+                sections.push(std::mem::take(&mut section));
+            }
+            continue;
+        }
+
+        if line.starts_with(char::is_whitespace) {
+            if let Some((_, values)) = section.last_mut() {
+                values.push(line.trim().to_string());
+            }
+            continue;
+        }
+
+        // Section titles are underlined with dashes and have no values
+        match line.split_once(char::is_whitespace) {
+            Some((key, value)) => section.push((key.to_string(), vec![value.trim().to_string()])),
+            None if line.starts_with('-') => section.clear(),
+            None => (),
+        }
+    }
+    if !section.is_empty() {
+        sections.push(section);
+    }
+
+    sections
+}
+
+fn value<'a>(section: &'a Section, key: &str) -> Option<&'a str> {
+    section.iter().find(|(k, _)| k == key).and_then(|(_, values)| values.first()).map(String::as_str)
+}
+
+fn values<'a>(section: &'a Section, key: &str) -> &'a [String] {
+    section.iter().find(|(k, _)| k == key).map(|(_, values)| values.as_slice()).unwrap_or_default()
+}
+
+/// Parse the output of `bcdedit /enum firmware` into boot targets and the
+/// bcdedit identifier of each target
+pub fn parse(raw: &str) -> (BootTargets, Vec<String>) {
+    let sections = sections(raw);
+    let firmware = sections.iter().find(|section| value(section, "identifier") == Some(FIRMWARE_BOOT_MANAGER));
+
+    let mut identifiers = vec![];
+    let mut targets = vec![];
+    for section in sections.iter() {
+        let (Some(identifier), Some(description)) = (value(section, "identifier"), value(section, "description")) else {
+            continue;
+        };
+        if identifier == FIRMWARE_BOOT_MANAGER {
+            continue;
+        }
+
+        targets.push(BootTarget {
+            id: identifiers.len() as u16,
+            name: description.to_string(),
+            active: true,
+            device_path: None,
+            loader: LoaderStatus::Unchecked,
+            device: None,
+            os_name: None,
+            removable: false,
+        });
+        identifiers.push(identifier.to_string());
+    }
+
+    let id_of = |identifier: &str| identifiers.iter().position(|i| i == identifier).map(|index| index as u16);
+    let result = BootTargets {
+        current: id_of(WINDOWS_BOOT_MANAGER),
+        next: firmware.and_then(|f| value(f, "bootsequence")).and_then(id_of),
+        order: firmware.map(|f| values(f, "displayorder").iter().filter_map(|i| id_of(i)).collect()).unwrap_or_default(),
+        timeout: firmware.and_then(|f| value(f, "timeout")).and_then(|t| t.parse().ok()),
+        targets,
+    };
+
+    (result, identifiers)
+}
+
+/// List the firmware boot entries
+#[cfg(windows)]
+pub fn list() -> BootTargets {
+    let output = process::output(Command::new("bcdedit").args(["/enum", "firmware"]), None);
+    let raw = match output {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout).into_owned(),
+        Ok(output) => {
+            log::warn!("could not list boot entries: {}", String::from_utf8_lossy(&output.stderr).trim());
+            String::new()
+        }
+        Err(e) => {
+            log::warn!("could not run bcdedit: {}", e);
+            String::new()
+        }
+    };
+
+    let (targets, identifiers) = parse(&raw);
+    *IDENTIFIERS.lock().expect("identifier lock should not be poisoned") = identifiers;
+    targets
+}
+
+/// Make the firmware boot the target once, on the next boot
+#[cfg(windows)]
+pub fn set_next_boot(target: &BootTarget) -> Result<Output> {
+    let identifier = IDENTIFIERS.lock().expect("identifier lock should not be poisoned").get(target.id as usize).cloned()
+        .ok_or(std::io::Error::new(std::io::ErrorKind::NotFound, "unknown boot entry"))?;
+
+    process::retry(|| sudo::run(Command::new("bcdedit").args(["/set", FIRMWARE_BOOT_MANAGER, "bootsequence", &identifier])))
+}
+
+#[cfg(windows)]
+pub fn clear_next_boot() -> Result<Output> {
+    process::retry(|| sudo::run(Command::new("bcdedit").args(["/deletevalue", FIRMWARE_BOOT_MANAGER, "bootsequence"])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = "
+Firmware Boot Manager
+---------------------
+identifier              {fwbootmgr}
+displayorder            {bootmgr}
+                        {7f3b2c1d-0000-11ef-9a1b-806e6f6e6963}
+                        {7f3b2c1e-0000-11ef-9a1b-806e6f6e6963}
+timeout                 2
+bootsequence            {7f3b2c1d-0000-11ef-9a1b-806e6f6e6963}
+
+Windows Boot Manager
+--------------------
+identifier              {bootmgr}
+device                  partition=\\Device\\HarddiskVolume1
+path                    \\EFI\\Microsoft\\Boot\\bootmgfw.efi
+description             Windows Boot Manager
+locale                  en-US
+
+Firmware Application (101fffff)
+-------------------------------
+identifier              {7f3b2c1d-0000-11ef-9a1b-806e6f6e6963}
+device                  partition=\\Device\\HarddiskVolume1
+path                    \\EFI\\ubuntu\\shimx64.efi
+description             ubuntu
+
+Firmware Application (101fffff)
+-------------------------------
+identifier              {7f3b2c1e-0000-11ef-9a1b-806e6f6e6963}
+description             UEFI: PXE IPv4 Intel(R) Ethernet
+";
+
+    #[test]
+    fn parses_firmware_entries() {
+        let (targets, identifiers) = parse(OUTPUT);
+
+        let names: Vec<&str> = targets.targets.iter().map(|target| target.name.as_str()).collect();
+        assert_eq!(names, vec!["Windows Boot Manager", "ubuntu", "UEFI: PXE IPv4 Intel(R) Ethernet"]);
+        assert_eq!(identifiers[1], "{7f3b2c1d-0000-11ef-9a1b-806e6f6e6963}");
+        assert_eq!(targets.current, Some(0));
+        assert_eq!(targets.next, Some(1));
+        assert_eq!(targets.order, vec![0, 1, 2]);
+        assert_eq!(targets.timeout, Some(2));
+    }
+
+    #[test]
+    fn handles_empty_output() {
+        let (targets, identifiers) = parse("");
+
+        assert!(targets.targets.is_empty());
+        assert!(identifiers.is_empty());
+        assert_eq!(targets.next, None);
+    }
+}
@@ -0,0 +1,156 @@
+/***
+ * macOS backend
+ *
+ * Macs have no efibootmgr. The bootable volumes are the mounted disks, taken
+ * from the output of `mount`, and the one-time boot volume is set with
+ * `bless --nextonly`. Volumes that macOS cannot mount, like most Linux
+ * filesystems, are not listed. Their EFI partition can be mounted first.
+ ***/
+#[cfg(target_os = "macos")]
+use std::{fs, io::Result, path::Path, process::{Command, Output}, sync::Mutex};
+
+use crate::{verify::LoaderStatus, BootTarget, BootTargets};
+#[cfg(target_os = "macos")]
+use crate::{process, sudo};
+
+/// Directory where macOS mounts volumes other than the startup disk
+const VOLUMES: &str = "/Volumes/";
+
+/// Name shown for the startup disk if its volume name can't be found
+const STARTUP_DISK: &str = "Macintosh HD";
+
+/// Mount points of the listed volumes, indexed by ID
+#[cfg(target_os = "macos")]
+static MOUNT_POINTS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+/// Parse the output of `mount` into boot targets and the mount point of each
+/// target. `startup_name` is the volume name of the startup disk.
+pub fn parse(raw: &str, startup_name: &str) -> (BootTargets, Vec<String>) {
+    let mut mount_points = vec![];
+    let mut targets = vec![];
+
+    // Lines look like `/dev/disk0s3 on /Volumes/BOOTCAMP (ntfs, local, read-only)`
+    for line in raw.lines() {
+        let Some((device, rest)) = line.split_once(" on ") else {
+            continue;
+        };
+        let Some((mount_point, _)) = rest.rsplit_once(" (") else {
+            continue;
+        };
+        if !device.starts_with("/dev/disk") {
+            continue;
+        }
+
+        let name = match mount_point {
+            "/" => startup_name,
+            _ => match mount_point.strip_prefix(VOLUMES) {
+                Some(name) if !name.is_empty() && !name.contains('/') => name,
+                _ => continue,
+            },
+        };
+        if mount_points.iter().any(|m| m == mount_point) {
+            continue;
+        }
+
+        targets.push(BootTarget {
+            id: mount_points.len() as u16,
+            name: name.to_string(),
+            active: true,
+            device_path: None,
+            loader: LoaderStatus::Unchecked,
+            device: None,
+            os_name: None,
+            removable: false,
+        });
+        mount_points.push(mount_point.to_string());
+    }
+
+    let result = BootTargets {
+        current: mount_points.iter().position(|m| m == "/").map(|index| index as u16),
+        next: None,
+        order: vec![],
+        timeout: None,
+        targets,
+    };
+
+    (result, mount_points)
+}
+
+/// Volume name of the startup disk, found through its link in /Volumes
+#[cfg(target_os = "macos")]
+fn startup_name() -> String {
+    let Ok(entries) = fs::read_dir(VOLUMES) else {
+        return String::from(STARTUP_DISK);
+    };
+
+    entries.flatten()
+        .find(|entry| fs::canonicalize(entry.path()).is_ok_and(|path| path == Path::new("/")))
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from(STARTUP_DISK))
+}
+
+/// List the mounted volumes
+#[cfg(target_os = "macos")]
+pub fn list() -> BootTargets {
+    let output = process::output(&mut Command::new("mount"), None);
+    let raw = match output {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout).into_owned(),
+        Ok(output) => {
+            log::warn!("could not list volumes: {}", String::from_utf8_lossy(&output.stderr).trim());
+            String::new()
+        }
+        Err(e) => {
+            log::warn!("could not run mount: {}", e);
+            String::new()
+        }
+    };
+
+    let (targets, mount_points) = parse(&raw, &startup_name());
+    *MOUNT_POINTS.lock().expect("mount point lock should not be poisoned") = mount_points;
+    targets
+}
+
+/// Make the Mac boot from the target's volume once, on the next boot
+#[cfg(target_os = "macos")]
+pub fn set_next_boot(target: &BootTarget) -> Result<Output> {
+    let mount_point = MOUNT_POINTS.lock().expect("mount point lock should not be poisoned").get(target.id as usize).cloned()
+        .ok_or(std::io::Error::new(std::io::ErrorKind::NotFound, "unknown volume"))?;
+
+    process::retry(|| sudo::run(Command::new("bless").args(["--mount", &mount_point, "--setBoot", "--nextonly"])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = "\
+/dev/disk3s1s1 on / (apfs, sealed, local, read-only, journaled)
+devfs on /dev (devfs, local, nobrowse)
+/dev/disk3s6 on /System/Volumes/VM (apfs, local, noexec, journaled, noatime, nobrowse)
+/dev/disk3s5 on /System/Volumes/Data (apfs, local, journaled, nobrowse, protect)
+map auto_home on /System/Volumes/Data/home (autofs, automounted, nobrowse)
+/dev/disk0s3 on /Volumes/BOOTCAMP (ntfs, local, read-only, noowners)
+/dev/disk2s1 on /Volumes/Install macOS (hfs, local, nodev, nosuid, journaled, noowners)
+/dev/disk0s1 on /Volumes/EFI (msdos, local, nodev, nosuid, noowners)
+";
+
+    #[test]
+    fn parses_mounted_volumes() {
+        let (targets, mount_points) = parse(OUTPUT, "Macintosh HD");
+
+        let names: Vec<&str> = targets.targets.iter().map(|target| target.name.as_str()).collect();
+        assert_eq!(names, vec!["Macintosh HD", "BOOTCAMP", "Install macOS", "EFI"]);
+        assert_eq!(mount_points[2], "/Volumes/Install macOS");
+        assert_eq!(targets.current, Some(0));
+        assert_eq!(targets.next, None);
+    }
+
+    #[test]
+    fn handles_empty_output() {
+        let (targets, mount_points) = parse("", STARTUP_DISK);
+
+        assert!(targets.targets.is_empty());
+        assert!(mount_points.is_empty());
+        assert_eq!(targets.current, None);
+    }
+}
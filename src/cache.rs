@@ -0,0 +1,103 @@
+/***
+ * Boot entry cache
+ *
+ * Running `efibootmgr -v` is the slow part of most invocations. Its output is
+ * kept in memory and in `$XDG_CACHE_HOME/reboot-to/efibootmgr`, or
+ * `~/.cache/reboot-to/efibootmgr`, together with a digest of the boot
+ * variables in efivarfs, and used again for as long as the digest matches.
+ * Scripts calling `reboot-to --get-next` over and over then only read
+ * efivarfs. Writes through efibootmgr drop the cache, and without efivarfs,
+ * as in demo mode or on other systems, nothing is cached.
+ ***/
+use std::{env, fs, io, path::PathBuf, sync::Mutex};
+
+use crate::{demo, efivar, sha256};
+
+/// Output of the last `efibootmgr -v` in this process, with the digest it belongs to
+static MEMORY: Mutex<Option<(String, String)>> = Mutex::new(None);
+
+/// Location of the cache file
+pub fn path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()).map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+
+    Some(base.join("reboot-to").join("efibootmgr"))
+}
+
+/// Digest of the boot variables as they are now, None if they can't be read
+pub fn digest() -> Option<String> {
+    if demo::is_enabled() {
+        return None;
+    }
+
+    efivar::boot_fingerprint().map(|fingerprint| sha256::hex_digest(&fingerprint))
+}
+
+/// First line of the cache file. The version is part of it, as another
+/// version may print or parse things differently.
+fn header(digest: &str) -> String {
+    format!("reboot-to {} {}", env!("CARGO_PKG_VERSION"), digest)
+}
+
+/// Output in the contents of a cache file, if it was written for `digest`
+fn parse<'a>(text: &'a str, digest: &str) -> Option<&'a str> {
+    let (first, output) = text.split_once('\n')?;
+    (first == header(digest)).then_some(output)
+}
+
+/// `efibootmgr -v` output cached for the boot variables with this digest
+pub fn load(digest: &str) -> Option<String> {
+    let mut memory = MEMORY.lock().expect("cache lock should not be poisoned");
+    if let Some((cached, output)) = memory.as_ref() {
+        if cached == digest {
+            return Some(output.clone());
+        }
+    }
+
+    let text = fs::read_to_string(path()?).ok()?;
+    let output = parse(&text, digest)?.to_string();
+    *memory = Some((digest.to_string(), output.clone()));
+    Some(output)
+}
+
+/// Remember `efibootmgr -v` output for the boot variables with this digest
+pub fn store(digest: &str, output: &str) {
+    *MEMORY.lock().expect("cache lock should not be poisoned") = Some((digest.to_string(), output.to_string()));
+
+    if let Err(e) = write(digest, output) {
+        log::debug!("could not write cache: {}", e);
+    }
+}
+
+fn write(digest: &str, output: &str) -> io::Result<()> {
+    let path = path().ok_or(io::Error::new(io::ErrorKind::NotFound, "neither XDG_CACHE_HOME nor HOME is set"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(path, format!("{}\n{}", header(digest), output))
+}
+
+/// Forget the cached output, after the boot variables were written to
+pub fn invalidate() {
+    *MEMORY.lock().expect("cache lock should not be poisoned") = None;
+
+    if let Some(path) = path() {
+        let _ = fs::remove_file(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_output_for_matching_digest() {
+        let text = format!("{}\nBootCurrent: 0001\nBootOrder: 0001,0000\n", header("abc123"));
+
+        assert_eq!(parse(&text, "abc123"), Some("BootCurrent: 0001\nBootOrder: 0001,0000\n"));
+        assert_eq!(parse(&text, "def456"), None);
+        assert_eq!(parse("reboot-to 0.0.1 abc123\nBootCurrent: 0001\n", "abc123"), None);
+        assert_eq!(parse("", "abc123"), None);
+    }
+}
@@ -0,0 +1,550 @@
+/***
+ * Configuration file
+ *
+ * Settings are read from `/etc/reboot-to/config.toml` for the whole system,
+ * and then from `$XDG_CONFIG_HOME/reboot-to/config.toml`, or
+ * `~/.config/reboot-to/config.toml`, whose settings override those of the
+ * system one by one, unless `--config` names a file to read instead. Only the
+ * part of TOML needed here is understood: `[section]` headers and
+ * `key = value` lines, where values are strings, integers, booleans or
+ * arrays of those. A broken file is reported and the defaults are used
+ * instead.
+ *
+ * Sections named like `[profile.work.kiosk]` only apply with `--profile work`,
+ * or on a machine named `work` without `--profile`, and override the settings
+ * of the plain section, so that one file can be shared between machines.
+ * Environment variables like `REBOOT_TO_TUI_THEME` override both.
+ ***/
+use std::{collections::BTreeMap, env, fmt, fs, io, path::{Path, PathBuf}};
+
+#[cfg(feature = "tui")]
+use crate::{keys::KeyMap, theme::Preset};
+use crate::{os::Icons, BootTarget};
+
+/// Settings from the configuration file
+#[derive(Debug, Default)]
+pub struct Config {
+    #[cfg(feature = "tui")]
+    pub keys: KeyMap,
+    /// Hide duplicate entries behind the one to keep
+    pub collapse_duplicates: bool,
+    /// Leave network boot entries out of the TUI, see `sections`
+    pub hide_network: bool,
+    /// List entries by their own names, even ones like "Windows Boot
+    /// Manager" that could be listed by the system they start
+    pub keep_names: bool,
+    /// Entries, as <DEST>s, that always need confirming before rebooting to
+    /// them or setting them as the next boot, even with `--yes`
+    pub protected: Vec<String>,
+    /// Entry the TUI reboots to unless a key is pressed in time
+    pub kiosk: Option<Kiosk>,
+    /// Seconds without input after which the TUI quits
+    pub idle_timeout: Option<u64>,
+    /// Draw the TUI as a window in the middle of the terminal
+    pub floating: bool,
+    /// Title above the TUI list, see `DEFAULT_TITLE`
+    pub title: Option<String>,
+    /// Icons in front of the entries, showing the OS they start
+    pub icons: Icons,
+    /// Colors of the TUI, see `theme`
+    #[cfg(feature = "tui")]
+    pub theme: Preset,
+    /// SHA-256 digest of the PIN to enter in the TUI before rebooting or
+    /// setting the next boot, in lowercase hexadecimal
+    pub pin_sha256: Option<String>,
+    /// Let the desktop session end before rebooting, see `session`
+    pub end_session: bool,
+    /// Group whose members may use the daemon's socket, see `daemon`
+    pub daemon_group: Option<String>,
+}
+
+/// Settings of the `[kiosk]` section
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kiosk {
+    /// Entry to reboot to, as a <DEST>
+    pub default: String,
+    /// Seconds to wait for a key
+    pub countdown: u64,
+}
+
+/// Seconds the kiosk countdown lasts unless configured
+const DEFAULT_COUNTDOWN: u64 = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    /// The value as a list of strings, where a single string is a list of one
+    pub fn strings(&self) -> Option<Vec<&str>> {
+        match self {
+            Value::String(s) => Some(vec![s.as_str()]),
+            Value::Array(values) => values.iter().map(|value| match value {
+                Value::String(s) => Some(s.as_str()),
+                _ => None,
+            }).collect(),
+            _ => None,
+        }
+    }
+}
+
+/// Keys and values of a section, in file order
+pub type Section = Vec<(String, Value)>;
+
+/// Sections by name, keys before the first header are in the "" section
+pub type Document = BTreeMap<String, Section>;
+
+/// Problem with the contents of the configuration file
+#[derive(Debug, PartialEq)]
+pub struct ConfigError {
+    pub line: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl Config {
+    /// Whether the entry is one of the protected entries, by exact name or ID
+    pub fn is_protected(&self, target: &BootTarget) -> bool {
+        self.protected.iter().any(|dest| target.confirmed_by(dest))
+    }
+}
+
+/// Configuration file for all users, read before the user's own
+const SYSTEM_PATH: &str = "/etc/reboot-to/config.toml";
+
+/// Location of the user's configuration file
+pub fn path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()).map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(base.join("reboot-to").join("config.toml"))
+}
+
+/// Set a key in a section, replacing an earlier value
+fn set(document: &mut Document, section: &str, key: String, value: Value) {
+    let entries = document.entry(section.to_string()).or_default();
+    match entries.iter_mut().find(|(k, _)| *k == key) {
+        Some(entry) => entry.1 = value,
+        None => entries.push((key, value)),
+    }
+}
+
+/// Lay the settings of one file over those of another
+pub fn overlay(mut base: Document, top: Document) -> Document {
+    for (name, section) in top {
+        base.entry(name.clone()).or_default();
+        for (key, value) in section {
+            set(&mut base, &name, key, value);
+        }
+    }
+    base
+}
+
+/// Start of the names of sections that belong to a profile
+const PROFILE_PREFIX: &str = "profile.";
+
+/// Whether the file has any sections for the profile
+fn has_profile(document: &Document, profile: &str) -> bool {
+    document.keys().any(|name| name.strip_prefix(PROFILE_PREFIX).and_then(|name| name.split_once('.')).is_some_and(|(name, _)| name == profile))
+}
+
+/// Lay the sections of the profile over the plain sections, leaving out the
+/// sections of all profiles
+pub fn apply_profile(document: Document, profile: Option<&str>) -> Document {
+    let (profiles, mut plain): (Document, Document) = document.into_iter().partition(|(name, _)| name.starts_with(PROFILE_PREFIX));
+
+    for (name, section) in profiles {
+        let Some((owner, name)) = name[PROFILE_PREFIX.len()..].split_once('.') else {
+            log::warn!("ignoring [{}] in configuration, it should be [{}<profile>.<section>]", name, PROFILE_PREFIX);
+            continue;
+        };
+        if Some(owner) != profile {
+            continue;
+        }
+
+        plain.entry(name.to_string()).or_default();
+        for (key, value) in section {
+            set(&mut plain, name, key, value);
+        }
+    }
+
+    plain
+}
+
+/// Read and parse the configuration file, reporting why it can't be used
+fn read(path: &Path) -> Option<Document> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            log::warn!("could not read {}: {}", path.display(), e);
+            return None;
+        }
+    };
+
+    parse(&text).inspect_err(|e| log::warn!("ignoring {}, {}", path.display(), e)).ok()
+}
+
+/// Start of the names of environment variables for settings
+const ENV_PREFIX: &str = "REBOOT_TO_";
+
+/// Sections that can be set through environment variables
+const ENV_SECTIONS: [&str; 6] = ["daemon", "keys", "list", "kiosk", "reboot", "tui"];
+
+/// Lay settings from `REBOOT_TO_<SECTION>_<KEY>` environment variables over
+/// the file. Values are written like in the file, but strings need no quotes
+/// unless they would be read as something else, like "0001" as a number.
+pub fn apply_env(mut document: Document, vars: impl Iterator<Item = (String, String)>) -> Document {
+    for (name, text) in vars {
+        let Some(name) = name.strip_prefix(ENV_PREFIX).map(str::to_lowercase) else {
+            continue;
+        };
+        let Some((section, key)) = ENV_SECTIONS.iter().find_map(|section| Some((*section, name.strip_prefix(section)?.strip_prefix('_')?))) else {
+            continue;
+        };
+
+        let value = match parse_value(&text) {
+            Ok((value, rest)) if rest.trim().is_empty() => value,
+            _ => Value::String(text),
+        };
+        set(&mut document, section, key.to_string(), value);
+    }
+
+    document
+}
+
+/// Read the configuration files and environment variables, falling back to
+/// defaults for anything missing or broken. A `file` given is read instead of
+/// both the system and user files. Without a profile, the one in
+/// REBOOT_TO_PROFILE or else the one named after the machine is used.
+pub fn load(profile: Option<&str>, file: Option<&Path>) -> Config {
+    let document = match file {
+        Some(file) => {
+            if !file.exists() {
+                log::warn!("{} does not exist, using the default settings", file.display());
+            }
+            read(file).unwrap_or_default()
+        }
+        None => overlay(read(Path::new(SYSTEM_PATH)).unwrap_or_default(), path().as_deref().and_then(read).unwrap_or_default()),
+    };
+
+    let hostname = crate::hostname();
+    let explicit = profile.map(String::from).or_else(|| env::var(format!("{}PROFILE", ENV_PREFIX)).ok().filter(|profile| !profile.is_empty()));
+    let profile = match explicit.as_deref() {
+        Some(profile) => {
+            if !has_profile(&document, profile) {
+                log::warn!("no sections for profile {:?} in configuration", profile);
+            }
+            Some(profile)
+        }
+        None => Some(hostname.as_str()).filter(|hostname| has_profile(&document, hostname)),
+    };
+
+    from_document(&apply_env(apply_profile(document, profile), env::vars()))
+}
+
+/// Build the settings from a parsed file, reporting settings that don't make sense
+pub fn from_document(document: &Document) -> Config {
+    let mut config = Config::default();
+
+    for (name, section) in document.iter() {
+        match name.as_str() {
+            #[cfg(feature = "tui")]
+            "keys" => match KeyMap::from_section(section) {
+                Ok(keys) => config.keys = keys,
+                Err(e) => log::warn!("ignoring [keys] in configuration, {}", e),
+            },
+            // Shared files may set up a TUI this build doesn't have
+            #[cfg(not(feature = "tui"))]
+            "keys" => (),
+            "list" => for (key, value) in section.iter() {
+                match (key.as_str(), value) {
+                    ("collapse_duplicates", Value::Boolean(collapse)) => config.collapse_duplicates = *collapse,
+                    ("collapse_duplicates", _) => log::warn!("ignoring collapse_duplicates in configuration, it should be true or false"),
+                    ("hide_network", Value::Boolean(hide)) => config.hide_network = *hide,
+                    ("hide_network", _) => log::warn!("ignoring hide_network in configuration, it should be true or false"),
+                    ("keep_names", Value::Boolean(keep)) => config.keep_names = *keep,
+                    ("keep_names", _) => log::warn!("ignoring keep_names in configuration, it should be true or false"),
+                    ("protected", value) => match value.strings() {
+                        Some(dests) => config.protected = dests.into_iter().map(String::from).collect(),
+                        None => log::warn!("ignoring protected in configuration, it should be a list of entry names or IDs"),
+                    },
+                    _ => log::warn!("ignoring unknown setting {:?} in [list] in configuration", key),
+                }
+            },
+            "daemon" => for (key, value) in section.iter() {
+                match (key.as_str(), value) {
+                    ("group", Value::String(group)) if !group.is_empty() => config.daemon_group = Some(group.clone()),
+                    ("group", _) => log::warn!("ignoring group in configuration, it should be the name of a group"),
+                    _ => log::warn!("ignoring unknown setting {:?} in [daemon] in configuration", key),
+                }
+            },
+            "kiosk" => config.kiosk = kiosk(section),
+            "reboot" => for (key, value) in section.iter() {
+                match (key.as_str(), value) {
+                    ("end_session", Value::Boolean(end)) => config.end_session = *end,
+                    ("end_session", _) => log::warn!("ignoring end_session in configuration, it should be true or false"),
+                    _ => log::warn!("ignoring unknown setting {:?} in [reboot] in configuration", key),
+                }
+            },
+            "tui" => for (key, value) in section.iter() {
+                match (key.as_str(), value) {
+                    ("idle_timeout", Value::Integer(seconds)) if *seconds > 0 => config.idle_timeout = Some(*seconds as u64),
+                    ("idle_timeout", _) => log::warn!("ignoring idle_timeout in configuration, it should be a number of seconds"),
+                    ("layout", Value::String(layout)) if layout == "full" || layout == "floating" => config.floating = layout == "floating",
+                    ("layout", _) => log::warn!("ignoring layout in configuration, it should be \"full\" or \"floating\""),
+                    ("title", Value::String(title)) => config.title = Some(title.clone()),
+                    ("title", _) => log::warn!("ignoring title in configuration, it should be a string"),
+                    ("icons", Value::String(icons)) if Icons::parse(icons).is_some() => config.icons = Icons::parse(icons).unwrap_or_default(),
+                    ("icons", _) => log::warn!("ignoring icons in configuration, it should be \"nerd\", \"ascii\" or \"none\""),
+                    #[cfg(feature = "tui")]
+                    ("theme", Value::String(theme)) if Preset::parse(theme).is_some() => config.theme = Preset::parse(theme).unwrap_or_default(),
+                    #[cfg(not(feature = "tui"))]
+                    ("theme", _) => (),
+                    #[cfg(feature = "tui")]
+                    ("theme", _) => log::warn!("ignoring theme in configuration, it should be \"default\", \"high-contrast\" or \"color-blind\""),
+                    ("pin_sha256", Value::String(digest)) if digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit()) => config.pin_sha256 = Some(digest.to_lowercase()),
+                    ("pin_sha256", _) => log::warn!("ignoring pin_sha256 in configuration, it should be the 64 hexadecimal digits printed by sha256sum"),
+                    _ => log::warn!("ignoring unknown setting {:?} in [tui] in configuration", key),
+                }
+            },
+            "" => section.iter().for_each(|(key, _)| log::warn!("ignoring unknown setting {:?} in configuration", key)),
+            _ => log::warn!("ignoring unknown configuration section [{}]", name),
+        }
+    }
+
+    config
+}
+
+/// Read the `[kiosk]` section, which needs a default entry to do anything
+fn kiosk(section: &Section) -> Option<Kiosk> {
+    let mut default = None;
+    let mut countdown = DEFAULT_COUNTDOWN;
+
+    for (key, value) in section.iter() {
+        match (key.as_str(), value) {
+            ("default", Value::String(dest)) if !dest.is_empty() => default = Some(dest.clone()),
+            ("default", _) => log::warn!("ignoring default in [kiosk] in configuration, it should be the name or ID of an entry"),
+            ("countdown", Value::Integer(seconds)) if *seconds > 0 => countdown = *seconds as u64,
+            ("countdown", _) => log::warn!("ignoring countdown in [kiosk] in configuration, it should be a number of seconds"),
+            _ => log::warn!("ignoring unknown setting {:?} in [kiosk] in configuration", key),
+        }
+    }
+
+    if default.is_none() {
+        log::warn!("ignoring [kiosk] in configuration, it has no default entry");
+    }
+    default.map(|default| Kiosk { default, countdown })
+}
+
+/// Parse a quoted string starting at the beginning of `text`, returning it and the rest
+fn parse_string(text: &str) -> Result<(String, &str), String> {
+    let mut chars = text.char_indices().skip(1);
+    let mut result = String::new();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((result, &text[i + 1..])),
+            '\\' => match chars.next().map(|(_, c)| c) {
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some(c @ ('"' | '\\')) => result.push(c),
+                other => return Err(format!("unknown escape \\{}", other.map(String::from).unwrap_or_default())),
+            },
+            c => result.push(c),
+        }
+    }
+
+    Err(String::from("unterminated string"))
+}
+
+/// Parse a value at the beginning of `text`, returning it and the rest
+fn parse_value(text: &str) -> Result<(Value, &str), String> {
+    let text = text.trim_start();
+
+    if text.starts_with('"') {
+        let (s, rest) = parse_string(text)?;
+        return Ok((Value::String(s), rest));
+    }
+
+    if let Some(mut rest) = text.strip_prefix('[') {
+        let mut values = vec![];
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::Array(values), after));
+            }
+
+            let (value, after) = parse_value(rest)?;
+            values.push(value);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after;
+            } else if !rest.starts_with(']') {
+                return Err(String::from("expected ',' or ']' in array"));
+            }
+        }
+    }
+
+    let end = text.find([',', ']', ' ', '\t', '#']).unwrap_or(text.len());
+    let (word, rest) = text.split_at(end);
+    let value = match word {
+        "true" => Value::Boolean(true),
+        "false" => Value::Boolean(false),
+        _ => Value::Integer(word.replace('_', "").parse().map_err(|_| format!("invalid value {:?}", word))?),
+    };
+    Ok((value, rest))
+}
+
+/// Parse the configuration file format
+pub fn parse(text: &str) -> Result<Document, ConfigError> {
+    let mut document = Document::new();
+    let mut section = String::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let error = |reason: String| ConfigError { line: index + 1, reason };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            let name = header.split('#').next().unwrap_or_default().trim_end().strip_suffix(']')
+                .ok_or(error(String::from("expected ']' after section name")))?;
+            section = name.trim().to_string();
+            document.entry(section.clone()).or_default();
+            continue;
+        }
+
+        let (key, value) = line.split_once('=').ok_or(error(String::from("expected key = value")))?;
+        let key = key.trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(error(format!("invalid key {:?}", key)));
+        }
+
+        let (value, rest) = parse_value(value).map_err(error)?;
+        let rest = rest.trim();
+        if !rest.is_empty() && !rest.starts_with('#') {
+            return Err(error(format!("unexpected {:?} after value", rest)));
+        }
+
+        let entries = document.entry(section.clone()).or_default();
+        if entries.iter().any(|(k, _)| k == key) {
+            return Err(error(format!("duplicate key {:?}", key)));
+        }
+        entries.push((key.to_string(), value));
+    }
+
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sections_and_values() {
+        let document = parse(r#"
+# Top-level settings
+answer = 42
+
+[keys]
+reboot = "Enter"   # the default
+set_next = ["Space", "n"]
+enabled = true
+"#).expect("config should parse");
+
+        assert_eq!(document[""], vec![(String::from("answer"), Value::Integer(42))]);
+        let keys = &document["keys"];
+        assert_eq!(keys[0], (String::from("reboot"), Value::String(String::from("Enter"))));
+        assert_eq!(keys[1].1.strings(), Some(vec!["Space", "n"]));
+        assert_eq!(keys[2].1, Value::Boolean(true));
+    }
+
+    #[test]
+    fn reads_kiosk_settings() {
+        let config = from_document(&parse("[kiosk]\ndefault = \"Fedora\"\n").expect("config should parse"));
+        assert_eq!(config.kiosk, Some(Kiosk { default: String::from("Fedora"), countdown: DEFAULT_COUNTDOWN }));
+
+        let config = from_document(&parse("[kiosk]\ncountdown = 5\n").expect("config should parse"));
+        assert_eq!(config.kiosk, None);
+    }
+
+    #[test]
+    fn reads_reboot_settings() {
+        assert!(!from_document(&Document::new()).end_session);
+        assert!(from_document(&parse("[reboot]\nend_session = true\n").expect("config should parse")).end_session);
+        assert!(!from_document(&parse("[reboot]\nend_session = \"yes\"\n").expect("config should parse")).end_session);
+    }
+
+    #[test]
+    fn reads_daemon_settings() {
+        assert_eq!(from_document(&parse("[daemon]\ngroup = \"wheel\"\n").expect("config should parse")).daemon_group.as_deref(), Some("wheel"));
+        assert_eq!(from_document(&parse("[daemon]\ngroup = 10\n").expect("config should parse")).daemon_group, None);
+    }
+
+    #[test]
+    fn matches_protected_entries() {
+        let config = from_document(&parse("[list]\nprotected = [\"Windows Boot Manager\", \"0003\"]\n").expect("config should parse"));
+        let targets = crate::parse_boot_targets("Boot0000* Windows Boot Manager\nBoot0001* Fedora\nBoot0003* UEFI Shell\n");
+        let protected: Vec<bool> = targets.targets.iter().map(|target| config.is_protected(target)).collect();
+        assert_eq!(protected, vec![true, false, true]);
+    }
+
+    #[test]
+    fn applies_profile_over_plain_sections() {
+        let document = parse("[list]\ncollapse_duplicates = true\n[tui]\ntitle = \"home\"\nlayout = \"full\"\n\
+            [profile.work.tui]\ntitle = \"work\"\nidle_timeout = 60\n[profile.bench.kiosk]\ndefault = \"Fedora\"\n").expect("config should parse");
+
+        let config = from_document(&apply_profile(document.clone(), Some("work")));
+        assert_eq!(config.title.as_deref(), Some("work"));
+        assert_eq!(config.idle_timeout, Some(60));
+        assert!(config.collapse_duplicates);
+        assert_eq!(config.kiosk, None);
+
+        let config = from_document(&apply_profile(document, None));
+        assert_eq!(config.title.as_deref(), Some("home"));
+        assert_eq!(config.idle_timeout, None);
+    }
+
+    #[test]
+    fn overlays_user_file_on_system_file() {
+        let system = parse("[list]\nprotected = \"Windows Boot Manager\"\n[tui]\nidle_timeout = 60\ntitle = \"lab\"\n").expect("config should parse");
+        let user = parse("[tui]\nidle_timeout = 300\n").expect("config should parse");
+
+        let config = from_document(&overlay(system, user));
+        assert_eq!(config.protected, vec![String::from("Windows Boot Manager")]);
+        assert_eq!(config.idle_timeout, Some(300));
+        assert_eq!(config.title.as_deref(), Some("lab"));
+    }
+
+    #[test]
+    fn applies_environment_over_file() {
+        let document = parse("[tui]\ntitle = \"home\"\n").expect("config should parse");
+        let vars = [("REBOOT_TO_TUI_TITLE", "bench"), ("REBOOT_TO_TUI_IDLE_TIMEOUT", "60"), ("REBOOT_TO_KIOSK_DEFAULT", "\"0001\""),
+            ("REBOOT_TO_LIST_PROTECTED", "Windows Boot Manager"), ("REBOOT_TO_MOCK", "1"), ("HOME", "/root")];
+
+        let config = from_document(&apply_env(document, vars.into_iter().map(|(name, value)| (name.to_string(), value.to_string()))));
+        assert_eq!(config.title.as_deref(), Some("bench"));
+        assert_eq!(config.idle_timeout, Some(60));
+        assert_eq!(config.kiosk, Some(Kiosk { default: String::from("0001"), countdown: DEFAULT_COUNTDOWN }));
+        assert_eq!(config.protected, vec![String::from("Windows Boot Manager")]);
+    }
+
+    #[test]
+    fn reports_line_of_error() {
+        assert_eq!(parse("[keys]\nreboot = \"Enter").unwrap_err(), ConfigError { line: 2, reason: String::from("unterminated string") });
+        assert_eq!(parse("a = 1\na = 2").unwrap_err().line, 2);
+        assert_eq!(parse("[keys\n").unwrap_err().line, 1);
+        assert_eq!(parse("reboot\n").unwrap_err().reason, "expected key = value");
+    }
+}
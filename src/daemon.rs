@@ -0,0 +1,208 @@
+/***
+ * Daemon
+ *
+ * `--serve-socket` answers the JSON-RPC requests of `--serve-stdio`, see
+ * `rpc`, on a Unix socket, for any number of connections, so that a status
+ * bar widget can ask for the pending next entry without starting reboot-to
+ * and efibootmgr each time. The entries are only read again when the boot
+ * variables changed, see `cache`.
+ *
+ * Who may connect is decided by the socket's ownership: it belongs to the
+ * user running the daemon and only they may use it, or with `group` set in
+ * `[daemon]`, also members of that group. The socket is created under a
+ * temporary name and only moved in place once its permissions are set.
+ *
+ * Started by a systemd .socket unit, the daemon takes the socket systemd
+ * passes, whose ownership the unit sets, and exits after a minute without
+ * connections. systemd starts it again on the next one.
+ ***/
+use std::{
+    env, fs, io::{self, BufReader},
+    os::unix::{fs::PermissionsExt, io::{FromRawFd, RawFd}, net::{UnixListener, UnixStream}},
+    path::{Path, PathBuf},
+    process,
+    sync::{atomic::{AtomicUsize, Ordering}, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{config::Config, i18n::tr, rpc};
+
+/// Socket of a daemon running without a session, like a system service
+const SYSTEM_SOCKET: &str = "/run/reboot-to.sock";
+
+/// File descriptor of the first socket systemd passes
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Time without connections after which a socket-activated daemon exits
+const IDLE_EXIT: Duration = Duration::from_secs(60);
+
+/// Default socket: in the user's runtime directory, or else the system one
+pub fn default_path() -> PathBuf {
+    env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty())
+        .map(|dir| PathBuf::from(dir).join("reboot-to.sock"))
+        .unwrap_or(PathBuf::from(SYSTEM_SOCKET))
+}
+
+/// ID of a group in the contents of /etc/group
+fn group_id(groups: &str, name: &str) -> Option<u32> {
+    groups.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        match fields.as_slice() {
+            [group, _, gid, ..] if *group == name => gid.parse().ok(),
+            _ => None,
+        }
+    })
+}
+
+/// Number of sockets systemd passed to this process, like sd_listen_fds(3),
+/// from LISTEN_PID and LISTEN_FDS
+fn passed_sockets(listen_pid: Option<&str>, listen_fds: Option<&str>) -> usize {
+    // The variables may have been inherited from a process systemd started
+    match (listen_pid.and_then(|pid| pid.parse::<u32>().ok()), listen_fds.and_then(|fds| fds.parse().ok())) {
+        (Some(pid), Some(fds)) if pid == process::id() => fds,
+        _ => 0,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn close_on_exec(fd: RawFd) {
+    // SAFETY: only changes the flags of a file descriptor this process owns
+    unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+}
+
+#[cfg(not(target_os = "linux"))]
+fn close_on_exec(_fd: RawFd) {}
+
+/// The socket systemd passed, when started by a .socket unit
+fn activated() -> Option<UnixListener> {
+    let count = passed_sockets(env::var("LISTEN_PID").ok().as_deref(), env::var("LISTEN_FDS").ok().as_deref());
+    if count == 0 {
+        return None;
+    }
+    if count > 1 {
+        log::warn!("systemd passed {} sockets, only answering on the first", count);
+    }
+
+    // Not for commands run from here, like efibootmgr
+    for name in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        env::remove_var(name);
+    }
+    close_on_exec(LISTEN_FDS_START);
+
+    // SAFETY: systemd passes the listening socket as the first file descriptor after stderr, and nothing else uses it
+    Some(unsafe { UnixListener::from_raw_fd(LISTEN_FDS_START) })
+}
+
+/// Remove a socket left behind by a daemon that is no longer running
+fn remove_stale(path: &Path) -> io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    if UnixStream::connect(path).is_ok() {
+        return Err(io::Error::new(io::ErrorKind::AddrInUse, tr!("a daemon is already listening on {}", path.display())));
+    }
+    fs::remove_file(path)
+}
+
+/// Create the socket at `path`, only accessible to its owner and `group`
+fn listen(path: &Path, group: Option<&str>) -> io::Result<UnixListener> {
+    remove_stale(path)?;
+
+    let temporary = path.with_file_name(format!(".{}.{}", path.file_name().unwrap_or_default().to_string_lossy(), process::id()));
+    let listener = UnixListener::bind(&temporary)?;
+    let prepared = (|| {
+        let mode = match group {
+            Some(group) => {
+                let groups = fs::read_to_string("/etc/group")?;
+                let gid = group_id(&groups, group)
+                    .ok_or(io::Error::new(io::ErrorKind::NotFound, tr!("no group named {}", group)))?;
+                std::os::unix::fs::chown(&temporary, None, Some(gid))?;
+                0o660
+            }
+            None => 0o600,
+        };
+        fs::set_permissions(&temporary, fs::Permissions::from_mode(mode))?;
+        fs::rename(&temporary, path)
+    })();
+
+    if let Err(e) = prepared {
+        let _ = fs::remove_file(&temporary);
+        return Err(e);
+    }
+    Ok(listener)
+}
+
+/// Answer requests on the socket at `path`, or the one systemd passed, until
+/// the process is ended or, when started by systemd, idle
+pub fn serve(path: &Path, config: &Config) -> io::Result<()> {
+    let (listener, on_demand) = match activated() {
+        Some(listener) => {
+            log::info!("listening on the socket passed by systemd");
+            (listener, true)
+        }
+        None => {
+            let listener = listen(path, config.daemon_group.as_deref())?;
+            log::info!("listening on {}", path.display());
+            (listener, false)
+        }
+    };
+    let connections = AtomicUsize::new(0);
+    let last_active = Mutex::new(Instant::now());
+
+    thread::scope(|scope| {
+        if on_demand {
+            scope.spawn(|| loop {
+                thread::sleep(Duration::from_secs(1));
+                let idle = last_active.lock().expect("activity lock should not be poisoned").elapsed();
+                if connections.load(Ordering::SeqCst) == 0 && idle >= IDLE_EXIT {
+                    log::info!("no connections for {} seconds, exiting", IDLE_EXIT.as_secs());
+                    process::exit(0);
+                }
+            });
+        }
+
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn!("could not accept a connection: {}", e);
+                    continue;
+                }
+            };
+            connections.fetch_add(1, Ordering::SeqCst);
+            let (connections, last_active) = (&connections, &last_active);
+            scope.spawn(move || {
+                let answered = stream.try_clone().and_then(|reader| rpc::serve(BufReader::new(reader), &stream, config));
+                if let Err(e) = answered {
+                    log::debug!("connection ended: {}", e);
+                }
+                *last_active.lock().expect("activity lock should not be poisoned") = Instant::now();
+                connections.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_group_ids() {
+        let groups = "root:x:0:\nwheel:x:10:alice,bob\n# comment\nreboot:x:975:\n";
+        assert_eq!(group_id(groups, "wheel"), Some(10));
+        assert_eq!(group_id(groups, "reboot"), Some(975));
+        assert_eq!(group_id(groups, "admin"), None);
+    }
+
+    #[test]
+    fn counts_sockets_passed_by_systemd() {
+        let own = process::id().to_string();
+        assert_eq!(passed_sockets(Some(&own), Some("1")), 1);
+        assert_eq!(passed_sockets(Some("1"), Some("1")), 0);
+        assert_eq!(passed_sockets(Some(&own), None), 0);
+        assert_eq!(passed_sockets(None, None), 0);
+    }
+}
@@ -0,0 +1,127 @@
+/***
+ * Grace period
+ *
+ * `--delay` counts down before `--reboot-to` reboots, as a last chance to
+ * stop a reboot started from a script or a remote shell. Where Ctrl+C can be
+ * caught, BootNext is set before the countdown, so that a failure shows
+ * right away, and put back when Ctrl+C cuts the countdown short. Elsewhere
+ * BootNext is only set once the countdown is over, and Ctrl+C ends the
+ * process with nothing changed.
+ ***/
+use std::{
+    io::{stderr, stdout, IsTerminal, Write},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{i18n::tr, logger::{self, say}};
+
+/// How often the countdown checks for Ctrl+C
+const STEP: Duration = Duration::from_millis(100);
+
+/// Set when Ctrl+C is pressed, once it is caught
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Parse a delay like "10s", "2m", "1h" or "30", which is in seconds
+pub fn parse(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let (number, unit) = match text.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => text.split_at(index),
+        None => (text, "s"),
+    };
+    let multiplier = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        _ => return Err(tr!("\"{}\" is not a delay, give a number of seconds or one like 10s, 2m or 1h", text)),
+    };
+    let number: u64 = number.parse().map_err(|_| tr!("\"{}\" is not a delay, give a number of seconds or one like 10s, 2m or 1h", text))?;
+
+    Ok(Duration::from_secs(number.saturating_mul(multiplier)))
+}
+
+#[cfg(target_os = "linux")]
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Catch Ctrl+C instead of being ended by it, returning whether that worked
+#[cfg(target_os = "linux")]
+pub fn catch_interrupt() -> bool {
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+    let previous = unsafe { libc::signal(libc::SIGINT, on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t) };
+    previous != libc::SIG_ERR
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn catch_interrupt() -> bool {
+    false
+}
+
+/// Whether Ctrl+C has been pressed since it was caught
+fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Write a countdown line over the previous one, where messages go
+fn overwrite(text: &str) {
+    if logger::quiet() {
+        eprint!("\r{}\x1b[K", text);
+    } else {
+        print!("\r{}\x1b[K", text);
+        let _ = stdout().flush();
+    }
+}
+
+/// Count down `delay` before rebooting to `name`, returning false when Ctrl+C cut it short
+pub fn count_down(delay: Duration, name: &str) -> bool {
+    let end = Instant::now() + delay;
+    // Redirected output gets the first line only, not one per second
+    let on_terminal = if logger::quiet() { stderr().is_terminal() } else { stdout().is_terminal() };
+    let mut shown = None;
+
+    let completed = loop {
+        if interrupted() {
+            break false;
+        }
+        let left = end.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            break true;
+        }
+
+        let seconds = left.as_secs() + u64::from(left.subsec_nanos() > 0);
+        if shown != Some(seconds) && (on_terminal || shown.is_none()) {
+            let text = tr!("Rebooting to \"{}\" in {} s, press Ctrl+C to cancel", name, seconds);
+            if on_terminal {
+                overwrite(&text);
+            } else {
+                say!("{}", text);
+            }
+            shown = Some(seconds);
+        }
+        thread::sleep(STEP.min(left));
+    };
+
+    if on_terminal && shown.is_some() {
+        say!();
+    }
+    completed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_delays() {
+        assert_eq!(parse("10s"), Ok(Duration::from_secs(10)));
+        assert_eq!(parse("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse("1h"), Ok(Duration::from_secs(3600)));
+        assert_eq!(parse("0"), Ok(Duration::ZERO));
+        assert!(parse("10 minutes").is_err());
+        assert!(parse("s").is_err());
+        assert!(parse("-5").is_err());
+    }
+}
@@ -0,0 +1,56 @@
+/***
+ * Demo mode
+ *
+ * With --demo or REBOOT_TO_MOCK set, boot entries come from a canned
+ * `efibootmgr -v` output and no system command is run, so the TUI can be tried
+ * on machines without UEFI or root access.
+ ***/
+use std::{fs, io, process::{Command, ExitStatus, Output}, sync::OnceLock};
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+#[cfg(windows)]
+use std::os::windows::process::ExitStatusExt;
+
+/// Output of `efibootmgr -v` shown in demo mode
+const CANNED_OUTPUT: &str = "BootCurrent: 0001
+Timeout: 3 seconds
+BootOrder: 0001,0000,0004,0002,0003
+Boot0000* Windows Boot Manager\tHD(1,GPT,4f3c2a1b-9e8d-4c7b-a6f5-0e1d2c3b4a59,0x800,0x82000)/File(\\EFI\\Microsoft\\Boot\\bootmgfw.efi)WINDOWS.........
+Boot0001* ubuntu\tHD(1,GPT,4f3c2a1b-9e8d-4c7b-a6f5-0e1d2c3b4a59,0x800,0x82000)/File(\\EFI\\ubuntu\\shimx64.efi)
+Boot0002* UEFI PXEv4 (MAC:3C7C3F1A2B4D)\tPciRoot(0x0)/Pci(0x1c,0x0)/Pci(0x0,0x0)/MAC(3c7c3f1a2b4d,0)/IPv4(0.0.0.0,0,DHCP)
+Boot0003* UEFI Shell\tFvVol(7cb8bdc9-f8eb-4f34-aaea-3ee4af6516a1)/FvFile(c57ad6b7-0515-40a8-9d21-551652854e37)
+Boot0004* Fedora\tHD(2,GPT,8a7b6c5d-4e3f-4a1b-9c8d-7e6f5a4b3c2d,0x82800,0x100000)/File(\\EFI\\fedora\\shimx64.efi)
+Boot000A  Arch Linux (old kernel)\tHD(1,GPT,4f3c2a1b-9e8d-4c7b-a6f5-0e1d2c3b4a59,0x800,0x82000)/File(\\EFI\\arch\\grubx64.efi)
+";
+
+/// `efibootmgr -v` output to use, set when demo mode is enabled
+static OUTPUT: OnceLock<String> = OnceLock::new();
+
+/// Enable demo mode. `mock` is the value of REBOOT_TO_MOCK: a file containing
+/// `efibootmgr -v` output, or anything else for the canned entries.
+pub fn enable(mock: Option<&str>) -> io::Result<()> {
+    let output = match mock {
+        Some(path) if fs::metadata(path).is_ok_and(|meta| meta.is_file()) => fs::read_to_string(path)?,
+        _ => String::from(CANNED_OUTPUT),
+    };
+
+    let _ = OUTPUT.set(output);
+    Ok(())
+}
+
+pub fn is_enabled() -> bool {
+    OUTPUT.get().is_some()
+}
+
+/// Pretend to run a command: listing entries returns the demo entries, and
+/// everything else succeeds without output
+pub fn output(command: &Command) -> Output {
+    let listing = command.get_program() == "efibootmgr" && command.get_args().eq(["-v"]);
+    log::debug!("demo mode, not running {}", crate::sudo::command_line(command));
+
+    Output {
+        status: ExitStatus::from_raw(0),
+        stdout: if listing { OUTPUT.get().cloned().unwrap_or_default().into_bytes() } else { vec![] },
+        stderr: vec![],
+    }
+}
@@ -0,0 +1,209 @@
+/***
+ * Block devices
+ *
+ * Resolves the partition GUIDs in device paths to block devices, and names
+ * the disk they are on by its model, from sysfs or else udev's database, so
+ * that an entry can be told apart by more than a GUID. Which partitions are
+ * present at all is also read from the partition tables, as without udev
+ * there is no /dev/disk/by-partuuid to go by.
+ ***/
+use std::{collections::HashSet, fs, io::{Read, Seek, SeekFrom}, path::{Path, PathBuf}};
+
+use crate::verify;
+
+/// Properties in the contents of a udev database file, from its `E:KEY=value` lines
+fn parse_udev(text: &str) -> impl Iterator<Item = (&str, &str)> {
+    text.lines().filter_map(|line| line.strip_prefix("E:")?.split_once('='))
+}
+
+/// A udev property of a block device, like ID_PART_ENTRY_TYPE
+pub fn udev_property(device: &Path, key: &str) -> Option<String> {
+    let name = device.file_name()?;
+    let numbers = fs::read_to_string(Path::new("/sys/class/block").join(name).join("dev")).ok()?;
+
+    fs::read_to_string(format!("/run/udev/data/b{}", numbers.trim())).ok()
+        .and_then(|text| parse_udev(&text).find(|(k, _)| *k == key).map(|(_, value)| value.to_string()))
+}
+
+/// Model of a disk, like "Samsung SSD 980 PRO 1TB"
+pub fn model(disk: &Path) -> Option<String> {
+    let name = disk.file_name()?;
+    fs::read_to_string(Path::new("/sys/class/block").join(name).join("device").join("model")).ok()
+        .map(|model| model.trim().to_string())
+        .filter(|model| !model.is_empty())
+        .or_else(|| udev_property(disk, "ID_MODEL").map(|model| model.replace('_', " ")))
+}
+
+/// A partition and the model of its disk, like "Samsung SSD 980 PRO 1TB,
+/// nvme0n1p1"
+pub fn describe_partition(partition: &Path) -> Option<String> {
+    let name = partition.file_name()?.to_string_lossy().into_owned();
+
+    match verify::parent_disk(partition).and_then(|disk| model(&disk)) {
+        Some(model) => Some(format!("{}, {}", model, name)),
+        None => Some(name),
+    }
+}
+
+/// Partitions on a disk, like `/dev/nvme0n1p2`
+pub fn partitions(disk: &Path) -> Vec<PathBuf> {
+    let Some(name) = disk.file_name() else {
+        return vec![];
+    };
+
+    let mut partitions: Vec<PathBuf> = fs::read_dir(Path::new("/sys/class/block").join(name)).into_iter().flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join("partition").exists())
+        .map(|entry| Path::new("/dev").join(entry.file_name()))
+        .collect();
+    partitions.sort();
+    partitions
+}
+
+/// Whether a partition is on a removable disk, like a USB stick or an SD
+/// card. USB hard disks don't count as removable to the kernel, but do here.
+pub fn is_removable(partition: &Path) -> bool {
+    let removable = verify::parent_disk(partition)
+        .and_then(|disk| fs::read_to_string(Path::new("/sys/class/block").join(disk.file_name()?).join("removable")).ok())
+        .is_some_and(|removable| removable.trim() == "1");
+    removable || udev_property(partition, "ID_BUS").is_some_and(|bus| bus == "usb")
+}
+
+/// Names of the block devices there are, to notice disks being plugged in or pulled
+pub fn block_devices() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir("/sys/class/block").into_iter().flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Signature at the start of a GPT header
+const GPT_SIGNATURE: &[u8] = b"EFI PART";
+
+/// Largest partition entry array read, 128 entries of 128 bytes being usual
+const MAX_GPT_ENTRIES_SIZE: u64 = 1 << 20;
+
+/// GUID as stored on disk, with its first three fields little-endian, in lowercase
+fn format_guid(bytes: &[u8]) -> String {
+    format!("{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{}",
+        bytes[3], bytes[2], bytes[1], bytes[0], bytes[5], bytes[4], bytes[7], bytes[6], bytes[8], bytes[9],
+        bytes[10..16].iter().map(|byte| format!("{:02x}", byte)).collect::<String>())
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    bytes.get(offset..offset + 4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Number and size of the partition entries in a GPT header, None if it isn't one
+fn gpt_entries(header: &[u8]) -> Option<(usize, usize)> {
+    if header.get(0..8)? != GPT_SIGNATURE {
+        return None;
+    }
+    let count = u32_at(header, 80)? as usize;
+    let size = u32_at(header, 84)? as usize;
+    (size >= 128).then_some((count, size))
+}
+
+/// Offset and length in bytes of the partition entry array of a GPT header,
+/// None if they are out of range, as in a corrupt or crafted header
+fn gpt_entries_span(header: &[u8], count: usize, size: usize, sector_size: u64) -> Option<(u64, usize)> {
+    let start = u64::from_le_bytes(header.get(72..80)?.try_into().ok()?).checked_mul(sector_size)?;
+    let length = u64::try_from(count).ok()?.checked_mul(u64::try_from(size).ok()?)?.min(MAX_GPT_ENTRIES_SIZE);
+    Some((start, usize::try_from(length).ok()?))
+}
+
+/// Unique GUIDs of the used partitions in a GPT partition entry array
+fn gpt_partition_guids(entries: &[u8], count: usize, size: usize) -> Vec<String> {
+    entries.chunks_exact(size).take(count)
+        // Unused entries have a zero partition type
+        .filter(|entry| entry[0..16].iter().any(|byte| *byte != 0))
+        .map(|entry| format_guid(&entry[16..32]))
+        .collect()
+}
+
+/// Partition GUIDs in the GPT of a disk, None if it can't be read or has none
+fn read_gpt(disk: &str) -> Option<Vec<String>> {
+    let sector_size: u64 = fs::read_to_string(format!("/sys/block/{}/queue/logical_block_size", disk)).ok()?.trim().parse().ok()?;
+    let mut device = fs::File::open(Path::new("/dev").join(disk)).ok()?;
+
+    let mut header = vec![0; 92];
+    device.seek(SeekFrom::Start(sector_size)).ok()?;
+    device.read_exact(&mut header).ok()?;
+    let (count, size) = gpt_entries(&header)?;
+
+    let (start, length) = gpt_entries_span(&header, count, size, sector_size)?;
+    let mut entries = vec![0; length];
+    device.seek(SeekFrom::Start(start)).ok()?;
+    device.read_exact(&mut entries).ok()?;
+
+    Some(gpt_partition_guids(&entries, count, size))
+}
+
+/// GUIDs of the partitions on the disks that are present, or None if they
+/// can't be listed, without udev and root access
+pub fn present_partuuids() -> Option<HashSet<String>> {
+    let by_partuuid = fs::read_dir("/dev/disk/by-partuuid").ok().map(|entries| entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_lowercase())
+        .collect::<Vec<String>>());
+
+    // Partition tables can only be read as root, but also show disks udev doesn't know about
+    let tables: Vec<Vec<String>> = fs::read_dir("/sys/block").into_iter().flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|disk| !["loop", "ram", "zram"].iter().any(|prefix| disk.starts_with(prefix)))
+        .filter_map(|disk| read_gpt(&disk))
+        .collect();
+
+    if by_partuuid.is_none() && tables.is_empty() {
+        return None;
+    }
+    Some(by_partuuid.into_iter().flatten().chain(tables.into_iter().flatten()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_udev_properties() {
+        let text = "S:disk/by-partuuid/4f3c2a1b-9e8d-4c7b-a6f5-0e1d2c3b4a59\n\
+            I:1234567\n\
+            E:ID_PART_ENTRY_TYPE=c12a7328-f81f-11d2-ba4b-00a0c93ec93b\n\
+            E:ID_MODEL=Samsung_SSD_980_PRO_1TB\n\
+            G:systemd\n";
+
+        let properties: Vec<(&str, &str)> = parse_udev(text).collect();
+        assert_eq!(properties, [("ID_PART_ENTRY_TYPE", "c12a7328-f81f-11d2-ba4b-00a0c93ec93b"), ("ID_MODEL", "Samsung_SSD_980_PRO_1TB")]);
+    }
+
+    #[test]
+    fn reads_gpt_partition_guids() {
+        let mut header = vec![0; 92];
+        header[0..8].copy_from_slice(GPT_SIGNATURE);
+        header[80..84].copy_from_slice(&3u32.to_le_bytes());
+        header[84..88].copy_from_slice(&128u32.to_le_bytes());
+        assert_eq!(gpt_entries(&header), Some((3, 128)));
+        assert_eq!(gpt_entries(&[0; 92]), None);
+
+        header[72..80].copy_from_slice(&2u64.to_le_bytes());
+        assert_eq!(gpt_entries_span(&header, 3, 128, 512), Some((1024, 384)));
+        assert_eq!(gpt_entries_span(&header, u32::MAX as usize, u32::MAX as usize, 4096), Some((8192, MAX_GPT_ENTRIES_SIZE as usize)));
+        header[72..80].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(gpt_entries_span(&header, 3, 128, 512), None);
+
+        // An ESP, an unused entry and a Linux partition
+        let mut entries = vec![0; 3 * 128];
+        entries[0..16].copy_from_slice(&[0x28, 0x73, 0x2a, 0xc1, 0x1f, 0xf8, 0xd2, 0x11, 0xba, 0x4b, 0x00, 0xa0, 0xc9, 0x3e, 0xc9, 0x3b]);
+        entries[16..32].copy_from_slice(&[0x1b, 0x2a, 0x3c, 0x4f, 0x8d, 0x9e, 0x7b, 0x4c, 0xa6, 0xf5, 0x0e, 0x1d, 0x2c, 0x3b, 0x4a, 0x59]);
+        entries[256] = 0xaf;
+        entries[272..288].copy_from_slice(&[0x5d, 0x6c, 0x7b, 0x8a, 0x3f, 0x4e, 0x1b, 0x4a, 0x9c, 0x8d, 0x7e, 0x6f, 0x5a, 0x4b, 0x3c, 0x2d]);
+
+        assert_eq!(gpt_partition_guids(&entries, 3, 128), [
+            "4f3c2a1b-9e8d-4c7b-a6f5-0e1d2c3b4a59",
+            "8a7b6c5d-4e3f-4a1b-9c8d-7e6f5a4b3c2d",
+        ]);
+    }
+}
@@ -0,0 +1,163 @@
+/***
+ * Self-check
+ *
+ * `reboot-to doctor` goes through everything a reboot depends on, from the
+ * firmware to the efibootmgr output, and prints each check as passed or
+ * failed with a hint on how to fix it, so that a broken setup can be told
+ * apart from a bug in reboot-to.
+ ***/
+use std::{fs, io, path::{Path, PathBuf}, process::Command};
+
+use crate::{firmware::EFI_DIR, i18n::tr, parser, process::{self, find_program}, reboot, sudo};
+
+/// Where efivarfs is normally mounted
+const EFIVARS_DIR: &str = "/sys/firmware/efi/efivars";
+
+/// Outcome of one check
+pub struct Check {
+    /// What was checked
+    pub name: &'static str,
+    pub passed: bool,
+    /// What was found
+    pub detail: String,
+    /// How to fix it, for failed checks
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Check {
+        Check { name, passed: true, detail: detail.into(), hint: None }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Check {
+        Check { name, passed: false, detail: detail.into(), hint: Some(hint.into()) }
+    }
+}
+
+/// Mount options of efivarfs in the contents of /proc/self/mounts
+fn efivarfs_options(mounts: &str) -> Option<Vec<&str>> {
+    mounts.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            [_, target, "efivarfs", options, ..] if *target == EFIVARS_DIR => Some(options.split(',').collect()),
+            _ => None,
+        }
+    })
+}
+
+fn check_uefi() -> Check {
+    if Path::new(EFI_DIR).is_dir() {
+        Check::pass(tr!("UEFI firmware"), tr!("booted in UEFI mode"))
+    } else {
+        Check::fail(tr!("UEFI firmware"), tr!("{} does not exist, the system was booted in legacy BIOS mode", EFI_DIR),
+            tr!("Boot entries only exist with UEFI. Enable UEFI boot in the firmware settings, which may need the OS to be reinstalled."))
+    }
+}
+
+fn check_efivars(mounts: Option<&str>) -> Vec<Check> {
+    let options = mounts.and_then(efivarfs_options);
+    let populated = fs::read_dir(EFIVARS_DIR).is_ok_and(|mut entries| entries.next().is_some());
+
+    let mounted = match (&options, populated) {
+        (Some(_), _) => Check::pass(tr!("efivarfs mounted"), tr!("mounted on {}", EFIVARS_DIR)),
+        (None, true) => Check::pass(tr!("efivarfs mounted"), tr!("{} has variables", EFIVARS_DIR)),
+        (None, false) => Check::fail(tr!("efivarfs mounted"), tr!("nothing is mounted on {}", EFIVARS_DIR),
+            tr!("Mount it with: mount -t efivarfs efivarfs {}", EFIVARS_DIR)),
+    };
+
+    let writable = match &options {
+        Some(options) if options.contains(&"ro") => Check::fail(tr!("efivarfs writable"), tr!("mounted read-only"),
+            tr!("Remount it read-write with: mount -o remount,rw {}", EFIVARS_DIR)),
+        Some(_) => Check::pass(tr!("efivarfs writable"), tr!("mounted read-write")),
+        None => Check::fail(tr!("efivarfs writable"), tr!("not mounted"), tr!("Mount efivarfs first")),
+    };
+
+    vec![mounted, writable]
+}
+
+/// Run `efibootmgr -v`, and check both that it runs and that its output can be read
+fn check_efibootmgr() -> Vec<Check> {
+    let name = tr!("efibootmgr runs");
+    let mut command = Command::new("efibootmgr");
+    command.arg("-v");
+    let output = process::output(&mut command, None);
+    sudo::log_output(&command, &output);
+
+    let output = match output {
+        Ok(output) if output.status.success() => output,
+        Ok(output) if sudo::is_permission_denied(&output) => return vec![Check::fail(name, tr!("permission denied"),
+            tr!("Run reboot-to as root, or with sudo"))],
+        Ok(output) => return vec![Check::fail(name, tr!("failed with {}: {}", output.status, String::from_utf8_lossy(&output.stderr).trim()),
+            tr!("efibootmgr usually fails like this when the firmware variables can't be read, see the checks above"))],
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return vec![Check::fail(name, tr!("not found on PATH"),
+            tr!("Install the efibootmgr package of your distribution"))],
+        Err(e) => return vec![Check::fail(name, e.to_string(), tr!("Check that efibootmgr can be run, and --command-timeout if it timed out"))],
+    };
+
+    let raw = String::from_utf8_lossy(&output.stdout);
+    let parsed = parser::parse(&raw);
+    let readable = match parsed.errors.first() {
+        None if parsed.entries.is_empty() => Check::fail(tr!("efibootmgr output understood"), tr!("no boot entries found"),
+            tr!("The firmware has no boot entries, or efibootmgr printed them in an unexpected way. Please report it with the output of efibootmgr -v")),
+        None => Check::pass(tr!("efibootmgr output understood"), tr!("{} boot entries", parsed.entries.len())),
+        Some(error) => Check::fail(tr!("efibootmgr output understood"), tr!("{} lines could not be read, the first: {}", parsed.errors.len(), error),
+            tr!("Please report it with the output of efibootmgr -v")),
+    };
+
+    vec![Check::pass(name, tr!("found at {}", find_program("efibootmgr").unwrap_or(PathBuf::from("efibootmgr")).display())), readable]
+}
+
+fn check_shutdown() -> Check {
+    if cfg!(windows) {
+        return match find_program("shutdown.exe") {
+            Some(path) => Check::pass(tr!("shutdown available"), path.display().to_string()),
+            None => Check::fail(tr!("shutdown available"), tr!("not found on PATH"), tr!("reboot-to reboots with shutdown /r /t 0")),
+        };
+    }
+
+    match reboot::command() {
+        Some((path, args)) => Check::pass(tr!("shutdown available"), format!("{} {}", path.display(), args.join(" ")).trim_end().to_string()),
+        None if cfg!(target_os = "linux") => Check::pass(tr!("shutdown available"),
+            tr!("neither shutdown, systemctl nor reboot found, reboot(2) is used instead, which needs root")),
+        None => Check::fail(tr!("shutdown available"), tr!("neither shutdown, systemctl nor reboot found on PATH"),
+            tr!("reboot-to reboots with shutdown -r now, install the package providing it, e.g. systemd-sysv")),
+    }
+}
+
+/// Run all checks that apply to this system
+pub fn run() -> Vec<Check> {
+    let mut checks = vec![];
+    if cfg!(target_os = "linux") {
+        checks.push(check_uefi());
+        checks.extend(check_efivars(fs::read_to_string("/proc/self/mounts").ok().as_deref()));
+    }
+    if cfg!(not(any(windows, target_os = "macos"))) {
+        checks.extend(check_efibootmgr());
+    }
+    checks.push(check_shutdown());
+    checks
+}
+
+/// Print the checks, with hints under the failed ones
+pub fn print(checks: &[Check]) {
+    for check in checks {
+        let status = if check.passed { tr!("ok") } else { tr!("FAIL") };
+        println!("{:<5} {}: {}", status, check.name, check.detail);
+        if let Some(hint) = &check.hint {
+            println!("      {}", hint);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_efivarfs_options() {
+        let mounts = "sysfs /sys sysfs rw,nosuid,nodev,noexec,relatime 0 0\n\
+            efivarfs /sys/firmware/efi/efivars efivarfs ro,nosuid,nodev,noexec,relatime 0 0\n";
+        assert_eq!(efivarfs_options(mounts), Some(vec!["ro", "nosuid", "nodev", "noexec", "relatime"]));
+        assert_eq!(efivarfs_options("sysfs /sys sysfs rw 0 0\n"), None);
+    }
+}
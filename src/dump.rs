@@ -0,0 +1,165 @@
+/***
+ * Debug dump
+ *
+ * `reboot-to debug-dump` prints what is needed to look into a bug report in
+ * one go: the version, the system, the raw `efibootmgr -v` output and what
+ * the parser made of it. Details that identify the machine or its network,
+ * like MAC and IP addresses, partition GUIDs and the hostname, are masked.
+ ***/
+use std::{env, fs, path::{Path, PathBuf}, process::Command};
+
+use crate::{config, demo, efivar, firmware, hostname, parser, process};
+
+/// Mask letters and digits, keeping the punctuation the parser may trip over
+fn mask(text: &str) -> String {
+    text.chars().map(|c| if c.is_ascii_alphanumeric() { 'x' } else { c }).collect()
+}
+
+/// Mask arguments of all `node(...)` device path nodes, picked by their
+/// position and the arguments before them
+fn mask_node(text: &str, node: &str, secret: impl Fn(&[&str]) -> bool) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(node) {
+        let (before, after) = rest.split_at(start + node.len());
+        result.push_str(before);
+        let Some(end) = after.find(')') else {
+            rest = after;
+            break;
+        };
+
+        let args: Vec<&str> = after[..end].split(',').collect();
+        let masked: Vec<String> = args.iter().enumerate()
+            .map(|(index, arg)| if secret(&args[..=index]) { mask(arg) } else { arg.to_string() })
+            .collect();
+        result.push_str(&masked.join(","));
+        rest = &after[end..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Mask the hexadecimal digits after every `MAC:`, as in "UEFI PXEv4 (MAC:3C7C3F1A2B4D)"
+fn mask_mac_labels(text: &str) -> String {
+    let mut parts = text.split("MAC:");
+    let mut result = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        let end = part.find(|c: char| !c.is_ascii_hexdigit()).unwrap_or(part.len());
+        result.push_str("MAC:");
+        result.push_str(&mask(&part[..end]));
+        result.push_str(&part[end..]);
+    }
+    result
+}
+
+/// Mask details identifying the machine in efibootmgr output, or anything containing it
+pub fn redact(text: &str, hostname: &str) -> String {
+    // Partition GUIDs and MBR signatures
+    let text = mask_node(text, "HD(", |args| args.len() == 3 && matches!(args[1], "GPT" | "MBR"));
+    let text = mask_node(&text, "MAC(", |args| args.len() == 1);
+    let text = mask_node(&text, "IPv4(", |args| args.last().is_some_and(|arg| arg.contains(|c: char| c.is_ascii_digit())));
+    let text = mask_node(&text, "IPv6(", |args| args.last().is_some_and(|arg| arg.contains(|c: char| c.is_ascii_digit())));
+    let text = mask_node(&text, "Uri(", |_| true);
+    let text = mask_mac_labels(&text);
+
+    match hostname {
+        "" | "localhost" => text,
+        hostname => replace_word(&text, hostname, "<hostname>"),
+    }
+}
+
+/// Replace `word` where it isn't part of a longer word, so that a short
+/// hostname like "vm" leaves "nvme" alone
+fn replace_word(text: &str, word: &str, with: &str) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(word) {
+        let end = start + word.len();
+        let joined = rest[..start].chars().next_back().is_some_and(char::is_alphanumeric)
+            || rest[end..].chars().next().is_some_and(char::is_alphanumeric);
+        result.push_str(&rest[..start]);
+        result.push_str(if joined { word } else { with });
+        rest = &rest[end..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Value of a key in /etc/os-release
+fn os_release(key: &str) -> Option<String> {
+    let text = fs::read_to_string("/etc/os-release").ok()?;
+    text.lines()
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+        .map(|value| value.trim_matches('"').to_string())
+}
+
+/// First line of a command's output, or why it could not be run
+fn first_line(command: &mut Command) -> String {
+    match process::output(command, None) {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).lines().next().unwrap_or_default().to_string(),
+        Err(e) => e.to_string(),
+    }
+}
+
+/// The whole dump, redacted
+pub fn collect() -> String {
+    let mut dump = String::new();
+    let mut line = |text: String| {
+        dump.push_str(&text);
+        dump.push('\n');
+    };
+
+    line(String::from("== reboot-to debug dump =="));
+    line(format!("Version: reboot-to {}", env!("CARGO_PKG_VERSION")));
+    line(format!("Platform: {} {}", env::consts::OS, env::consts::ARCH));
+    line(format!("Distribution: {}", os_release("PRETTY_NAME").unwrap_or_else(|| String::from("unknown"))));
+    line(format!("Kernel: {}", fs::read_to_string("/proc/sys/kernel/osrelease").map(|text| text.trim().to_string()).unwrap_or_else(|_| String::from("unknown"))));
+    line(format!("Firmware: {}", firmware::read().describe().unwrap_or_else(|| String::from("unknown"))));
+    line(format!("Secure Boot: {}", efivar::secure_boot().map(|state| state.describe()).unwrap_or("unknown")));
+    line(format!("Demo mode: {}", demo::is_enabled()));
+    line(format!("efibootmgr version: {}", first_line(Command::new("efibootmgr").arg("--version"))));
+    // The home directory gives away the user name
+    let home = env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+    line(format!("Configuration: {}", config::path().map(|path| format!("{} ({})",
+        path.strip_prefix(&home).map(|path| Path::new("~").join(path)).unwrap_or(path.clone()).display(),
+        if path.exists() { "exists" } else { "missing" })).unwrap_or_default()));
+    line(format!("Terminal: TERM={} LANG={}", env::var("TERM").unwrap_or_default(), env::var("LANG").unwrap_or_default()));
+
+    let raw = match process::output(Command::new("efibootmgr").arg("-v"), None) {
+        Ok(output) => {
+            line(format!("\n== efibootmgr -v ({}) ==", output.status));
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if !stderr.trim().is_empty() {
+                line(format!("stderr: {}", stderr.trim()));
+            }
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        }
+        Err(e) => {
+            line(format!("\n== efibootmgr -v (could not run: {}) ==", e));
+            String::new()
+        }
+    };
+    line(raw.trim_end().to_string());
+
+    line(String::from("\n== Parsed =="));
+    line(format!("{:#?}", parser::parse(&raw)));
+
+    redact(&dump, &hostname())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_identifying_details() {
+        let raw = "Boot0000* ubuntu\tHD(1,GPT,4f3c2a1b-9e8d-4c7b-a6f5-0e1d2c3b4a59,0x800,0x82000)/File(\\EFI\\ubuntu\\shimx64.efi)\n\
+            Boot0002* UEFI PXEv4 (MAC:3C7C3F1A2B4D)\tPciRoot(0x0)/MAC(3c7c3f1a2b4d,0)/IPv4(192.168.1.20,0,Static)\n\
+            Boot0003* ubuntu on buildhost, not buildhosts\n";
+
+        assert_eq!(redact(raw, "buildhost"),
+            "Boot0000* ubuntu\tHD(1,GPT,xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx,0x800,0x82000)/File(\\EFI\\ubuntu\\shimx64.efi)\n\
+            Boot0002* UEFI PXEv4 (MAC:xxxxxxxxxxxx)\tPciRoot(0x0)/MAC(xxxxxxxxxxxx,0)/IPv4(xxx.xxx.x.xx,x,Static)\n\
+            Boot0003* ubuntu on <hostname>, not buildhosts\n");
+    }
+}
@@ -0,0 +1,103 @@
+/***
+ * Duplicate entries
+ *
+ * Firmware and OS updates tend to add an entry again instead of reusing the
+ * old one, leaving several entries with the same device path. Of each set of
+ * duplicates one entry is kept: the booted one, else the pending next one,
+ * else the first in BootOrder, else the one with the lowest ID.
+ ***/
+use crate::{BootTarget, BootTargets};
+
+/// Entries sharing a device path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    /// Entry to keep
+    pub keep: u16,
+    /// The other entries, which can be deleted
+    pub extras: Vec<u16>,
+}
+
+impl Group {
+    pub fn contains(&self, id: u16) -> bool {
+        self.keep == id || self.extras.contains(&id)
+    }
+}
+
+/// Sort key preferring the entry to keep
+fn preference(targets: &BootTargets, target: &BootTarget) -> (bool, bool, usize, u16) {
+    let order = targets.order.iter().position(|id| *id == target.id).unwrap_or(usize::MAX);
+    (targets.current != Some(target.id), targets.next != Some(target.id), order, target.id)
+}
+
+/// Find sets of entries with the same device path
+pub fn find(targets: &BootTargets) -> Vec<Group> {
+    let mut groups: Vec<Vec<&BootTarget>> = vec![];
+
+    for target in targets.targets.iter() {
+        let Some(path) = target.device_path.as_ref() else {
+            continue;
+        };
+
+        let existing = groups.iter_mut().find(|group| group[0].device_path.as_ref().is_some_and(|p| p.as_str() == path.as_str()));
+        match existing {
+            Some(group) => group.push(target),
+            None => groups.push(vec![target]),
+        }
+    }
+
+    groups.into_iter()
+        .filter(|group| group.len() > 1)
+        .map(|mut group| {
+            group.sort_by_key(|target| preference(targets, target));
+            Group { keep: group[0].id, extras: group[1..].iter().map(|target| target.id).collect() }
+        })
+        .collect()
+}
+
+/// Group an entry belongs to, if it has duplicates
+pub fn group_of(groups: &[Group], id: u16) -> Option<&Group> {
+    groups.iter().find(|group| group.contains(id))
+}
+
+/// Hide the extra entries of each group from the list
+pub fn collapse(targets: &mut BootTargets, groups: &[Group]) {
+    targets.targets.retain(|target| !groups.iter().any(|group| group.extras.contains(&target.id)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_boot_targets;
+
+    const OUTPUT: &str = "BootCurrent: 0001\n\
+        BootOrder: 0004,0001,0000,0002\n\
+        Boot0000* Windows Boot Manager\tHD(1,GPT,4f3c2a1b-9e8d-4c7b-a6f5-0e1d2c3b4a59,0x800,0x82000)/File(\\EFI\\Microsoft\\Boot\\bootmgfw.efi)\n\
+        Boot0001* ubuntu\tHD(1,GPT,4f3c2a1b-9e8d-4c7b-a6f5-0e1d2c3b4a59,0x800,0x82000)/File(\\EFI\\ubuntu\\shimx64.efi)\n\
+        Boot0002* Windows Boot Manager\tHD(1,GPT,4f3c2a1b-9e8d-4c7b-a6f5-0e1d2c3b4a59,0x800,0x82000)/File(\\EFI\\Microsoft\\Boot\\bootmgfw.efi)\n\
+        Boot0003* ubuntu\tHD(1,GPT,4f3c2a1b-9e8d-4c7b-a6f5-0e1d2c3b4a59,0x800,0x82000)/File(\\EFI\\ubuntu\\shimx64.efi)\n\
+        Boot0004* Windows Boot Manager\tHD(1,GPT,4f3c2a1b-9e8d-4c7b-a6f5-0e1d2c3b4a59,0x800,0x82000)/File(\\EFI\\Microsoft\\Boot\\bootmgfw.efi)\n\
+        Boot0005* UEFI Shell\n";
+
+    #[test]
+    fn finds_duplicates_by_device_path() {
+        let targets = parse_boot_targets(OUTPUT);
+
+        assert_eq!(find(&targets), vec![
+            // First in BootOrder wins
+            Group { keep: 0x0004, extras: vec![0x0000, 0x0002] },
+            // The booted entry wins
+            Group { keep: 0x0001, extras: vec![0x0003] },
+        ]);
+    }
+
+    #[test]
+    fn collapses_extras() {
+        let mut targets = parse_boot_targets(OUTPUT);
+        let groups = find(&targets);
+        collapse(&mut targets, &groups);
+
+        let ids: Vec<u16> = targets.targets.iter().map(|target| target.id).collect();
+        assert_eq!(ids, vec![0x0001, 0x0004, 0x0005]);
+        assert_eq!(group_of(&groups, 0x0002).map(|group| group.keep), Some(0x0004));
+    }
+}
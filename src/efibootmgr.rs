@@ -0,0 +1,290 @@
+/***
+ * efibootmgr invocations
+ *
+ * Thin wrappers around the efibootmgr executable for every operation that
+ * writes to the EFI boot variables. Output is captured, so it never ends up
+ * drawn over the TUI. Writes that can safely be repeated are retried.
+ ***/
+use std::{fmt, fs, io::{Result, Write}, path::{Path, PathBuf}, process::{Command, Output}};
+
+use crate::{cache, efivar, i18n::tr, parser::DevicePath, process, sudo, tempdir, verify::{self, LoaderLocation}, BootTarget, BootTargets};
+
+/// Format a boot entry ID the way efibootmgr expects it, as 4 hexadecimal digits
+pub fn format_id(id: u16) -> String {
+    format!("{:04X}", id)
+}
+
+/// Parse a hexadecimal boot entry ID as formatted by efibootmgr
+pub fn parse_id(id: &str) -> Option<u16> {
+    let id = id.trim();
+    if id.is_empty() || id.len() > 4 || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    u16::from_str_radix(id, 16).ok()
+}
+
+/// Failed system command
+#[derive(Debug)]
+pub struct CommandError {
+    pub message: String,
+    /// The command failed for lack of privileges, retrying through sudo may help
+    pub permission_denied: bool,
+}
+
+impl CommandError {
+    /// Prefix the message with what was being attempted
+    pub fn context(self, what: &str) -> CommandError {
+        CommandError { message: format!("{}: {}", what, self.message), ..self }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<CommandError> for String {
+    fn from(e: CommandError) -> String {
+        e.message
+    }
+}
+
+/// A failure before any command ran, like a partition that was not found
+impl From<String> for CommandError {
+    fn from(message: String) -> CommandError {
+        CommandError { message, permission_denied: false }
+    }
+}
+
+/// A failed file operation, for which privileges may help as for a command
+fn io_failure(e: std::io::Error) -> CommandError {
+    CommandError { message: e.to_string(), permission_denied: e.kind() == std::io::ErrorKind::PermissionDenied }
+}
+
+/// Non-empty lines a command wrote to stderr, joined into a single line
+fn stderr_summary(output: &Output) -> Option<String> {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lines: Vec<&str> = stderr.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+
+    Some(lines.join("; ")).filter(|summary| !summary.is_empty())
+}
+
+/// Turn the result of a command invocation into a readable error
+pub fn check_command(name: &str, output: Result<Output>) -> std::result::Result<(), CommandError> {
+    match output {
+        Ok(o) if o.status.success() => Ok(()),
+        Ok(o) => Err(CommandError {
+            message: match stderr_summary(&o) {
+                Some(stderr) => tr!("{} exited with non-zero status {}: {}", name, o.status.code().unwrap_or(-1), stderr),
+                None => tr!("{} exited with non-zero status: {}", name, o.status.code().unwrap_or(-1)),
+            },
+            permission_denied: sudo::is_permission_denied(&o),
+        }),
+        Err(e) => Err(CommandError {
+            message: tr!("could not run {}: {}", name, e),
+            permission_denied: e.kind() == std::io::ErrorKind::PermissionDenied,
+        }),
+    }
+}
+
+/// Turn the result of an efibootmgr invocation into a readable error
+pub fn check(output: Result<Output>) -> std::result::Result<(), CommandError> {
+    check_command("efibootmgr", output)
+}
+
+/// efibootmgr with the given arguments. FreeBSD's efibootmgr has different
+/// flags and no --quiet, so the arguments for both are needed.
+fn efibootmgr(linux: &[&str], freebsd: &[&str]) -> Command {
+    let mut command = Command::new("efibootmgr");
+    if cfg!(target_os = "freebsd") {
+        command.args(freebsd);
+    } else {
+        command.arg("--quiet").args(linux);
+    }
+
+    command
+}
+
+/// Run an efibootmgr command that writes to the boot variables, which
+/// makes the cached listing outdated
+fn run(command: &mut Command) -> Result<Output> {
+    let output = sudo::run(command);
+    cache::invalidate();
+    output
+}
+
+pub fn set_next_boot(target: &BootTarget) -> Result<Output> {
+    let id = format_id(target.id);
+    process::retry(|| run(&mut efibootmgr(&["--bootnext", &id], &["-n", "-b", &id])))
+}
+
+pub fn clear_next_boot() -> Result<Output> {
+    process::retry(|| run(&mut efibootmgr(&["--delete-bootnext"], &["-N"])))
+}
+
+/// Parameters for creating a new boot entry
+pub struct NewEntry<'a> {
+    pub id: Option<u16>,
+    pub disk: &'a Path,
+    pub partition: u32,
+    pub loader: &'a str,
+    pub label: &'a str,
+    /// File containing binary optional data to append to the entry
+    pub optional_data: Option<&'a Path>,
+    /// Leave BootOrder untouched instead of adding the entry to the front
+    pub create_only: bool,
+}
+
+pub fn create_entry(entry: &NewEntry) -> Result<Output> {
+    // FreeBSD's efibootmgr takes the loader as a path on a mounted filesystem instead
+    if cfg!(target_os = "freebsd") {
+        return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "creating boot entries is not supported on FreeBSD"));
+    }
+
+    let mut command = Command::new("efibootmgr");
+    command.arg("--quiet").arg(if entry.create_only { "--create-only" } else { "--create" });
+
+    if let Some(id) = entry.id {
+        command.arg("--bootnum").arg(format_id(id));
+    }
+
+    command
+        .arg("--disk").arg(entry.disk)
+        .arg("--part").arg(entry.partition.to_string())
+        .arg("--loader").arg(entry.loader)
+        .arg("--label").arg(entry.label);
+
+    if let Some(file) = entry.optional_data {
+        command.arg("--append-binary-args").arg(file);
+    }
+
+    run(&mut command)
+}
+
+/// Device path and raw variable contents of an existing or backed up entry
+pub struct EntrySource<'a> {
+    pub device_path: Option<&'a str>,
+    pub raw: Option<&'a [u8]>,
+}
+
+/// Find the disk and loader location needed to recreate an entry
+pub fn resolve_location(device_path: Option<&str>) -> std::result::Result<(PathBuf, LoaderLocation), String> {
+    let location = device_path
+        .map(DevicePath::parse)
+        .and_then(|path| verify::loader_location(&path))
+        .ok_or("entry does not point at a file on a GPT partition, cannot recreate it")?;
+    let partition = verify::partition_device(&location.partuuid)
+        .ok_or(tr!("partition {} not found", location.partuuid))?;
+    let disk = verify::parent_disk(&partition)
+        .ok_or(tr!("could not find disk of partition {}", partition.display()))?;
+
+    Ok((disk, location))
+}
+
+/// Create an entry with the loader, optional data and active flag of `source`.
+/// Without an `id`, efibootmgr picks a free one and the entry is created active.
+pub fn recreate_entry(id: Option<u16>, label: &str, source: &EntrySource, add_to_order: bool) -> std::result::Result<(), CommandError> {
+    let (disk, location) = resolve_location(source.device_path)?;
+    let option = source.raw.and_then(efivar::parse_load_option);
+
+    // efibootmgr reads optional data from a file, written where nobody else can put a symlink in its place
+    let data_dir = match &option {
+        Some(option) if !option.optional_data.is_empty() => Some(tempdir::create("data").map_err(io_failure)?),
+        _ => None,
+    };
+    let data_file = data_dir.as_ref().map(|dir| dir.join("optional-data"));
+    let written = match (&data_file, &option) {
+        (Some(data_file), Some(option)) => fs::OpenOptions::new().write(true).create_new(true).open(data_file)
+            .and_then(|mut file| file.write_all(&option.optional_data)),
+        _ => Ok(()),
+    };
+
+    let status = written.map_err(io_failure).map(|()| create_entry(&NewEntry {
+        id,
+        disk: &disk,
+        partition: location.partition,
+        loader: &location.path,
+        label,
+        optional_data: data_file.as_deref(),
+        create_only: !add_to_order,
+    }));
+    if let (Some(data_dir), Some(data_file)) = (&data_dir, &data_file) {
+        let _ = fs::remove_file(data_file);
+        let _ = fs::remove_dir(data_dir);
+    }
+
+    check(status?)?;
+
+    if let Some(id) = id {
+        if option.is_some_and(|option| !option.active) {
+            check(set_active(id, false))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that a label can be stored as an entry description: the firmware
+/// keeps it as UCS-2, and a tab or newline would break the efibootmgr output
+pub fn check_label(label: &str) -> std::result::Result<(), String> {
+    if label.trim().is_empty() {
+        return Err(String::from(tr!("The label cannot be empty")));
+    }
+    if label.chars().any(char::is_control) {
+        return Err(String::from(tr!("The label cannot contain tabs, newlines or other control characters")));
+    }
+    if let Some(c) = label.chars().find(|c| u32::from(*c) > 0xFFFF) {
+        return Err(tr!("The label cannot contain \"{}\", the firmware only stores characters up to U+FFFF", c));
+    }
+
+    Ok(())
+}
+
+/// Give an entry a new label. efibootmgr cannot rename in place, so the entry is
+/// deleted and recreated under the same ID, after which its BootOrder position
+/// and any pending BootNext are restored.
+pub fn rename_entry(targets: &BootTargets, target: &BootTarget, label: &str) -> std::result::Result<(), CommandError> {
+    // Make sure the entry can be recreated before deleting anything
+    resolve_location(target.device_path.as_ref().map(DevicePath::as_str))?;
+    let raw = efivar::read_efivar(&format!("Boot{}", format_id(target.id)));
+
+    check(delete_entry(target.id))?;
+    recreate_entry(Some(target.id), label, &EntrySource {
+        device_path: target.device_path.as_ref().map(DevicePath::as_str),
+        raw: raw.as_deref(),
+    }, false).map_err(|e| CommandError { message: tr!("{} (entry {} was deleted and could not be recreated)", e.message, format_id(target.id)), ..e })?;
+
+    if !targets.order.is_empty() {
+        check(set_order(&targets.order))?;
+    }
+    if targets.next == Some(target.id) {
+        check(set_next_boot(target))?;
+    }
+
+    Ok(())
+}
+
+pub fn delete_entry(id: u16) -> Result<Output> {
+    let id = format_id(id);
+    run(&mut efibootmgr(&["--bootnum", &id, "--delete-bootnum"], &["-B", "-b", &id]))
+}
+
+pub fn set_active(id: u16, active: bool) -> Result<Output> {
+    let id = format_id(id);
+    process::retry(|| run(&mut efibootmgr(
+        &["--bootnum", &id, if active { "--active" } else { "--inactive" }],
+        &[if active { "-a" } else { "-A" }, "-b", &id],
+    )))
+}
+
+pub fn set_order(order: &[u16]) -> Result<Output> {
+    let order = order.iter().map(|id| format_id(*id)).collect::<Vec<String>>().join(",");
+    process::retry(|| run(&mut efibootmgr(&["--bootorder", &order], &["-o", &order])))
+}
+
+pub fn set_timeout(seconds: u16) -> Result<Output> {
+    let seconds = seconds.to_string();
+    process::retry(|| run(&mut efibootmgr(&["--timeout", &seconds], &["-t", &seconds])))
+}
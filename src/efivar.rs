@@ -0,0 +1,100 @@
+/***
+ * EFI variables
+ *
+ * Reads boot variables directly from efivarfs, and decodes the EFI_LOAD_OPTION
+ * structure stored in Boot#### variables. The SecureBoot and SetupMode
+ * variables tell whether the firmware only starts signed loaders.
+ ***/
+use std::fs;
+
+use crate::{demo, i18n::tr};
+
+/// GUID under which the global EFI variables (Boot####, BootOrder, ...) live
+pub const EFI_GLOBAL_GUID: &str = "8be4df61-93ca-11d2-aa0d-00e098032b8c";
+
+/// Read the raw contents of a global EFI variable from efivarfs, including the
+/// leading four attribute bytes.
+pub fn read_efivar(name: &str) -> Option<Vec<u8>> {
+    fs::read(format!("/sys/firmware/efi/efivars/{}-{}", name, EFI_GLOBAL_GUID)).ok()
+}
+
+/// Parsed EFI_LOAD_OPTION, as stored in a Boot#### variable
+pub struct LoadOption {
+    pub active: bool,
+    pub optional_data: Vec<u8>,
+}
+
+/// Decode a raw Boot#### variable (including the four efivarfs attribute bytes)
+pub fn parse_load_option(raw: &[u8]) -> Option<LoadOption> {
+    let data = raw.get(4..)?;
+    let attributes = u32::from_le_bytes(data.get(0..4)?.try_into().ok()?);
+    let path_length = u16::from_le_bytes(data.get(4..6)?.try_into().ok()?) as usize;
+
+    // Description is a null-terminated UCS-2 string
+    let mut offset = 6;
+    while data.get(offset..offset + 2)? != [0, 0] {
+        offset += 2;
+    }
+    offset += 2 + path_length;
+
+    Some(LoadOption {
+        active: attributes & 1 == 1,
+        optional_data: data.get(offset..)?.to_vec(),
+    })
+}
+
+/// Whether the firmware enforces Secure Boot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecureBoot {
+    Enabled,
+    Disabled,
+    /// No platform key enrolled yet, so nothing is enforced
+    SetupMode,
+}
+
+impl SecureBoot {
+    pub fn describe(self) -> &'static str {
+        match self {
+            SecureBoot::Enabled => tr!("enabled"),
+            SecureBoot::Disabled => tr!("disabled"),
+            SecureBoot::SetupMode => tr!("setup mode"),
+        }
+    }
+}
+
+/// Secure Boot state from the SecureBoot and SetupMode variables, None if
+/// they can't be read, e.g. without UEFI. Demo mode pretends it is enabled.
+pub fn secure_boot() -> Option<SecureBoot> {
+    if demo::is_enabled() {
+        return Some(SecureBoot::Enabled);
+    }
+
+    // Both hold a single byte after the attributes
+    let flag = |name: &str| read_efivar(name).and_then(|raw| raw.get(4).copied()).map(|value| value == 1);
+    match (flag("SecureBoot")?, flag("SetupMode")) {
+        (_, Some(true)) => Some(SecureBoot::SetupMode),
+        (true, _) => Some(SecureBoot::Enabled),
+        (false, _) => Some(SecureBoot::Disabled),
+    }
+}
+
+/// Snapshot of all boot related variables (Boot####, BootOrder, BootNext, Timeout, ...)
+/// in efivarfs. Two fingerprints differ if any boot variable was added, removed or
+/// changed in between. Returns None if efivarfs is not available.
+pub fn boot_fingerprint() -> Option<Vec<u8>> {
+    let suffix = format!("-{}", EFI_GLOBAL_GUID);
+    let mut names: Vec<String> = fs::read_dir("/sys/firmware/efi/efivars").ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|name| name.ends_with(&suffix) && (name.starts_with("Boot") || name.starts_with("Timeout")))
+        .collect();
+    names.sort();
+
+    let mut fingerprint = vec![];
+    for name in names {
+        fingerprint.extend_from_slice(name.as_bytes());
+        fingerprint.extend(read_efivar(name.trim_end_matches(&suffix)).unwrap_or_default());
+    }
+
+    Some(fingerprint)
+}
@@ -0,0 +1,97 @@
+/***
+ * EFI System Partition
+ *
+ * Finds the ESP: the partition the loader of the current boot entry is on,
+ * or else a partition with the ESP type GUID in udev's data, or else a FAT
+ * filesystem mounted where distributions put the ESP. Loaders on it are
+ * looked up under its mount point, also where /dev/disk/by-partuuid is
+ * missing, as in containers without udev.
+ ***/
+use std::{fs, path::{Path, PathBuf}};
+
+use crate::{demo, disk, i18n::tr, verify, BootTargets};
+
+/// GPT partition type of the ESP
+const ESP_TYPE: &str = "c12a7328-f81f-11d2-ba4b-00a0c93ec93b";
+
+/// Where distributions mount the ESP, most specific first
+const MOUNT_POINTS: [&str; 3] = ["/efi", "/boot/efi", "/boot"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Esp {
+    /// Partition block device, e.g. `/dev/nvme0n1p1`
+    pub device: PathBuf,
+    /// Partition GUID, lowercase
+    pub partuuid: Option<String>,
+    pub mount_point: Option<PathBuf>,
+}
+
+impl Esp {
+    fn new(device: PathBuf, partuuid: Option<String>) -> Esp {
+        let partuuid = partuuid.or_else(|| disk::udev_property(&device, "ID_PART_ENTRY_UUID")).map(|uuid| uuid.to_lowercase());
+        let mount_point = verify::find_mount_point(&device);
+        Esp { device, partuuid, mount_point }
+    }
+
+    /// Like "/dev/nvme0n1p1 on /boot/efi"
+    pub fn describe(&self) -> String {
+        match &self.mount_point {
+            Some(mount_point) => tr!("{} on {}", self.device.display(), mount_point.display()),
+            None => tr!("{}, not mounted", self.device.display()),
+        }
+    }
+}
+
+/// Partitions with the ESP type, mounted ones first
+fn typed_partitions() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir("/sys/class/block") else {
+        return vec![];
+    };
+
+    let mut partitions: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join("partition").exists())
+        .map(|entry| Path::new("/dev").join(entry.file_name()))
+        .filter(|device| disk::udev_property(device, "ID_PART_ENTRY_TYPE").is_some_and(|kind| kind.eq_ignore_ascii_case(ESP_TYPE)))
+        .collect();
+    partitions.sort_by_key(|device| (verify::find_mount_point(device).is_none(), device.clone()));
+    partitions
+}
+
+/// Device of a FAT filesystem mounted at one of the usual ESP mount points
+fn mounted_fat() -> Option<PathBuf> {
+    let mounts = fs::read_to_string("/proc/self/mounts").ok()?;
+    let fat: Vec<(&str, &str)> = mounts.lines().filter_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            [source, target, "vfat", ..] => Some((*source, *target)),
+            _ => None,
+        }
+    }).collect();
+
+    MOUNT_POINTS.iter()
+        .find_map(|mount_point| fat.iter().find(|(_, target)| target == mount_point))
+        .and_then(|(source, _)| fs::canonicalize(source).ok())
+}
+
+/// The ESP of this system, if it can be found
+pub fn find(targets: &BootTargets) -> Option<Esp> {
+    // Demo entries point at partitions that do not exist
+    if demo::is_enabled() {
+        return None;
+    }
+
+    // The one the running system was booted from
+    let current = targets.current.and_then(|id| targets.by_id(id))
+        .and_then(|target| target.device_path.as_ref())
+        .and_then(verify::loader_location);
+    if let Some(location) = current {
+        if let Some(device) = verify::partition_device(&location.partuuid) {
+            return Some(Esp::new(device, Some(location.partuuid)));
+        }
+    }
+
+    typed_partitions().into_iter().next()
+        .or_else(mounted_fat)
+        .map(|device| Esp::new(device, None))
+}
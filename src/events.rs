@@ -0,0 +1,124 @@
+/***
+ * TUI events
+ *
+ * Everything the TUI reacts to arrives on one channel: terminal input, read
+ * on its own thread, boot variables changed by someone else and disks being
+ * plugged in or pulled, noticed by a watcher thread, and background actions
+ * finishing on their worker threads.
+ * The TUI loop sleeps on the channel until one of them happens, or until it
+ * has something to animate, instead of waking up every few milliseconds.
+ ***/
+use std::{io, sync::{atomic::{AtomicBool, Ordering}, mpsc, Arc}, thread, time::Duration};
+
+use ratatui::crossterm::event;
+
+use crate::{disk, efivar};
+
+/// How long the input thread waits for input before checking whether it should stop
+const INPUT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub enum Event {
+    /// Key press, resize or other terminal event
+    Terminal(event::Event),
+    /// The boot variables were changed since they were last read
+    BootVariablesChanged,
+    /// A block device was added or removed, like a USB stick
+    BlockDevicesChanged,
+    /// A background action finished, its result is waiting in its channel
+    ActionFinished,
+}
+
+/// Sending half of the event channel, for worker threads
+pub type Sender = mpsc::Sender<io::Result<Event>>;
+
+/// Event channel with the threads feeding it, stopped when dropped
+pub struct Events {
+    sender: Sender,
+    receiver: mpsc::Receiver<io::Result<Event>>,
+    stop: Arc<AtomicBool>,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl Events {
+    /// Start reading terminal input, and checking the boot variables and block devices every `interval`
+    pub fn start(interval: Duration) -> Events {
+        let (sender, receiver) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let input_sender = sender.clone();
+        let input_stop = stop.clone();
+        let input = thread::spawn(move || {
+            // Polling with a timeout rather than blocking in read(), so that no
+            // key meant for whatever comes after the TUI is taken
+            while !input_stop.load(Ordering::SeqCst) {
+                let event = match event::poll(INPUT_POLL_INTERVAL) {
+                    Ok(false) => continue,
+                    Ok(true) => event::read().map(Event::Terminal),
+                    Err(e) => Err(e),
+                };
+                let failed = event.is_err();
+                if input_sender.send(event).is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        let watcher_sender = sender.clone();
+        let watcher_stop = stop.clone();
+        let watcher = thread::spawn(move || {
+            let mut fingerprint = efivar::boot_fingerprint();
+            let mut devices = disk::block_devices();
+            loop {
+                // Woken up early when stopped
+                thread::park_timeout(interval);
+                if watcher_stop.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                let mut changes = vec![];
+                let current = efivar::boot_fingerprint();
+                if current.is_some() && current != fingerprint {
+                    fingerprint = current;
+                    changes.push(Event::BootVariablesChanged);
+                }
+                let current = disk::block_devices();
+                if current != devices {
+                    devices = current;
+                    changes.push(Event::BlockDevicesChanged);
+                }
+                if changes.into_iter().any(|event| watcher_sender.send(Ok(event)).is_err()) {
+                    break;
+                }
+            }
+        });
+
+        Events { sender, receiver, stop, threads: vec![input, watcher] }
+    }
+
+    pub fn sender(&self) -> Sender {
+        self.sender.clone()
+    }
+
+    /// Wait for the next event, at most `timeout` if given. Returns None on timeout.
+    pub fn next(&self, timeout: Option<Duration>) -> io::Result<Option<Event>> {
+        let event = match timeout {
+            Some(timeout) => match self.receiver.recv_timeout(timeout) {
+                Ok(event) => event,
+                Err(mpsc::RecvTimeoutError::Timeout) => return Ok(None),
+                Err(mpsc::RecvTimeoutError::Disconnected) => unreachable!("Events holds a sender itself"),
+            },
+            None => self.receiver.recv().expect("Events holds a sender itself"),
+        };
+        event.map(Some)
+    }
+}
+
+impl Drop for Events {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        for thread in self.threads.drain(..) {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
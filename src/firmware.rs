@@ -0,0 +1,71 @@
+/***
+ * Firmware information
+ *
+ * Identifies the firmware of the machine from the DMI data the kernel exposes
+ * in `/sys/class/dmi/id`, and the UEFI word size from `/sys/firmware/efi`, to
+ * tell machines in a mixed fleet apart. Whatever can't be read is left out.
+ ***/
+use std::{fs, path::Path};
+
+use crate::demo;
+
+/// Where the kernel shows the UEFI firmware, absent after a legacy BIOS boot
+pub const EFI_DIR: &str = "/sys/firmware/efi";
+
+/// Firmware vendor and version, as far as they are known
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Firmware {
+    pub vendor: Option<String>,
+    pub version: Option<String>,
+    pub date: Option<String>,
+    /// 32 or 64, the UEFI word size, which can differ from the kernel's
+    pub platform_size: Option<u32>,
+}
+
+/// Contents of a sysfs file without the line break, if it has any
+fn read_sysfs(path: &str) -> Option<String> {
+    fs::read_to_string(path).ok()
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+/// The firmware of this machine, or a made-up one in demo mode
+pub fn read() -> Firmware {
+    if demo::is_enabled() {
+        return Firmware {
+            vendor: Some(String::from("Demo Firmware Inc.")),
+            version: Some(String::from("1.0")),
+            date: Some(String::from("01/01/2024")),
+            platform_size: Some(64),
+        };
+    }
+
+    Firmware {
+        vendor: read_sysfs("/sys/class/dmi/id/bios_vendor"),
+        version: read_sysfs("/sys/class/dmi/id/bios_version"),
+        date: read_sysfs("/sys/class/dmi/id/bios_date"),
+        platform_size: read_sysfs("/sys/firmware/efi/fw_platform_size").and_then(|size| size.parse().ok()),
+    }
+}
+
+/// Whether the system was booted through UEFI. Only Linux tells, elsewhere
+/// and in demo mode it is assumed.
+pub fn is_uefi() -> bool {
+    demo::is_enabled() || !cfg!(target_os = "linux") || Path::new(EFI_DIR).is_dir()
+}
+
+impl Firmware {
+    /// One line like "LENOVO N32ET86W (1.62) 05/10/2023, 64-bit UEFI", or None if nothing is known
+    pub fn describe(&self) -> Option<String> {
+        let mut parts: Vec<String> = [&self.vendor, &self.version, &self.date].into_iter().flatten().cloned().collect();
+        if let Some(size) = self.platform_size {
+            let uefi = format!("{}-bit UEFI", size);
+            match parts.last_mut() {
+                Some(last) => last.push_str(&format!(", {}", uefi)),
+                None => parts.push(uefi),
+            }
+        }
+
+        if parts.is_empty() { None } else { Some(parts.join(" ")) }
+    }
+}
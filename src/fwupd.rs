@@ -0,0 +1,81 @@
+/***
+ * Pending firmware updates
+ *
+ * fwupd installs most UEFI firmware updates as capsules on the next reboot:
+ * the reboot first detours through the firmware updater, which can take a
+ * few minutes and reboot again, before the chosen entry starts. The devices
+ * with such an update are asked from fwupd's GetDevices on the system bus,
+ * through `busctl`, and those whose UpdateState is pending or waiting for a
+ * reboot are shown by `status` and the TUI. Without fwupd there are none.
+ ***/
+use std::process::Command;
+
+use crate::{json::{self, Value}, process::{self, find_program}};
+
+/// UpdateState of a device whose update is installed on the next reboot
+const UPDATE_STATE_PENDING: u64 = 1;
+/// UpdateState of a device whose update is done once it reboots
+const UPDATE_STATE_NEEDS_REBOOT: u64 = 4;
+
+/// Names of the devices with an update pending, in the `busctl --json` reply
+/// to GetDevices, whose single return value is an array of dictionaries
+fn parse_devices(reply: &str) -> Vec<String> {
+    let reply = match json::parse(reply) {
+        Ok(reply) => reply,
+        Err(e) => {
+            log::debug!("could not parse the devices of fwupd: {}", e);
+            return vec![];
+        }
+    };
+    // Variants are objects with their "type" and "data"
+    let property = |device: &Value, key: &str| device.get(key).and_then(|variant| variant.get("data")).cloned();
+
+    let devices = reply.get("data").and_then(Value::as_array).and_then(|values| values.first()).and_then(Value::as_array);
+    devices.into_iter().flatten()
+        .filter(|device| property(device, "UpdateState")
+            .and_then(|state| state.as_u64())
+            .is_some_and(|state| state == UPDATE_STATE_PENDING || state == UPDATE_STATE_NEEDS_REBOOT))
+        .map(|device| property(device, "Name").and_then(|name| name.as_str().map(String::from)).unwrap_or(String::from("?")))
+        .collect()
+}
+
+/// Names of the devices whose firmware is updated on the next reboot
+pub fn pending_updates() -> Vec<String> {
+    if find_program("busctl").is_none() {
+        log::debug!("busctl not found, not asking fwupd for pending updates");
+        return vec![];
+    }
+
+    // Not through sudo, fwupd answers anyone
+    let output = process::output(Command::new("busctl")
+        .args(["--system", "--json=short", "--timeout=3", "call", "org.freedesktop.fwupd", "/", "org.freedesktop.fwupd", "GetDevices"]), None);
+    match output {
+        Ok(output) if output.status.success() => parse_devices(&String::from_utf8_lossy(&output.stdout)),
+        Ok(output) => {
+            log::debug!("fwupd did not list its devices: {}", String::from_utf8_lossy(&output.stderr).trim());
+            vec![]
+        }
+        Err(e) => {
+            log::debug!("could not run busctl: {}", e);
+            vec![]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_pending_updates() {
+        let reply = r#"{"type":"aa{sv}","data":[[
+            {"Name":{"type":"s","data":"System Firmware"},"UpdateState":{"type":"u","data":1}},
+            {"Name":{"type":"s","data":"UEFI dbx"},"UpdateState":{"type":"u","data":2}},
+            {"Name":{"type":"s","data":"Thunderbolt Controller"}},
+            {"Name":{"type":"s","data":"Embedded Controller"},"UpdateState":{"type":"u","data":4}}
+        ]]}"#;
+        assert_eq!(parse_devices(reply), ["System Firmware", "Embedded Controller"]);
+        assert!(parse_devices("").is_empty());
+        assert!(parse_devices(r#"{"type":"aa{sv}","data":[[]]}"#).is_empty());
+    }
+}
@@ -0,0 +1,76 @@
+/***
+ * Hibernated systems
+ *
+ * A hibernated system resumes with the filesystems it had mounted as it left
+ * them, so whatever another system writes to them in the meantime can be
+ * lost or corrupt them. This is easy to run into with Windows, whose Fast
+ * Startup hibernates instead of shutting down, and a shared NTFS partition.
+ *
+ * Windows is found hibernated by the header of `hiberfil.sys` on the NTFS
+ * filesystems mounted here, which reads "hibr" until it resumes. A Linux
+ * hibernation image is a swap partition that udev, like blkid, reports as
+ * "swsuspend" instead of "swap". Images in swap files go unnoticed.
+ ***/
+use std::{fs::{self, File}, io::Read, path::{Path, PathBuf}};
+
+use crate::disk;
+
+/// Filesystem types NTFS is mounted as, by the kernel drivers and ntfs-3g
+const NTFS_TYPES: [&str; 3] = ["ntfs", "ntfs3", "fuseblk"];
+
+/// Whether the start of a hiberfil.sys says Windows is hibernated. After
+/// resuming it reads "wake", or zeros.
+fn is_hibernated(header: &[u8]) -> bool {
+    header.get(..4).is_some_and(|magic| magic.eq_ignore_ascii_case(b"hibr"))
+}
+
+/// Mount points of NTFS filesystems in /proc/self/mounts. Octal escapes,
+/// like \040 for a space, are decoded.
+fn ntfs_mount_points(mounts: &str) -> Vec<PathBuf> {
+    mounts.lines().filter_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            [_, target, kind, ..] if NTFS_TYPES.contains(kind) => Some(PathBuf::from(target.replace("\\040", " "))),
+            _ => None,
+        }
+    }).collect()
+}
+
+/// Windows hibernation files, on the NTFS filesystems mounted here
+pub fn windows_hiberfiles() -> Vec<PathBuf> {
+    let mounts = fs::read_to_string("/proc/self/mounts").unwrap_or_default();
+    ntfs_mount_points(&mounts).into_iter()
+        .map(|mount_point| mount_point.join("hiberfil.sys"))
+        .filter(|path| {
+            let mut header = [0; 4];
+            File::open(path).and_then(|mut file| file.read_exact(&mut header)).is_ok() && is_hibernated(&header)
+        })
+        .collect()
+}
+
+/// Swap partitions holding a Linux hibernation image
+pub fn linux_images() -> Vec<PathBuf> {
+    disk::block_devices().into_iter()
+        .map(|name| Path::new("/dev").join(name))
+        .filter(|device| disk::udev_property(device, "ID_FS_TYPE").as_deref() == Some("swsuspend"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_hibernated_windows() {
+        assert!(is_hibernated(b"HIBR\0\0\0\0"));
+        assert!(is_hibernated(b"hibr"));
+        assert!(!is_hibernated(b"wake"));
+        assert!(!is_hibernated(b"\0\0\0\0"));
+        assert!(!is_hibernated(b"hi"));
+
+        let mounts = "/dev/nvme0n1p2 / ext4 rw,relatime 0 0\n\
+            /dev/nvme0n1p4 /mnt/windows ntfs3 rw,relatime,uid=1000 0 0\n\
+            /dev/sda1 /media/alex/Shared\\040Data fuseblk rw,nosuid,nodev 0 0\n";
+        assert_eq!(ntfs_mount_points(mounts), [PathBuf::from("/mnt/windows"), PathBuf::from("/media/alex/Shared Data")]);
+    }
+}
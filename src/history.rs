@@ -0,0 +1,161 @@
+/***
+ * Reboot history
+ *
+ * Entries rebooted to are recorded in `$XDG_STATE_HOME/reboot-to/history`, or
+ * `~/.local/state/reboot-to/history`, as "<unix time> <tab> <ID> <tab> <name>"
+ * lines, newest last. The entry selected when the TUI was left is kept the
+ * same way in `selection` next to it. Entries are matched back by ID and
+ * name, or by name alone when the ID changed, e.g. after the entry was
+ * recreated.
+ ***/
+use std::{env, fs, io, path::PathBuf, time::SystemTime};
+
+use crate::{demo, efibootmgr::{format_id, parse_id}, BootTarget, BootTargets};
+
+/// Lines kept in the history file
+const MAX_VISITS: usize = 50;
+
+/// A reboot to an entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Visit {
+    /// Seconds since the Unix epoch
+    pub time: u64,
+    pub id: u16,
+    pub name: String,
+}
+
+impl Visit {
+    /// The entry this visit was to, if it still exists
+    fn find<'a>(&self, targets: &'a BootTargets) -> Option<&'a BootTarget> {
+        let by_name: Vec<&BootTarget> = targets.targets.iter().filter(|target| target.name == self.name).collect();
+        match by_name.iter().find(|target| target.id == self.id) {
+            Some(target) => Some(target),
+            None if by_name.len() == 1 => Some(by_name[0]),
+            None => None,
+        }
+    }
+}
+
+/// Location of a file in the state directory
+pub fn state_file(name: &str) -> Option<PathBuf> {
+    let base = env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()).map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state")))?;
+
+    Some(base.join("reboot-to").join(name))
+}
+
+/// Location of the history file
+pub fn path() -> Option<PathBuf> {
+    state_file("history")
+}
+
+/// Parse the history file, skipping lines that don't make sense
+pub fn parse(text: &str) -> Vec<Visit> {
+    text.lines().filter_map(|line| {
+        let mut fields = line.splitn(3, '\t');
+        let time = fields.next()?.parse().ok()?;
+        let id = parse_id(fields.next()?)?;
+        let name = fields.next()?.to_string();
+        Some(Visit { time, id, name })
+    }).collect()
+}
+
+/// Past reboots, oldest first, or none if there is no history
+pub fn load() -> Vec<Visit> {
+    path().and_then(|path| fs::read_to_string(path).ok()).map(|text| parse(&text)).unwrap_or_default()
+}
+
+/// Write visits to a file in the state directory, creating it if needed
+fn write(name: &str, visits: &[Visit]) -> io::Result<()> {
+    let path = state_file(name).ok_or(io::Error::new(io::ErrorKind::NotFound, "neither XDG_STATE_HOME nor HOME is set"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let text: String = visits.iter()
+        .map(|visit| format!("{}\t{}\t{}\n", visit.time, format_id(visit.id), visit.name.replace(['\t', '\n'], " ")))
+        .collect();
+    fs::write(path, text)
+}
+
+/// A visit to the entry now
+fn visit(target: &BootTarget) -> Visit {
+    let time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or_default();
+    Visit { time, id: target.id, name: target.name.clone() }
+}
+
+fn append(target: &BootTarget) -> io::Result<()> {
+    let mut visits = load();
+    visits.push(visit(target));
+
+    let start = visits.len().saturating_sub(MAX_VISITS);
+    write("history", &visits[start..])
+}
+
+/// Record a reboot to the entry, except for made-up entries. Failing to is
+/// only worth a warning.
+pub fn record(target: &BootTarget) {
+    if demo::is_enabled() {
+        return;
+    }
+    if let Err(e) = append(target) {
+        log::warn!("could not record reboot in history: {}", e);
+    }
+}
+
+/// Remember the entry selected when leaving the TUI, except for made-up entries
+pub fn save_selection(target: &BootTarget) {
+    if demo::is_enabled() {
+        return;
+    }
+    if let Err(e) = write("selection", &[visit(target)]) {
+        log::warn!("could not remember the selected entry: {}", e);
+    }
+}
+
+/// The entry selected when the TUI was left last time, if it still exists
+pub fn selection(targets: &BootTargets) -> Option<&BootTarget> {
+    let text = fs::read_to_string(state_file("selection")?).ok()?;
+    parse(&text).first()?.find(targets)
+}
+
+/// Up to `count` distinct entries rebooted to, most recent first, that still exist
+pub fn recent<'a>(visits: &[Visit], targets: &'a BootTargets, count: usize) -> Vec<&'a BootTarget> {
+    let mut recent: Vec<&BootTarget> = vec![];
+    for target in visits.iter().rev().filter_map(|visit| visit.find(targets)) {
+        if recent.len() == count {
+            break;
+        }
+        if !recent.iter().any(|r| r.id == target.id) {
+            recent.push(target);
+        }
+    }
+    recent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_boot_targets;
+
+    #[test]
+    fn parses_history() {
+        let visits = parse("1700000000\t0000\tWindows Boot Manager\nbroken line\n1700000100\t0003\tFedora\n");
+
+        assert_eq!(visits, vec![
+            Visit { time: 1700000000, id: 0x0000, name: String::from("Windows Boot Manager") },
+            Visit { time: 1700000100, id: 0x0003, name: String::from("Fedora") },
+        ]);
+    }
+
+    #[test]
+    fn finds_recent_entries() {
+        let targets = parse_boot_targets("Boot0000* Windows Boot Manager\nBoot0001* ubuntu\nBoot0005* Fedora\n");
+        let visits = parse("1\t0001\tubuntu\n2\t0000\tWindows Boot Manager\n3\t0003\tFedora\n4\t0000\tWindows Boot Manager\n5\t0002\tGone\n");
+
+        // Fedora was recreated as 0005, the entry named "Gone" no longer exists
+        let ids: Vec<u16> = recent(&visits, &targets, 3).iter().map(|target| target.id).collect();
+        assert_eq!(ids, vec![0x0000, 0x0005, 0x0001]);
+        assert_eq!(recent(&visits, &targets, 1).len(), 1);
+    }
+}
@@ -0,0 +1,242 @@
+/***
+ * Translations
+ *
+ * User-facing text goes through `tr!`, which looks up the English text in a
+ * gettext .po catalog for the user's language. Catalogs are read from
+ * REBOOT_TO_LOCALEDIR, or /usr/share/reboot-to/locale, as `<language>.po`,
+ * e.g. `de.po` or `pt_BR.po`. Texts without a translation stay in English.
+ ***/
+use std::{collections::HashMap, env, fmt::Display, fs, path::PathBuf, sync::OnceLock};
+
+/// Directory with the catalogs, unless REBOOT_TO_LOCALEDIR is set
+const DEFAULT_LOCALEDIR: &str = "/usr/share/reboot-to/locale";
+
+/// Translations of the user's language, by English text
+static CATALOG: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// Translate a text, and fill in its `{}` placeholders in order
+macro_rules! tr {
+    ($text:literal) => {
+        $crate::i18n::translate($text)
+    };
+    ($text:literal, $($arg:expr),+ $(,)?) => {
+        $crate::i18n::format($crate::i18n::translate($text), &[$(&$arg),+])
+    };
+}
+pub(crate) use tr;
+
+/// Load the catalog of the user's language, if there is one
+pub fn init() {
+    let dir = env::var_os("REBOOT_TO_LOCALEDIR").map(PathBuf::from).unwrap_or(PathBuf::from(DEFAULT_LOCALEDIR));
+    let catalog = languages().iter()
+        .find_map(|language| fs::read_to_string(dir.join(format!("{}.po", language))).ok())
+        .map(|text| parse_po(&text))
+        .unwrap_or_default();
+
+    let _ = CATALOG.set(catalog);
+}
+
+/// Languages to look for, most preferred first, following gettext: LANGUAGE
+/// is a list of languages, otherwise the first of LC_ALL, LC_MESSAGES and
+/// LANG that is set is used
+fn languages() -> Vec<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+        .find_map(|name| env::var(name).ok().filter(|value| !value.is_empty()));
+    let Some(locale) = locale else {
+        return vec![];
+    };
+    if locale == "C" || locale == "POSIX" {
+        return vec![];
+    }
+
+    let list = env::var("LANGUAGE").ok().filter(|value| !value.is_empty()).unwrap_or(locale);
+    list.split(':').flat_map(candidates).collect()
+}
+
+/// Catalog names for a locale like `pt_BR.UTF-8@euro`: `pt_BR`, then `pt`
+fn candidates(locale: &str) -> Vec<String> {
+    let locale = locale.split(['.', '@']).next().unwrap_or_default();
+    let language = locale.split('_').next().unwrap_or_default();
+
+    let mut candidates = vec![];
+    if !locale.is_empty() {
+        candidates.push(locale.to_string());
+    }
+    if !language.is_empty() && language != locale {
+        candidates.push(language.to_string());
+    }
+    candidates
+}
+
+/// Unquote a .po string, resolving its escapes
+fn unquote(text: &str) -> Option<String> {
+    let inner = text.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    let mut result = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => result.push('\n'),
+            't' => result.push('\t'),
+            other => result.push(other),
+        }
+    }
+    Some(result)
+}
+
+/// Parse a .po catalog into translations by English text. Fuzzy, untranslated,
+/// plural and context entries are skipped.
+pub fn parse_po(text: &str) -> HashMap<String, String> {
+    #[derive(Default)]
+    struct Message {
+        id: Option<String>,
+        text: Option<String>,
+        skip: bool,
+    }
+
+    fn finish(message: Message, catalog: &mut HashMap<String, String>) {
+        if let (Some(id), Some(text), false) = (message.id, message.text, message.skip) {
+            if !id.is_empty() && !text.is_empty() {
+                catalog.insert(id, text);
+            }
+        }
+    }
+
+    let mut catalog = HashMap::new();
+    let mut message = Message::default();
+    // Which part of the message continuation lines add to
+    let mut in_text = false;
+
+    for line in text.lines().map(str::trim) {
+        // Entries are usually separated by blank lines, but a comment or msgid
+        // after a msgstr also starts a new one
+        if message.text.is_some() && (line.starts_with('#') || line.starts_with("msgid ")) {
+            finish(std::mem::take(&mut message), &mut catalog);
+        }
+
+        if line.is_empty() {
+            finish(std::mem::take(&mut message), &mut catalog);
+        } else if let Some(flags) = line.strip_prefix("#,") {
+            message.skip |= flags.split(',').any(|flag| flag.trim() == "fuzzy");
+        } else if line.starts_with('#') {
+            continue;
+        } else if let Some(id) = line.strip_prefix("msgid ") {
+            message.id = unquote(id);
+            in_text = false;
+        } else if let Some(text) = line.strip_prefix("msgstr ") {
+            message.text = unquote(text);
+            in_text = true;
+        } else if line.starts_with("msgctxt") || line.starts_with("msgid_plural") || line.starts_with("msgstr[") {
+            message.skip = true;
+        } else if let Some(more) = unquote(line) {
+            let part = if in_text { &mut message.text } else { &mut message.id };
+            if let Some(part) = part {
+                part.push_str(&more);
+            }
+        }
+    }
+    finish(message, &mut catalog);
+
+    catalog
+}
+
+/// Translation of `text`, or `text` itself if it has none
+pub fn translate(text: &str) -> &str {
+    CATALOG.get().and_then(|catalog| catalog.get(text)).map(String::as_str).unwrap_or(text)
+}
+
+/// Fill in the `{}` placeholders of a template in order. `{{` and `}}` are
+/// literal braces.
+pub fn format(template: &str, args: &[&dyn Display]) -> String {
+    let mut result = String::new();
+    let mut args = args.iter();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                result.push(c);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                if let Some(arg) = args.next() {
+                    result.push_str(&arg.to_string());
+                }
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Translate the help texts of a command line definition and its subcommands
+pub fn localize_command(mut command: clap::Command) -> clap::Command {
+    if let Some(about) = command.get_about().map(|about| translate(&about.to_string()).to_string()) {
+        command = command.about(about);
+    }
+    if let Some(about) = command.get_long_about().map(|about| translate(&about.to_string()).to_string()) {
+        command = command.long_about(about);
+    }
+
+    command = command.mut_args(|arg| match arg.get_help().map(|help| translate(&help.to_string()).to_string()) {
+        Some(help) => arg.help(help),
+        None => arg,
+    });
+
+    let names: Vec<String> = command.get_subcommands().map(|subcommand| subcommand.get_name().to_string()).collect();
+    names.iter().fold(command, |command, name| command.mut_subcommand(name, localize_command))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_catalog() {
+        let catalog = parse_po(r#"
+# German translation
+msgid ""
+msgstr ""
+"Content-Type: text/plain; charset=UTF-8\n"
+
+msgid "Reboot"
+msgstr "Neustarten"
+
+#, fuzzy
+msgid "Quit"
+msgstr "Verlassen"
+
+msgid ""
+"Setting next boot to \"{}\""
+msgstr "Nächster Start wird auf \"{}\" gesetzt"
+
+msgid "Refresh"
+msgstr ""
+"#);
+
+        assert_eq!(catalog.get("Reboot").map(String::as_str), Some("Neustarten"));
+        assert_eq!(catalog.get("Setting next boot to \"{}\"").map(String::as_str), Some("Nächster Start wird auf \"{}\" gesetzt"));
+        assert!(!catalog.contains_key("Quit"));
+        assert!(!catalog.contains_key("Refresh"));
+        assert!(!catalog.contains_key(""));
+    }
+
+    #[test]
+    fn formats_placeholders() {
+        assert_eq!(format("Renamed \"{}\" to \"{}\"", &[&"a", &"b"]), "Renamed \"a\" to \"b\"");
+        assert_eq!(format("{{}} {}", &[&3]), "{} 3");
+        assert_eq!(format("{} {}", &[&1]), "1 ");
+    }
+
+    #[test]
+    fn finds_language_candidates() {
+        assert_eq!(candidates("pt_BR.UTF-8@euro"), vec!["pt_BR", "pt"]);
+        assert_eq!(candidates("de"), vec!["de"]);
+        assert!(candidates("").is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+/***
+ * Journal
+ *
+ * Every reboot and next boot set, from the TUI or the command line, is sent
+ * to the systemd journal with the fields ACTION ("reboot" or "set_next"),
+ * RESULT ("success", "cancelled" or "failure"), TARGET_ID and TARGET_NAME,
+ * under the identifier `reboot-to`, so that `journalctl -t reboot-to` shows
+ * what was done, by whom and when, also for users without a history file.
+ * Entries are written to journald's socket in its native protocol, and left
+ * out where there is no journal, as in containers or on other systems.
+ ***/
+use crate::{demo, efibootmgr::{format_id, CommandError}, BootTarget};
+
+/// Socket journald reads native entries from
+#[cfg(target_os = "linux")]
+const SOCKET: &str = "/run/systemd/journal/socket";
+
+/// syslog priorities
+const PRIORITY_ERR: u8 = 3;
+const PRIORITY_NOTICE: u8 = 5;
+const PRIORITY_INFO: u8 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    SetNext,
+    Reboot,
+}
+
+impl Action {
+    /// Value of the ACTION field, named like the TUI actions
+    fn field(self) -> &'static str {
+        match self {
+            Action::SetNext => "set_next",
+            Action::Reboot => "reboot",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome<'a> {
+    Success,
+    Cancelled,
+    /// With the error message
+    Failure(&'a str),
+}
+
+/// Encode fields in the native journal protocol. Values with a newline are
+/// written as their length in 64 bits, little-endian, followed by the value.
+fn encode(fields: &[(&str, &str)]) -> Vec<u8> {
+    let mut entry = vec![];
+    for (key, value) in fields {
+        entry.extend(key.as_bytes());
+        if value.contains('\n') {
+            entry.push(b'\n');
+            entry.extend((value.len() as u64).to_le_bytes());
+        } else {
+            entry.push(b'=');
+        }
+        entry.extend(value.as_bytes());
+        entry.push(b'\n');
+    }
+    entry
+}
+
+#[cfg(target_os = "linux")]
+fn send(entry: &[u8]) {
+    use std::os::unix::net::UnixDatagram;
+
+    let sent = UnixDatagram::unbound().and_then(|socket| socket.send_to(entry, SOCKET));
+    if let Err(e) = sent {
+        log::debug!("could not write to the journal: {}", e);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn send(_entry: &[u8]) {}
+
+/// Send an action on an entry to the journal
+pub fn record(action: Action, target: &BootTarget, outcome: Outcome) {
+    // Made-up entries would pass for real ones
+    if demo::is_enabled() {
+        return;
+    }
+
+    let id = format_id(target.id);
+    let (message, result, priority) = match (action, outcome) {
+        (Action::Reboot, Outcome::Success) => (format!("Rebooting to \"{}\" ({})", target.name, id), "success", PRIORITY_INFO),
+        (Action::Reboot, Outcome::Cancelled) => (format!("Reboot to \"{}\" ({}) cancelled", target.name, id), "cancelled", PRIORITY_NOTICE),
+        (Action::Reboot, Outcome::Failure(e)) => (format!("Could not reboot to \"{}\" ({}): {}", target.name, id, e), "failure", PRIORITY_ERR),
+        (Action::SetNext, Outcome::Success) => (format!("Next boot set to \"{}\" ({})", target.name, id), "success", PRIORITY_INFO),
+        (Action::SetNext, Outcome::Cancelled) => (format!("Setting next boot to \"{}\" ({}) cancelled", target.name, id), "cancelled", PRIORITY_NOTICE),
+        (Action::SetNext, Outcome::Failure(e)) => (format!("Could not set next boot to \"{}\" ({}): {}", target.name, id, e), "failure", PRIORITY_ERR),
+    };
+
+    send(&encode(&[
+        ("MESSAGE", &message),
+        ("PRIORITY", &priority.to_string()),
+        ("SYSLOG_IDENTIFIER", "reboot-to"),
+        ("ACTION", action.field()),
+        ("RESULT", result),
+        ("TARGET_ID", &id),
+        ("TARGET_NAME", &target.name),
+    ]));
+}
+
+/// Send an action on an entry to the journal, by the result of running it
+pub fn record_result(action: Action, target: &BootTarget, result: &Result<(), CommandError>) {
+    match result {
+        Ok(()) => record(action, target, Outcome::Success),
+        Err(e) => record(action, target, Outcome::Failure(&e.message)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_native_entries() {
+        assert_eq!(encode(&[("MESSAGE", "Rebooting"), ("TARGET_ID", "0001")]), b"MESSAGE=Rebooting\nTARGET_ID=0001\n");
+
+        let mut multiline = b"MESSAGE\n".to_vec();
+        multiline.extend(4_u64.to_le_bytes());
+        multiline.extend(b"a\nbc\n");
+        assert_eq!(encode(&[("MESSAGE", "a\nbc")]), multiline);
+    }
+}
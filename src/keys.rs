@@ -0,0 +1,317 @@
+/***
+ * Key bindings
+ *
+ * Maps keys to TUI actions. The defaults can be changed per action in the
+ * `[keys]` section of the configuration file, e.g. `set_next = ["Space", "n"]`.
+ * A key bound to two actions makes the whole section ignored.
+ ***/
+use std::fmt;
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::config::Section;
+
+/// Something a key can do in the TUI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Up,
+    Down,
+    First,
+    Last,
+    PageUp,
+    PageDown,
+    Reboot,
+    SetNext,
+    /// Move the selected entry to the front of BootOrder
+    SetDefault,
+    /// Clear the pending BootNext
+    ClearNext,
+    /// Flip the active flag of the selected entry
+    ToggleActive,
+    Delete,
+    Refresh,
+    /// Put back BootNext and BootOrder from before the last change
+    Undo,
+    RemoveDuplicates,
+    NextTab,
+    PreviousTab,
+    /// Show or hide the commands run
+    ToggleCommands,
+    /// Hide or show network boot entries
+    ToggleNetwork,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 20] = [
+        Action::Up, Action::Down, Action::First, Action::Last, Action::PageUp, Action::PageDown,
+        Action::Reboot, Action::SetNext, Action::SetDefault, Action::ClearNext, Action::ToggleActive, Action::Delete, Action::Refresh, Action::Undo, Action::RemoveDuplicates,
+        Action::NextTab, Action::PreviousTab, Action::ToggleCommands, Action::ToggleNetwork, Action::Quit,
+    ];
+
+    /// Name of the action in the configuration file
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::First => "first",
+            Action::Last => "last",
+            Action::PageUp => "page_up",
+            Action::PageDown => "page_down",
+            Action::Reboot => "reboot",
+            Action::SetNext => "set_next",
+            Action::SetDefault => "set_default",
+            Action::ClearNext => "clear_next",
+            Action::ToggleActive => "toggle_active",
+            Action::Delete => "delete",
+            Action::Refresh => "refresh",
+            Action::Undo => "undo",
+            Action::RemoveDuplicates => "remove_duplicates",
+            Action::NextTab => "next_tab",
+            Action::PreviousTab => "previous_tab",
+            Action::ToggleCommands => "toggle_commands",
+            Action::ToggleNetwork => "toggle_network",
+            Action::Quit => "quit",
+        }
+    }
+
+    fn default_keys(self) -> Vec<Key> {
+        let keys = match self {
+            Action::Up => vec![KeyCode::Up],
+            Action::Down => vec![KeyCode::Down],
+            Action::First => vec![KeyCode::Home],
+            Action::Last => vec![KeyCode::End],
+            Action::PageUp => vec![KeyCode::PageUp],
+            Action::PageDown => vec![KeyCode::PageDown],
+            Action::Reboot => vec![KeyCode::Enter],
+            Action::SetNext => vec![KeyCode::Char('n')],
+            Action::SetDefault => vec![KeyCode::Char('d')],
+            Action::ClearNext => vec![KeyCode::Char('x')],
+            Action::ToggleActive => vec![KeyCode::Char('a')],
+            Action::Delete => vec![KeyCode::Delete, KeyCode::Char('D')],
+            Action::Refresh => vec![KeyCode::Char('r')],
+            Action::Undo => vec![KeyCode::Char('u')],
+            Action::RemoveDuplicates => vec![KeyCode::Char('c')],
+            Action::NextTab => vec![KeyCode::Tab],
+            Action::PreviousTab => vec![KeyCode::BackTab],
+            Action::ToggleCommands => vec![KeyCode::Char('o')],
+            Action::ToggleNetwork => vec![KeyCode::Char('p')],
+            Action::Quit => vec![KeyCode::Esc, KeyCode::Char('q')],
+        };
+        keys.into_iter().map(|code| Key { code, modifiers: KeyModifiers::NONE }).collect()
+    }
+}
+
+/// A key with the Ctrl and Alt modifiers it needs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+/// Names of special keys, the first name of each is used in key hints
+const KEY_NAMES: [(&str, KeyCode); 16] = [
+    ("Enter", KeyCode::Enter),
+    ("Space", KeyCode::Char(' ')),
+    ("Del", KeyCode::Delete),
+    ("Delete", KeyCode::Delete),
+    ("Backspace", KeyCode::Backspace),
+    ("Esc", KeyCode::Esc),
+    ("Tab", KeyCode::Tab),
+    ("Shift+Tab", KeyCode::BackTab),
+    ("BackTab", KeyCode::BackTab),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("Insert", KeyCode::Insert),
+];
+
+impl Key {
+    /// Parse a key like "Enter", "n", "F5" or "Ctrl+r". Special key names are
+    /// case-insensitive, single characters are not.
+    pub fn parse(text: &str) -> Option<Key> {
+        // Shift is only a modifier for Tab, for other keys it changes the character
+        if text.eq_ignore_ascii_case("shift+tab") {
+            return Some(Key { code: KeyCode::BackTab, modifiers: KeyModifiers::NONE });
+        }
+
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        while let Some((modifier, after)) = rest.split_once('+').filter(|(_, after)| !after.is_empty()) {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                _ => return None,
+            };
+            rest = after;
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => {
+                let lower = rest.to_lowercase();
+                match KEY_NAMES.iter().find(|(name, _)| name.to_lowercase() == lower) {
+                    Some((_, code)) => *code,
+                    None => match lower.as_str() {
+                        "pageup" | "pgup" => KeyCode::PageUp,
+                        "pagedown" | "pgdn" => KeyCode::PageDown,
+                        _ => KeyCode::F(lower.strip_prefix('f')?.parse().ok().filter(|n| (1..=24).contains(n))?),
+                    },
+                }
+            }
+        };
+
+        Some(Key { code, modifiers })
+    }
+
+    fn matches(&self, event: &KeyEvent) -> bool {
+        // Shift is part of the character for letters, so only Ctrl and Alt count
+        let modifiers = event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+        self.code == event.code && self.modifiers == modifiers
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            code => match KEY_NAMES.iter().find(|(_, c)| *c == code) {
+                Some((name, _)) => write!(f, "{}", name),
+                None => write!(f, "{:?}", code),
+            },
+        }
+    }
+}
+
+/// Keys bound to each action
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    bindings: Vec<(Action, Vec<Key>)>,
+}
+
+impl Default for KeyMap {
+    fn default() -> KeyMap {
+        KeyMap { bindings: Action::ALL.iter().map(|action| (*action, action.default_keys())).collect() }
+    }
+}
+
+impl KeyMap {
+    /// Apply the `[keys]` section of the configuration file to the default bindings
+    pub fn from_section(section: &Section) -> Result<KeyMap, String> {
+        let mut map = KeyMap::default();
+
+        for (name, value) in section.iter() {
+            let action = Action::ALL.iter().find(|action| action.name() == name)
+                .ok_or(format!("unknown action {:?}", name))?;
+            let names = value.strings().ok_or(format!("{} should be a key or a list of keys", name))?;
+            let keys = names.iter()
+                .map(|key| Key::parse(key).ok_or(format!("unknown key {:?} for {}", key, name)))
+                .collect::<Result<Vec<Key>, String>>()?;
+
+            if let Some((_, bound)) = map.bindings.iter_mut().find(|(a, _)| a == action) {
+                *bound = keys;
+            }
+        }
+
+        map.check_conflicts()?;
+        Ok(map)
+    }
+
+    fn check_conflicts(&self) -> Result<(), String> {
+        for (i, (action, keys)) in self.bindings.iter().enumerate() {
+            for (other, other_keys) in self.bindings.iter().skip(i + 1) {
+                if let Some(key) = keys.iter().find(|key| other_keys.contains(key)) {
+                    return Err(format!("{} is bound to both {} and {}", key, action.name(), other.name()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Action bound to a pressed key
+    pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+        self.bindings.iter()
+            .find(|(_, keys)| keys.iter().any(|key| key.matches(event)))
+            .map(|(action, _)| *action)
+    }
+
+    fn keys(&self, action: Action) -> &[Key] {
+        self.bindings.iter().find(|(a, _)| *a == action).map(|(_, keys)| keys.as_slice()).unwrap_or_default()
+    }
+
+    /// Keys of an action for key hints, like "Esc/q", or "-" when unbound
+    pub fn label(&self, action: Action) -> String {
+        let keys = self.keys(action);
+        if keys.is_empty() {
+            return String::from("-");
+        }
+        keys.iter().map(Key::to_string).collect::<Vec<String>>().join("/")
+    }
+
+    /// First key of an action, or "-" when unbound, where there is no room for all
+    pub fn short_label(&self, action: Action) -> String {
+        self.keys(action).first().map(Key::to_string).unwrap_or(String::from("-"))
+    }
+
+    /// First key of each action, like "Up/Down", for actions hinted together
+    pub fn pair_label(&self, first: Action, second: Action) -> String {
+        format!("{}/{}", self.short_label(first), self.short_label(second))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse;
+
+    fn press(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn parses_key_names() {
+        assert_eq!(Key::parse("enter"), Some(Key { code: KeyCode::Enter, modifiers: KeyModifiers::NONE }));
+        assert_eq!(Key::parse("Ctrl+r"), Some(Key { code: KeyCode::Char('r'), modifiers: KeyModifiers::CONTROL }));
+        assert_eq!(Key::parse("+"), Some(Key { code: KeyCode::Char('+'), modifiers: KeyModifiers::NONE }));
+        assert_eq!(Key::parse("F5").map(|key| key.to_string()), Some(String::from("F5")));
+        assert_eq!(Key::parse("Hyper+x"), None);
+        assert_eq!(Key::parse("F99"), None);
+    }
+
+    #[test]
+    fn rebinds_actions() {
+        let document = parse("[keys]\nreboot = \"Ctrl+b\"\nset_next = [\"Space\", \"Enter\"]").expect("config should parse");
+        let map = KeyMap::from_section(&document["keys"]).expect("bindings should be valid");
+
+        assert_eq!(map.action(&press(KeyCode::Enter)), Some(Action::SetNext));
+        assert_eq!(map.action(&press(KeyCode::Char(' '))), Some(Action::SetNext));
+        assert_eq!(map.action(&KeyEvent::new(KeyCode::Char('b'), KeyModifiers::CONTROL)), Some(Action::Reboot));
+        assert_eq!(map.action(&press(KeyCode::Char('n'))), None);
+        assert_eq!(map.label(Action::SetNext), "Space/Enter");
+        assert_eq!(map.label(Action::Quit), "Esc/q");
+        // Shift is part of the character
+        assert_eq!(map.action(&KeyEvent::new(KeyCode::Char('D'), KeyModifiers::SHIFT)), Some(Action::Delete));
+    }
+
+    #[test]
+    fn rejects_conflicts() {
+        let document = parse("[keys]\nset_next = \"Enter\"").expect("config should parse");
+
+        assert_eq!(KeyMap::from_section(&document["keys"]).unwrap_err(), "Enter is bound to both reboot and set_next");
+    }
+}
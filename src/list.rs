@@ -0,0 +1,246 @@
+/***
+ * List output
+ *
+ * Formats the entries printed by --list: an aligned table with the state of
+ * each entry, the bare "ID <tab> name" lines of earlier versions for scripts
+ * that still parse those, or TSV and CSV for spreadsheets and inventory
+ * tools. The TSV and CSV columns are fixed, untranslated, and only ever
+ * added to at the end. On a terminal the table and lines are colored like
+ * the TUI, unless NO_COLOR is set.
+ *
+ * The waybar and polybar formats show the pending next entry instead, as a
+ * single line for a status bar module.
+ ***/
+use std::{env, io::{stdout, IsTerminal}};
+
+use clap::ValueEnum;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{efibootmgr::format_id, i18n::tr, json::Value, BootTarget, BootTargets, SortKey};
+
+/// Layout of the --list output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Aligned columns with a header
+    Table,
+    /// One "ID <tab> name" line per entry
+    Simple,
+    /// Tab-separated values with a header
+    Tsv,
+    /// Comma-separated values with a header
+    Csv,
+    /// JSON for a waybar custom module
+    Waybar,
+    /// Plain line for a polybar script module
+    Polybar,
+}
+
+impl Format {
+    /// Whether the format shows the pending next entry rather than the list
+    pub fn is_status(self) -> bool {
+        matches!(self, Format::Waybar | Format::Polybar)
+    }
+}
+
+/// Columns of the TSV and CSV output
+const COLUMNS: [&str; 8] = ["id", "name", "active", "order", "current", "next", "loader", "device_path"];
+
+/// Cells of an entry's row in the table
+fn row(targets: &BootTargets, target: &BootTarget, verbose: bool) -> Vec<String> {
+    let position = targets.order.iter().position(|id| *id == target.id);
+    let boot: Vec<&str> = [(targets.current, tr!("current")), (targets.next, tr!("next"))].into_iter()
+        .filter(|(id, _)| *id == Some(target.id))
+        .map(|(_, marker)| marker)
+        .collect();
+
+    let mut cells = vec![
+        format_id(target.id),
+        String::from(if target.active { tr!("yes") } else { tr!("no") }),
+        position.map(|i| (i + 1).to_string()).unwrap_or(String::from("-")),
+        boot.join(","),
+        target.name.clone(),
+    ];
+    if verbose {
+        cells.push(target.device_path.as_ref().and_then(|path| path.file()).unwrap_or("-").to_string());
+        cells.push(target.device_path.as_ref().and_then(|path| path.optional_data.as_deref()).unwrap_or("-").to_string());
+    }
+    cells
+}
+
+/// Cells of an entry's row in the TSV and CSV output, matching COLUMNS
+fn record(targets: &BootTargets, target: &BootTarget) -> Vec<String> {
+    let position = targets.order.iter().position(|id| *id == target.id);
+
+    vec![
+        format_id(target.id),
+        target.name.clone(),
+        target.active.to_string(),
+        position.map(|i| (i + 1).to_string()).unwrap_or_default(),
+        (targets.current == Some(target.id)).to_string(),
+        (targets.next == Some(target.id)).to_string(),
+        target.device_path.as_ref().and_then(|path| path.file()).unwrap_or_default().to_string(),
+        target.device_path.as_ref().map(|path| path.as_str()).unwrap_or_default().to_string(),
+    ]
+}
+
+/// TSV has no quoting, so tabs and line breaks in a cell become spaces
+fn tsv_cell(cell: &str) -> String {
+    cell.replace(['\t', '\n', '\r'], " ")
+}
+
+/// CSV cell, quoted if it contains a separator, quote or line break
+fn csv_cell(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+/// Color the line of an entry: the current entry is highlighted, the next
+/// one marked and inactive ones dimmed
+fn paint(targets: &BootTargets, target: &BootTarget, line: String) -> String {
+    // ANSI bold bright green, bold bright yellow and gray
+    let style = if targets.current == Some(target.id) {
+        "1;92"
+    } else if targets.next == Some(target.id) {
+        "1;93"
+    } else if !target.active {
+        "90"
+    } else {
+        return line;
+    };
+    format!("\x1b[{}m{}\x1b[0m", style, line)
+}
+
+/// Rows padded to the widest cell of each column, without trailing spaces
+fn align(rows: &[Vec<String>]) -> Vec<String> {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or_default();
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| rows.iter().filter_map(|row| row.get(column)).map(|cell| cell.width()).max().unwrap_or_default())
+        .collect();
+
+    rows.iter().map(|row| {
+        let mut line = String::new();
+        for (cell, width) in row.iter().zip(widths.iter()) {
+            line.push_str(cell);
+            line.push_str(&" ".repeat(width - cell.width() + 2));
+        }
+        line.trim_end().to_string()
+    }).collect()
+}
+
+/// Status bar line with the pending next entry, empty when there is none so
+/// that the bar hides the module
+fn status(targets: &BootTargets, format: Format) -> String {
+    let name = |id: Option<u16>| id.map(|id| targets.by_id(id).map(|target| target.name.clone()).unwrap_or(format_id(id)));
+    let next = name(targets.next);
+    let text = next.as_ref().map(|next| tr!("next: {}", next)).unwrap_or_default();
+
+    if format == Format::Polybar {
+        return text + "\n";
+    }
+
+    let tooltip = [
+        tr!("Current boot: {}", name(targets.current).unwrap_or(String::from(tr!("unknown")))),
+        tr!("Next boot: {}", next.unwrap_or(String::from(tr!("default order")))),
+    ];
+    let class = if targets.next.is_some() { "pending" } else { "none" };
+    Value::object([("text", text.into()), ("tooltip", tooltip.join("\n").into()), ("class", class.into())]).to_compact() + "\n"
+}
+
+/// Whether the list is shown on a terminal that should get colors
+pub fn use_color() -> bool {
+    stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+/// The entries in the given format, with the loader path and optional data
+/// of each entry if verbose, and colored if `color`
+pub fn render(targets: &BootTargets, sort: SortKey, format: Format, verbose: bool, color: bool) -> String {
+    let sorted = targets.sorted(sort);
+    let paint = |target: &BootTarget, line: String| if color { paint(targets, target, line) } else { line } + "\n";
+
+    match format {
+        Format::Waybar | Format::Polybar => status(targets, format),
+        Format::Simple => sorted.iter().map(|target| paint(target, format!("{} \t {}", format_id(target.id), target.name))).collect(),
+        Format::Tsv | Format::Csv => {
+            let (escape, separator): (fn(&str) -> String, &str) = match format {
+                Format::Tsv => (tsv_cell, "\t"),
+                _ => (csv_cell, ","),
+            };
+
+            std::iter::once(COLUMNS.map(String::from).to_vec())
+                .chain(sorted.iter().map(|target| record(targets, target)))
+                .map(|cells| cells.iter().map(|cell| escape(cell)).collect::<Vec<String>>().join(separator) + "\n")
+                .collect()
+        }
+        Format::Table => {
+            let mut header = vec![tr!("ID"), tr!("ACTIVE"), tr!("ORDER"), tr!("BOOT"), tr!("NAME")];
+            if verbose {
+                header.extend([tr!("LOADER"), tr!("OPTIONS")]);
+            }
+
+            let rows: Vec<Vec<String>> = std::iter::once(header.into_iter().map(String::from).collect())
+                .chain(sorted.iter().map(|target| row(targets, target, verbose)))
+                .collect();
+            let mut lines = align(&rows).into_iter();
+
+            let header = lines.next().unwrap_or_default() + "\n";
+            header + &sorted.iter().zip(lines).map(|(target, line)| paint(target, line)).collect::<String>()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_boot_targets;
+
+    const OUTPUT: &str = "BootCurrent: 0001\n\
+        BootNext: 0002\n\
+        BootOrder: 0001,0000\n\
+        Boot0000* Windows Boot Manager\tHD(1,GPT,4f3c2a1b-9e8d-4c7b-a6f5-0e1d2c3b4a59,0x800,0x82000)/File(\\EFI\\Microsoft\\Boot\\bootmgfw.efi)\n\
+        Boot0001* ubuntu\tHD(1,GPT,4f3c2a1b-9e8d-4c7b-a6f5-0e1d2c3b4a59,0x800,0x82000)/File(\\EFI\\ubuntu\\shimx64.efi)\n\
+        Boot0002  UEFI Shell\n";
+
+    #[test]
+    fn renders_table() {
+        let targets = parse_boot_targets(&OUTPUT.replace("bootmgfw.efi)\n", "bootmgfw.efi)WINDOWS.........\n"));
+
+        assert_eq!(render(&targets, SortKey::Order, Format::Table, true, false), "\
+ID    ACTIVE  ORDER  BOOT     NAME                  LOADER                            OPTIONS
+0001  yes     1      current  ubuntu                \\EFI\\ubuntu\\shimx64.efi           -
+0000  yes     2               Windows Boot Manager  \\EFI\\Microsoft\\Boot\\bootmgfw.efi  WINDOWS.........
+0002  no      -      next     UEFI Shell            -                                 -
+");
+    }
+
+    #[test]
+    fn renders_csv() {
+        let mut targets = parse_boot_targets(OUTPUT);
+        targets.targets[2].name = String::from("Shell, \"built-in\"");
+
+        assert_eq!(render(&targets, SortKey::Id, Format::Csv, false, false), "\
+id,name,active,order,current,next,loader,device_path
+0000,Windows Boot Manager,true,2,false,false,\\EFI\\Microsoft\\Boot\\bootmgfw.efi,\"HD(1,GPT,4f3c2a1b-9e8d-4c7b-a6f5-0e1d2c3b4a59,0x800,0x82000)/File(\\EFI\\Microsoft\\Boot\\bootmgfw.efi)\"
+0001,ubuntu,true,1,true,false,\\EFI\\ubuntu\\shimx64.efi,\"HD(1,GPT,4f3c2a1b-9e8d-4c7b-a6f5-0e1d2c3b4a59,0x800,0x82000)/File(\\EFI\\ubuntu\\shimx64.efi)\"
+0002,\"Shell, \"\"built-in\"\"\",false,,false,true,,
+");
+    }
+
+    #[test]
+    fn renders_status_bar_module() {
+        let targets = parse_boot_targets(OUTPUT);
+
+        assert_eq!(render(&targets, SortKey::Order, Format::Waybar, false, false),
+            "{\"class\":\"pending\",\"text\":\"next: UEFI Shell\",\"tooltip\":\"Current boot: ubuntu\\nNext boot: UEFI Shell\"}\n");
+        assert_eq!(render(&targets, SortKey::Order, Format::Polybar, false, false), "next: UEFI Shell\n");
+    }
+
+    #[test]
+    fn renders_simple_lines() {
+        let targets = parse_boot_targets(OUTPUT);
+
+        assert_eq!(render(&targets, SortKey::Id, Format::Simple, false, false), "0000 \t Windows Boot Manager\n0001 \t ubuntu\n0002 \t UEFI Shell\n");
+    }
+}
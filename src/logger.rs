@@ -0,0 +1,85 @@
+/***
+ * Logging
+ *
+ * Minimal `log` backend writing warnings, and with --debug executed commands,
+ * to stderr. While the TUI owns the terminal, messages are held back and
+ * printed once it has been cleaned up.
+ *
+ * Messages for people, like "Deleted boot entry", go through `say!`, which
+ * prints them to stdout, or with --quiet to stderr so that stdout only has the
+ * output scripts ask for.
+ ***/
+use std::sync::{atomic::{AtomicBool, Ordering}, Mutex};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+struct Logger;
+
+/// Messages held back while the TUI is shown
+static HELD: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+/// Whether messages for people go to stderr
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Print a message for people, like `println!`, to stdout or with --quiet to stderr
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if $crate::logger::quiet() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+pub(crate) use say;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let level = match record.level() {
+            Level::Warn => String::from("warning"),
+            level => level.as_str().to_lowercase(),
+        };
+        let line = format!("{}: {}", level, record.args());
+        match HELD.lock().expect("log lock should not be poisoned").as_mut() {
+            Some(held) => held.push(line),
+            None => eprintln!("{}", line),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Install the logger, showing messages up to `level`
+pub fn init(level: LevelFilter) {
+    if log::set_logger(&Logger).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// Send messages for people to stderr from now on
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Hold back messages until `release` is called
+pub fn hold() {
+    HELD.lock().expect("log lock should not be poisoned").get_or_insert_with(Vec::new);
+}
+
+/// Print held back messages, and print new ones immediately again
+pub fn release() {
+    let held = HELD.lock().expect("log lock should not be poisoned").take();
+    held.into_iter().flatten().for_each(|line| eprintln!("{}", line));
+}
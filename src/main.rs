@@ -29,26 +29,100 @@
  * ********************************************************************************
  * 
  ***/
-use std::{io::{stdout, Result}, process::{Command, ExitCode, ExitStatus}, str::FromStr};
-use regex::Regex;
-
-use clap::Parser;
+// Without the TUI, helpers only it uses are left over
+#![cfg_attr(not(feature = "tui"), allow(dead_code))]
+
+use std::{io::{stdin, stdout, BufRead, IsTerminal, Write}, path::PathBuf, process::{Command, ExitCode}, time::Duration};
+#[cfg(feature = "tui")]
+use std::{io::Result, sync::{atomic::{AtomicBool, Ordering}, mpsc, Arc}, thread, time::Instant};
+
+#[cfg(feature = "tui")]
+mod accessible;
+mod backup;
+mod config;
+#[cfg(any(windows, test))]
+mod bcdedit;
+#[cfg(any(target_os = "macos", test))]
+mod bless;
+mod cache;
+#[cfg(unix)]
+mod daemon;
+mod delay;
+mod demo;
+mod disk;
+mod doctor;
+mod dump;
+mod duplicates;
+mod efibootmgr;
+mod efivar;
+mod esp;
+#[cfg(feature = "tui")]
+mod events;
+mod firmware;
+mod fwupd;
+mod hibernation;
+mod history;
+mod i18n;
+mod journal;
+#[cfg(feature = "tui")]
+mod keys;
+mod json;
+mod list;
+mod logger;
+mod os;
+mod os_release;
+mod parser;
+mod process;
+mod reboot;
+mod restore;
+mod rpc;
+mod safety;
+mod schedule;
+#[cfg(feature = "tui")]
+mod sections;
+mod session;
+mod sha256;
+mod sudo;
+mod tempdir;
+#[cfg(feature = "tui")]
+mod theme;
+#[cfg(feature = "tui")]
+mod ui;
+mod undo;
+mod verify;
+mod wizard;
+use i18n::tr;
+use logger::say;
+use config::Config;
+#[cfg(feature = "tui")]
+use keys::Action;
+use efibootmgr::{format_id, parse_id, set_next_boot, CommandError};
+#[cfg(feature = "tui")]
+use events::{Event, Events};
+use parser::DevicePath;
+#[cfg(feature = "tui")]
+use ui::Tab;
+use verify::LoaderStatus;
+
+use clap::{ArgGroup, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+#[cfg(feature = "tui")]
 use ratatui::{
     backend::CrosstermBackend, crossterm::{
         event::{self, KeyCode, KeyEventKind, KeyModifiers},
         terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
         ExecutableCommand,
-    }, style::{Color, Modifier, Style, Stylize}, text::Line, widgets::{block::Title, Block, List, ListDirection, ListState}, Terminal
+    }, widgets::ListState, Terminal, TerminalOptions, Viewport
 };
 
 
 
 #[derive(Debug, Parser)]
+#[command(group(ArgGroup::new("rebooting").args(["reboot_to", "last"])))]
 #[command(version, about = "Convenience TUI wrapper around efibootmgr", long_about = "reboot-to is a terminal UI (TUI) wrapper around the efibootmgr and shudown commands, intended to provide a simple way to reboot into another UEFI boot entry (typically another operating system).
 
 When executed without any arguments you will be able to select a UEFI boot entry in a TUI.
 
-Some of the options below require specifying a UEFI boot entry using a parameter called <DEST>. Valid values for <DEST> are either a number or a text. Numbers will be matched against the ID of boot entries, this ID can be retrieved by using the --list option, or by running efibootmgr without arguments. Text will be matched against the name of the boot entries, case-sensitive and from the start. For example, a boot entry named \"ubuntu\" will be matched by \"ub\" but not by \"Ub\" nor by \"bun\".
+Some of the options below require specifying a UEFI boot entry using a parameter called <DEST>. Valid values for <DEST> are either an ID or a text. IDs are hexadecimal, like 0001 or 000A, and will be matched against the ID of boot entries, this ID can be retrieved by using the --list option, or by running efibootmgr without arguments. Text will be matched against the name of the boot entries, case-sensitive and from the start. For example, a boot entry named \"ubuntu\" will be matched by \"ub\" but not by \"Ub\" nor by \"bun\". A <DEST> of \"-\" is read from the first line of standard input, so that other programs can pipe in the entry, e.g. `echo \"Windows Boot Manager\" | reboot-to -n -`.
 
 This executable runs the \"shutdown\" and \"efibootmgr\" commands. These should be available in PATH, and the executable should be ran with appropriate permission.
 ")]
@@ -58,6 +132,26 @@ struct Arguments {
     #[arg(short, long, action = clap::ArgAction::SetTrue, help = "Output a list of boot entries and their IDs")]
     list: Option<bool>,
 
+    // Orders the list
+    #[arg(long, value_name = "KEY", value_enum, default_value_t = SortKey::Order, help = "Order of the --list output: by position in BootOrder with entries not in it last, by ID, by name, or active entries first")]
+    sort: SortKey,
+
+    // Layout of the list
+    #[arg(long, value_name = "FORMAT", value_enum, default_value_t = list::Format::Table, help = "Layout of the --list output: a table with the state of each entry, the \"ID <tab> name\" lines of earlier versions, or tab- or comma-separated values with the columns id, name, active, order, current, next, loader and device_path. The waybar and polybar formats output the pending next entry for a status bar module instead, and need no --list")]
+    format: list::Format,
+
+    // Shows more details
+    #[arg(short, long, action = clap::ArgAction::SetTrue, help = "Include the loader path and optional data of each entry in the --list table")]
+    verbose: Option<bool>,
+
+    // Resolves an entry
+    #[arg(long, value_name = "DEST", help = "Print the four-digit hexadecimal ID (the #### of Boot####) of the entry specified by <DEST>, or fail like the other options if it matches none or several, e.g. to pass it to efibootmgr")]
+    resolve: Option<String>,
+
+    // Reboots to the previous entry
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "reboot_to", help = "Reboot directly to the entry rebooted to most recently by reboot-to, with the same checks as --reboot-to")]
+    last: Option<bool>,
+
     // Set next boot target
     #[arg(short, long, value_name = "DEST", help = "Set the entry specified by <DEST> as the next (one-time) boot target")]
     next: Option<String>,
@@ -65,337 +159,2523 @@ struct Arguments {
     // Reboots to provided destination
     #[arg(short, long, value_name = "DEST", help = "Reboot directly to the entry specified by <DEST>")]
     reboot_to: Option<String>,
+
+    // Counts down before rebooting
+    #[arg(long, value_name = "DURATION", value_parser = delay::parse, requires = "rebooting", help = "With --reboot-to or --last, count down <DURATION>, like 10s, 2m or a number of seconds, before rebooting. Ctrl+C during the countdown cancels the reboot and puts BootNext back")]
+    delay: Option<Duration>,
+
+    // Reboots later
+    #[arg(long, value_name = "TIME", conflicts_with = "delay", requires = "rebooting", help = "With --reboot-to or --last, reboot at <TIME> instead of now, through a systemd timer that is unaffected by closing the terminal. <TIME> is a systemd calendar time, like 03:00 or \"2026-10-16 03:00\", or a delay like 30m. reboot-to jobs lists and cancels scheduled reboots")]
+    at: Option<String>,
+
+    // Verifies loaders exist
+    #[arg(long, value_name = "DEST", num_args = 0..=1, help = "Check that the EFI loader of the entry specified by <DEST> (or of all entries) exists")]
+    verify: Option<Option<String>>,
+
+    // Deletes an entry
+    #[arg(long, value_name = "DEST", help = "Delete the entry specified by <DEST>, after typing its name or ID to confirm")]
+    delete: Option<String>,
+
+    // Duplicates an entry
+    #[arg(long, num_args = 2, value_names = ["DEST", "LABEL"], help = "Create a copy of the entry specified by <DEST>, with the same loader and options but named <LABEL>")]
+    duplicate: Option<Vec<String>>,
+
+    // Renames an entry
+    #[arg(long, num_args = 2, value_names = ["DEST", "LABEL"], help = "Rename the entry specified by <DEST> to <LABEL>, keeping its options and BootOrder position")]
+    rename: Option<Vec<String>>,
+
+    // Deletes duplicate entries
+    #[arg(long, action = clap::ArgAction::SetTrue, help = "Delete entries with the same device path as another entry, keeping the booted one or the first in BootOrder, after confirming")]
+    remove_duplicates: Option<bool>,
+
+    // Prints the pending next entry
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "get_current", help = "Output the name of the entry set as the next (one-time) boot target, or nothing and exit with 1 if none is set")]
+    get_next: Option<bool>,
+
+    // Prints the booted entry
+    #[arg(long, action = clap::ArgAction::SetTrue, help = "Output the name of the currently booted entry, or nothing and exit with 1 if it is unknown")]
+    get_current: Option<bool>,
+
+    // Prints IDs instead of names
+    #[arg(long, action = clap::ArgAction::SetTrue, help = "Make --get-next and --get-current output the ID of the entry instead of its name")]
+    id: Option<bool>,
+
+    // Prints the boot menu timeout
+    #[arg(long, action = clap::ArgAction::SetTrue, help = "Output the UEFI boot menu timeout in seconds")]
+    get_timeout: Option<bool>,
+
+    // Sets the boot menu timeout
+    #[arg(long, value_name = "SECONDS", help = "Set the UEFI boot menu timeout in seconds")]
+    set_timeout: Option<u16>,
+
+    // Puts back BootNext and BootOrder
+    #[arg(long, action = clap::ArgAction::SetTrue, help = "Put back BootNext and BootOrder as they were before the last change made by reboot-to, e.g. after setting the wrong next boot entry. Undoing again redoes the change")]
+    undo: Option<bool>,
+
+    // Takes requests from another program
+    #[arg(long, action = clap::ArgAction::SetTrue, help = "Answer JSON-RPC 2.0 requests, one per line on stdin, on stdout, for frontends that drive reboot-to as a subprocess. The methods are list, resolve, set_next, reboot and clear, see the README")]
+    serve_stdio: Option<bool>,
+
+    // Takes requests from other programs on a socket
+    #[cfg(unix)]
+    #[arg(long, value_name = "PATH", num_args = 0..=1, conflicts_with = "serve_stdio", help = "Answer the JSON-RPC requests of --serve-stdio on a Unix socket at <PATH> until ended, e.g. for status bar widgets. Only the user running it can connect, or also members of the group set in the configuration. Started by a systemd socket unit, the socket passed by systemd is used instead [default: $XDG_RUNTIME_DIR/reboot-to.sock, or /run/reboot-to.sock]")]
+    serve_socket: Option<Option<PathBuf>>,
+
+    // Skips all confirmations and safety checks
+    #[arg(short, long, visible_alias = "force", action = clap::ArgAction::SetTrue, help = "Do not ask for confirmation and skip all safety checks, e.g. reboot even while a package manager is running")]
+    yes: Option<bool>,
+
+    // Answers for nobody there, as scheduled reboots do
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "yes", requires = "rebooting", help = "With --reboot-to or --last, do not ask for confirmation, but still refuse to reboot while a package manager or firmware update is running. Scheduled reboots run with this")]
+    unattended: Option<bool>,
+
+    // Pins the entry of a recurring reboot by name
+    #[arg(long, value_name = "NAME", requires = "reboot_to", help = "With --reboot-to <ID>, the exact name the entry should have. The ID is only used while its entry still has this name, otherwise the one entry named exactly <NAME> is, and the reboot fails if there is none or several. Recurring reboots run with this, as an update may recreate an entry under another ID")]
+    expect_name: Option<String>,
+
+    // Keeps stdout for output scripts ask for
+    #[arg(short, long, visible_alias = "porcelain", action = clap::ArgAction::SetTrue, help = "Print messages meant for people, like confirmations of what was done and questions, to stderr instead of stdout, so that stdout only has requested output like --list, --resolve or --get-next")]
+    quiet: Option<bool>,
+
+    // Logs executed commands
+    #[arg(long, action = clap::ArgAction::SetTrue, help = "Log executed commands and their output to stderr")]
+    debug: Option<bool>,
+
+    // Limits how long commands may take
+    #[arg(long, value_name = "SECONDS", default_value_t = process::DEFAULT_TIMEOUT, help = "Kill efibootmgr and other commands that take longer than <SECONDS>")]
+    command_timeout: u64,
+
+    // Uses fake entries
+    #[arg(long, action = clap::ArgAction::SetTrue, help = "Show made-up boot entries and do not run any system command, e.g. to try the TUI. Also enabled by setting REBOOT_TO_MOCK, optionally to a file with efibootmgr -v output to show instead")]
+    demo: Option<bool>,
+
+    // Uses a screen-reader-friendly interface
+    #[cfg(feature = "tui")]
+    #[arg(long, action = clap::ArgAction::SetTrue, help = "Pick an entry without the full-screen TUI: the selected entry is printed as a line of text on each move, and confirmations are plain prompts, for use with screen readers")]
+    accessible: Option<bool>,
+
+    // Names the TUI
+    #[cfg(feature = "tui")]
+    #[arg(long, value_name = "TITLE", help = "Title of the TUI, where {hostname} is replaced by the name of the machine [default: \"reboot-to @ {hostname}\"]")]
+    title: Option<String>,
+
+    // Colors the TUI
+    #[cfg(feature = "tui")]
+    #[arg(long, value_name = "THEME", value_enum, help = "Colors of the TUI: the default, white text with a bright selection for low vision, or blue and orange instead of green and red for red-green color blindness [default: default]")]
+    theme: Option<theme::Preset>,
+
+    // Reads another configuration file
+    #[arg(long, value_name = "PATH", help = "Read settings from <PATH> instead of /etc/reboot-to/config.toml and the user's configuration file, e.g. to try out a new configuration")]
+    config: Option<PathBuf>,
+
+    // Ignores all configuration
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["config", "profile"], help = "Ignore configuration files and REBOOT_TO_* settings in the environment, and use the default settings")]
+    no_config: Option<bool>,
+
+    // Picks the profile sections of the configuration
+    #[arg(long, value_name = "NAME", help = "Use the [profile.<NAME>.*] sections of the configuration file over the plain ones [default: the name of the machine, if there are sections for it]")]
+    profile: Option<String>,
+
+    // Draws the TUI below the prompt
+    #[cfg(feature = "tui")]
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "accessible", help = "Draw a small TUI below the prompt instead of using the whole terminal, keeping the scrollback intact")]
+    inline: Option<bool>,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Commands {
+    /// Write all boot entries, BootOrder and timeout to a JSON file
+    Backup {
+        #[arg(value_name = "FILE", help = "File to write the backup to")]
+        file: PathBuf,
+    },
+
+    /// Recreate missing entries, BootOrder and timeout from a backup file
+    Restore {
+        #[arg(value_name = "FILE", help = "Backup file to restore from")]
+        file: PathBuf,
+
+        #[arg(long, help = "Only show the changes, do not apply them")]
+        dry_run: bool,
+    },
+
+    /// Interactively create a new boot entry
+    Create,
+
+    /// Show the current boot state: booted entry, pending next entry, BootOrder, timeout and ESP
+    Status,
+
+    /// Check that everything needed to change the next boot entry and reboot works
+    Doctor,
+
+    /// Print the version, system details, raw efibootmgr output and parsed entries for a bug report, with identifying details masked
+    DebugDump,
+
+    /// List the reboots scheduled with --at, or cancel one
+    Jobs {
+        #[arg(long, value_name = "JOB", help = "Cancel the scheduled reboot named <JOB> in the list")]
+        cancel: Option<String>,
+    },
+
+    /// Reboot to an entry on a recurring schedule, like every Sunday at 03:00
+    Schedule {
+        #[command(subcommand)]
+        action: ScheduleAction,
+    },
+
+    /// Show or change the BootOrder
+    Order {
+        #[command(subcommand)]
+        action: OrderAction,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ScheduleAction {
+    /// Reboot to an entry on a calendar schedule, until removed
+    Add {
+        #[arg(value_name = "WHEN", help = "When to reboot, as a systemd calendar event, like \"Sun 03:00\" or \"*-*-01 04:00\" for the first of every month")]
+        when: String,
+
+        #[arg(value_name = "DEST", help = "Entry to reboot to")]
+        dest: String,
+    },
+
+    /// Print the recurring reboots, one per line
+    List,
+
+    /// Stop and remove a recurring reboot
+    Remove {
+        #[arg(value_name = "NAME", help = "Name or number of the recurring reboot, as listed")]
+        name: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum OrderAction {
+    /// Print the BootOrder, one entry per line
+    Show,
+
+    /// Replace the BootOrder
+    Set {
+        #[arg(value_name = "DEST", required = true, num_args = 1.., help = "Entries in the new order, as IDs or names, separated by commas or spaces")]
+        entries: Vec<String>,
+    },
+}
+
+/// Order of the --list output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SortKey {
+    Order,
+    Id,
+    Name,
+    Active,
+}
+
+/// Exit codes, kept stable so wrapper scripts can branch on them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Exit {
+    Success = 0,
+    Failure = 1,
+    // 2 is used by clap for invalid arguments
+    NotFound = 3,
+    Ambiguous = 4,
+    EfibootmgrFailed = 5,
+    PermissionDenied = 6,
+    RebootFailed = 7,
+    Cancelled = 8,
+    NotUefi = 9,
+}
+
+impl Exit {
+    /// Exit code for a failed command, preferring PermissionDenied when applicable
+    fn from_command(error: &CommandError, otherwise: Exit) -> Exit {
+        if error.permission_denied { Exit::PermissionDenied } else { otherwise }
+    }
+}
+
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> ExitCode {
+        ExitCode::from(exit as u8)
+    }
 }
 
+/// Failure to resolve a <DEST> specifier to a boot entry
 #[derive(Debug)]
+enum LookupError {
+    NotFound(String),
+    Ambiguous(String, Vec<String>),
+}
+
+impl LookupError {
+    fn exit(&self) -> Exit {
+        match self {
+            LookupError::NotFound(_) => Exit::NotFound,
+            LookupError::Ambiguous(_, _) => Exit::Ambiguous,
+        }
+    }
+}
+
+impl std::fmt::Display for LookupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LookupError::NotFound(query) => write!(f, "{}", tr!("Could not find UEFI boot entry from specifier \"{}\"", query)),
+            LookupError::Ambiguous(query, names) => write!(f, "{}", tr!("Specifier \"{}\" is ambiguous, it matches: {}", query, names.join(", "))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 struct BootTarget {
     id: u16,
     name: String,
+    /// Inactive entries stay in NVRAM but are skipped by the firmware boot menu
+    active: bool,
+    device_path: Option<DevicePath>,
+    loader: LoaderStatus,
+    /// Partition the entry boots from and the model of its disk, see `disk`
+    device: Option<String>,
+    /// System the entry starts, for entries with a name like "Linux Boot
+    /// Manager" or "Windows Boot Manager", see `os_release`
+    os_name: Option<String>,
+    /// The disk it boots from is removable, like a USB stick, see `disk`
+    removable: bool,
 }
 
 #[derive(Debug)]
 struct BootTargets {
     targets: Vec<BootTarget>,
     current: Option<u16>,
-    next: Option<u16>
+    next: Option<u16>,
+    order: Vec<u16>,
+    timeout: Option<u16>,
 }
 
-enum ChosenAction<'a> {
-    None,
-    RebootTo(&'a BootTarget),
-    SetNext(&'a BootTarget),
+/// Recent entries shown above the TUI list
+#[cfg(feature = "tui")]
+const RECENT_ENTRIES: usize = 3;
+
+/// Rows the TUI takes at most with --inline
+#[cfg(feature = "tui")]
+const INLINE_MAX_HEIGHT: u16 = 12;
+
+/// Title of the TUI unless configured, telling apart sessions on different machines
+#[cfg(feature = "tui")]
+const DEFAULT_TITLE: &str = "reboot-to @ {hostname}";
 
+/// Lines of the commands run kept for the TUI command pane
+#[cfg(feature = "tui")]
+const MAX_COMMAND_LINES: usize = 500;
+
+/// How often the TUI checks whether the boot variables changed underneath it
+#[cfg(feature = "tui")]
+const AUTO_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Message shown in the status line at the bottom of the TUI
+#[cfg(feature = "tui")]
+struct StatusLine {
+    text: String,
+    error: bool,
 }
 
-impl BootTargets {
-    fn get_names(&self) -> Vec<String> {
-        self.targets.iter().map(|target| {
-            let mut s = target.name.clone();
-
-            if self.next.is_some_and(|next| next == target.id) {
-                s.insert_str(0, "nxt: ");
-            } else if self.current.is_some_and(|curr| curr == target.id) {
-                s.insert_str(0, "cur: ");
-            } else {
-                s.insert_str(0, "     ");
-            }
+#[cfg(feature = "tui")]
+impl StatusLine {
+    fn info(text: String) -> Option<StatusLine> {
+        Some(StatusLine { text, error: false })
+    }
 
-            s
-        }).collect::<Vec<String>>()
+    fn error(text: String) -> Option<StatusLine> {
+        Some(StatusLine { text, error: true })
     }
+}
+
+/// Backend action that runs on a worker thread while the TUI shows a spinner
+#[cfg(feature = "tui")]
+#[derive(Debug, Clone)]
+enum BackgroundAction {
+    Reboot,
+    SetNext,
+    Delete,
+    /// Delete the duplicates of the target, see `duplicates`
+    RemoveDuplicates,
+    /// Save the BootOrder edited on the Order tab
+    SetOrder(Vec<u16>),
+    /// Save the timeout edited on the Settings tab
+    SetTimeout(u16),
+    /// Mark the target active or inactive
+    SetActive(bool),
+    /// Clear the pending BootNext, which is the target
+    ClearNext,
+    /// Put back BootNext and BootOrder from before the last change, see `undo`
+    Undo,
+}
 
-    fn lookup(&self, query: &str) -> Option<&BootTarget> {
-        let parsed = query.parse::<u16>();
+/// Action running on a worker thread
+#[cfg(feature = "tui")]
+struct PendingAction {
+    action: BackgroundAction,
+    target: BootTarget,
+    result: mpsc::Receiver<std::result::Result<(), CommandError>>,
+    cancelled: Arc<AtomicBool>,
+    started: Instant,
+}
 
-        if parsed.is_ok() { // Integer provided
-            let id = parsed.expect("Query should be a valid number here");
-            self.targets.iter().find(|target| target.id == id)
-        } else {
-            self.targets.iter().find(|target| target.name.starts_with(query))
-        }
+#[cfg(feature = "tui")]
+impl PendingAction {
+    /// Start the action, with an event sent through `events` once it finished
+    fn spawn(action: BackgroundAction, target: &BootTarget, events: &Events) -> PendingAction {
+        let (sender, result) = mpsc::channel();
+        let finished = events.sender();
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let worker_action = action.clone();
+        let worker_target = target.clone();
+        let worker_cancelled = cancelled.clone();
+        thread::spawn(move || {
+            let result = match &worker_action {
+                BackgroundAction::Reboot => match try_set_next_boot(&worker_target) {
+                    Ok(()) => {
+                        history::record(&worker_target);
+
+                        // Last chance to back out before the machine goes down
+                        if worker_cancelled.load(Ordering::SeqCst) {
+                            journal::record(journal::Action::Reboot, &worker_target, journal::Outcome::Cancelled);
+                            Err(CommandError { message: String::from(tr!("Reboot cancelled, BootNext has been set")), permission_denied: false })
+                        } else {
+                            let result = try_shutdown();
+                            journal::record_result(journal::Action::Reboot, &worker_target, &result);
+                            result
+                        }
+                    }
+                    Err(e) => {
+                        journal::record(journal::Action::Reboot, &worker_target, journal::Outcome::Failure(&e.message));
+                        Err(e)
+                    }
+                },
+                BackgroundAction::SetNext => {
+                    let result = try_set_next_boot(&worker_target);
+                    journal::record_result(journal::Action::SetNext, &worker_target, &result);
+                    result
+                }
+                BackgroundAction::Delete => try_delete_entry(&worker_target),
+                BackgroundAction::RemoveDuplicates => try_remove_duplicates(&worker_target),
+                BackgroundAction::SetOrder(order) => try_set_order(order),
+                BackgroundAction::SetTimeout(seconds) => efibootmgr::check(efibootmgr::set_timeout(*seconds))
+                    .map_err(|e| e.context(tr!("Could not set timeout"))),
+                BackgroundAction::ClearNext => try_clear_next_boot(),
+                BackgroundAction::Undo => try_undo().map(|_| ()),
+                BackgroundAction::SetActive(active) => efibootmgr::check(efibootmgr::set_active(worker_target.id, *active))
+                    .map_err(|e| e.context(if *active { tr!("Could not activate entry") } else { tr!("Could not deactivate entry") })),
+            };
+            let _ = sender.send(result);
+            let _ = finished.send(Ok(Event::ActionFinished));
+        });
+
+        PendingAction { action, target: target.clone(), result, cancelled, started: Instant::now() }
     }
 
-    fn print_list(&self) {
-        for target in self.targets.iter() {
-            println!("{} \t {}", target.id, target.name);
+    fn description(&self) -> String {
+        match &self.action {
+            BackgroundAction::Reboot => tr!("Rebooting to \"{}\"", self.target.name),
+            BackgroundAction::SetNext => tr!("Setting next boot to \"{}\"", self.target.name),
+            BackgroundAction::Delete => tr!("Deleting \"{}\"", self.target.name),
+            BackgroundAction::RemoveDuplicates => tr!("Removing duplicates of \"{}\"", self.target.name),
+            BackgroundAction::SetOrder(_) => String::from(tr!("Saving BootOrder")),
+            BackgroundAction::SetTimeout(seconds) => tr!("Setting timeout to {} seconds", seconds),
+            BackgroundAction::Undo => String::from(tr!("Undoing the last change")),
+            BackgroundAction::ClearNext => tr!("Clearing next boot to \"{}\"", self.target.name),
+            BackgroundAction::SetActive(true) => tr!("Activating \"{}\"", self.target.name),
+            BackgroundAction::SetActive(false) => tr!("Deactivating \"{}\"", self.target.name),
         }
     }
 }
 
-fn parse_boot_targets(raw: String) -> BootTargets {
-    let regex_options = Regex::new(r"(?m)^([a-zA-Z]+):\s+(.*)$")
-        .expect("Hardcoded parse_boot_targets regex should compile (1)");
-    let regex_targets = Regex::new(r"(?m)^[a-zA-Z]*([0-9]+)\*\s+(.*?)\t.*$")
-        .expect("Hardcoded parse_boot_targets regex should compile (2)");
+/// Yes/no confirmation before running an action, listing the reasons to hesitate
+#[cfg(feature = "tui")]
+struct ConfirmPrompt {
+    action: BackgroundAction,
+    target: BootTarget,
+    lines: Vec<String>,
+    question: &'static str,
+}
 
-    let mut result = BootTargets {
-        targets: vec![],
-        current: None,
-        next: None
-    };
+/// Password entry to retry a failed action through sudo
+#[cfg(feature = "tui")]
+struct PasswordPrompt {
+    action: BackgroundAction,
+    target: BootTarget,
+    input: String,
+    /// A previously entered password was rejected
+    rejected: bool,
+}
 
-    // Iterate over found options
-    for (_, [key, value]) in regex_options.captures_iter(raw.as_str()).map(|res| res.extract()) {
-        match key {
-            "BootCurrent" => result.current = Some(value.parse::<u16>().unwrap_or(1)),
-            "BootNext" => result.next = Some(value.parse::<u16>().unwrap_or(1)),
-            _ => (),
+/// PIN entry before rebooting or setting the next boot, when a PIN is configured
+#[cfg(feature = "tui")]
+struct PinPrompt {
+    action: BackgroundAction,
+    target: BootTarget,
+    input: String,
+    /// A previously entered PIN was wrong
+    rejected: bool,
+}
+
+/// Typed confirmation for deleting an entry in the TUI
+#[cfg(feature = "tui")]
+struct DeletePrompt {
+    input: String,
+    mismatch: bool,
+}
+
+/// BootOrder being rearranged on the Order tab
+#[cfg(feature = "tui")]
+struct OrderEditor {
+    order: Vec<u16>,
+    selected: usize,
+    /// Differs from the BootOrder in NVRAM
+    modified: bool,
+}
+
+#[cfg(feature = "tui")]
+impl OrderEditor {
+    fn new(order: &[u16]) -> OrderEditor {
+        OrderEditor { order: order.to_vec(), selected: 0, modified: false }
+    }
+
+    /// Follow the BootOrder in NVRAM, unless there are changes to save
+    fn sync(&mut self, order: &[u16]) {
+        if !self.modified {
+            self.order = order.to_vec();
+            self.selected = self.selected.min(self.order.len().saturating_sub(1));
         }
     }
 
-    // Iterate over found boot targets
-    for (_, [id, name]) in regex_targets.captures_iter(raw.as_str()).map(|res| res.extract()) {
-        let parsed_id = id.parse::<u16>();
-        
-        if parsed_id.is_err() {
-            // Skip invalid IDs
-            continue;
+    /// Move the selected entry one place up or down, keeping it selected
+    fn move_selected(&mut self, down: bool) {
+        let other = if down { self.selected + 1 } else { self.selected.wrapping_sub(1) };
+        if other < self.order.len() {
+            self.order.swap(self.selected, other);
+            self.selected = other;
+            self.modified = true;
         }
+    }
 
-        result.targets.push(BootTarget {
-            id: parsed_id.expect("Parsed id should be valid here"),
-            name: String::from_str(name).unwrap_or(String::from_str("Failure parsing name").expect("Hardcoded string should be valid")),
-        });
+    /// Select an entry and move it, returning false if it isn't in the order
+    fn move_entry(&mut self, id: u16, down: bool) -> bool {
+        match self.order.iter().position(|other| *other == id) {
+            Some(index) => {
+                self.selected = index;
+                self.move_selected(down);
+                true
+            }
+            None => false,
+        }
     }
+}
 
-    result
+/// Timeout being changed on the Settings tab
+#[cfg(feature = "tui")]
+struct SettingsEditor {
+    timeout: Option<u16>,
+    modified: bool,
 }
 
-fn get_boot_targets() -> BootTargets {
-    // Run command
-    let result = Command::new("efibootmgr").output().expect("Error running the efibootmgr command");
-    let raw = String::from_utf8(result.stdout).expect("Error parsing result of efibootmgr command");
+#[cfg(feature = "tui")]
+impl SettingsEditor {
+    /// Follow the timeout in NVRAM, unless there are changes to save
+    fn sync(&mut self, timeout: Option<u16>) {
+        if !self.modified {
+            self.timeout = timeout;
+        }
+    }
 
-    // Parse results
-    parse_boot_targets(raw)
+    fn adjust(&mut self, up: bool) {
+        let timeout = self.timeout.unwrap_or(0);
+        self.timeout = Some(if up { timeout.saturating_add(1) } else { timeout.saturating_sub(1) });
+        self.modified = true;
+    }
 }
 
-fn set_next_boot(target: &BootTarget) -> Result<ExitStatus> {
-    Command::new("efibootmgr")
-        .arg("--quiet")
-        .arg("--bootnext")
-        .arg(format!("{:0>4}", target.id))
-        .status()
+impl BootTarget {
+    /// Whether the typed text confirms this entry, by exact name or ID
+    fn confirmed_by(&self, input: &str) -> bool {
+        input == self.name || parse_id(input) == Some(self.id)
+    }
+
+    /// Name to list the entry by, the system it starts if its own name is that vague
+    fn label(&self) -> &str {
+        self.os_name.as_deref().unwrap_or(&self.name)
+    }
 }
 
-fn reboot_to(target: &BootTarget) {
-    
-    let mut status = set_next_boot(target);
-    if status.is_err() {
-        println!("Could not set boot target using efibootmgr, aborting...");
-        return;
-    } else {
-        let s = status.expect("Status should be valid here");
-        if !s.success() {
-            println!("efibootmgr exited with non-zero status: {}", s.code().unwrap_or(-1));
+impl BootTargets {
+    fn lookup(&self, query: &str) -> std::result::Result<&BootTarget, LookupError> {
+        // An ID wins, names that happen to be valid hexadecimal are matched below
+        if let Some(target) = parse_id(query).and_then(|id| self.by_id(id)) {
+            return Ok(target);
+        }
+
+        // An exact name wins over names merely starting with the query
+        if let Some(target) = self.targets.iter().find(|target| target.name == query) {
+            return Ok(target);
+        }
+
+        let matches: Vec<&BootTarget> = self.targets.iter().filter(|target| target.name.starts_with(query)).collect();
+        match matches.as_slice() {
+            [] => Err(LookupError::NotFound(query.to_string())),
+            [target] => Ok(target),
+            _ => Err(LookupError::Ambiguous(query.to_string(), matches.iter().map(|target| target.name.clone()).collect())),
+        }
+    }
+
+    /// Look up the entry of an ID expected to have `name`, or else the one
+    /// entry with exactly that name, as for --expect-name. Unlike `lookup`,
+    /// names never match by prefix.
+    fn lookup_named(&self, query: &str, name: &str) -> std::result::Result<&BootTarget, LookupError> {
+        if let Some(target) = parse_id(query).and_then(|id| self.by_id(id)).filter(|target| target.name == name) {
+            return Ok(target);
+        }
+
+        let matches: Vec<&BootTarget> = self.targets.iter().filter(|target| target.name == name).collect();
+        match matches.as_slice() {
+            [] => Err(LookupError::NotFound(name.to_string())),
+            [target] => Ok(target),
+            _ => Err(LookupError::Ambiguous(name.to_string(), matches.iter().map(|target| self.describe(target.id)).collect())),
         }
     }
-    
 
-    status = Command::new("shutdown")
-        .args(["-r", "now"])
-        .status()
-    ;
-    if status.is_err() || !status.expect("Status should be valid here").success() {
-        // TODO: Detail how to clear
-        println!("Unable to reboot using shutdown command. Bootnext has been set, either reboot manually or clear");
+    fn by_id(&self, id: u16) -> Option<&BootTarget> {
+        self.targets.iter().find(|target| target.id == id)
     }
-}
 
-fn set_next_boot_wrapper(target: &BootTarget) {
-    let status = set_next_boot(target);
-    if status.is_err() {
-        println!("Could not set boot target using efibootmgr, aborting...");
-        return;
-    } else {
-        let s = status.expect("Status should be valid here");
-        if !s.success() {
-            println!("efibootmgr exited with non-zero status: {}", s.code().unwrap_or(-1));
+    /// Describe an entry ID as "ID name" for human-readable output
+    fn describe(&self, id: u16) -> String {
+        match self.by_id(id) {
+            Some(target) => format!("{} {}", format_id(id), target.name),
+            None => format_id(id),
+        }
+    }
+
+    fn print_status(&self) {
+        println!("{}", tr!("Current boot: {}", self.current.map(|id| self.describe(id)).unwrap_or(String::from(tr!("unknown")))));
+        println!("{}", tr!("Next boot:    {}", self.next.map(|id| tr!("{} (one-time)", self.describe(id))).unwrap_or(String::from(tr!("default order")))));
+        println!("{}", tr!("BootOrder:    {}", self.order.iter().map(|id| self.describe(*id)).collect::<Vec<String>>().join(", ")));
+        println!("{}", tr!("Timeout:      {}", self.timeout.map(|t| tr!("{} seconds", t)).unwrap_or(String::from(tr!("unset")))));
+    }
+
+    /// Entries in the given order. Ties, like entries outside BootOrder, are ordered by ID.
+    fn sorted(&self, key: SortKey) -> Vec<&BootTarget> {
+        let position = |target: &BootTarget| self.order.iter().position(|id| *id == target.id).unwrap_or(usize::MAX);
+
+        let mut targets: Vec<&BootTarget> = self.targets.iter().collect();
+        targets.sort_by_key(|target| target.id);
+        match key {
+            SortKey::Order => targets.sort_by_key(|target| position(target)),
+            SortKey::Id => (),
+            SortKey::Name => targets.sort_by_key(|target| target.name.to_lowercase()),
+            SortKey::Active => targets.sort_by_key(|target| (!target.active, position(target))),
+        }
+        targets
+    }
+
+    fn print_verification(&self, only: Option<&BootTarget>) {
+        for target in self.targets.iter().filter(|target| only.is_none_or(|o| o.id == target.id)) {
+            let location = target.device_path.as_ref()
+                .and_then(verify::loader_location)
+                .map(|location| location.path)
+                .unwrap_or_default();
+            println!("{} \t {} \t {} \t {} \t {}", format_id(target.id), target.name, target.loader.describe(), location, target.device.as_deref().unwrap_or_default());
         }
     }
 }
 
+fn parse_boot_targets(raw: &str) -> BootTargets {
+    let parsed = parser::parse(raw);
+    for error in parsed.errors.iter() {
+        log::warn!("ignoring unexpected efibootmgr output, {}", error);
+    }
 
-fn tui_selection(targets: &BootTargets) -> Result<()>{
+    BootTargets {
+        targets: parsed.entries.into_iter().map(|entry| BootTarget {
+            id: entry.id,
+            name: entry.name,
+            active: entry.active,
+            device_path: entry.device_path,
+            loader: LoaderStatus::Unchecked,
+            device: None,
+            os_name: None,
+            removable: false,
+        }).collect(),
+        current: parsed.current,
+        next: parsed.next,
+        order: parsed.order,
+        timeout: parsed.timeout,
+    }
+}
 
-    let item_count = targets.targets.len();
-    let mut action = ChosenAction::None;
+/// Output of `efibootmgr -v`, and whether it succeeded
+fn run_efibootmgr() -> (String, bool) {
+    let mut command = Command::new("efibootmgr");
+    command.arg("-v");
+    log::debug!("running {}", sudo::command_line(&command));
+    let output = process::output(&mut command, None);
+    sudo::log_output(&command, &output);
+
+    let (stdout, listed) = match output {
+        Ok(output) => {
+            let listed = efibootmgr::check(Ok(output.clone()));
+            if let Err(e) = &listed {
+                log::warn!("could not list boot entries: {}", e);
+            }
+            (output.stdout, listed.is_ok())
+        }
+        Err(e) => {
+            log::warn!("could not run efibootmgr: {}", e);
+            (vec![], false)
+        }
+    };
 
-    // Setup clear screen
-    stdout().execute(EnterAlternateScreen)?;
-    enable_raw_mode()?;
-    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
-    terminal.clear()?;
+    // Labels can contain bytes in any encoding, don't let one entry break the rest
+    (String::from_utf8_lossy(&stdout).into_owned(), listed)
+}
 
-    // List state
-    let mut list_state = ListState::default().with_selected(Some(0));
+fn get_boot_targets() -> BootTargets {
+    #[cfg(windows)]
+    if !demo::is_enabled() {
+        return bcdedit::list();
+    }
+    #[cfg(target_os = "macos")]
+    if !demo::is_enabled() {
+        return bless::list();
+    }
 
-    loop {
-        // Draw UI
-        let list_items = targets.get_names();
-        terminal.draw(|frame| {
-            let area = frame.size();
-
-            let block = Block::bordered()
-                .gray()
-                .title(" List title ".bold().fg(Color::Gray).into_centered_line())
-                .title(Title::from(Line::from(vec![
-                    " ".into(),
-                    "Up/Down".on_gray().black().bold(),
-                    " Select ".into(),
-                    "Enter".on_gray().black().bold(),
-                    " Reboot ".into(),
-                    "n".on_gray().black().bold(),
-                    " Set next ".into(),
-                    "Esc/q".on_gray().black().bold(),
-                    " Quit ".into(),
-
-                ]))
-                .alignment(ratatui::layout::Alignment::Center)
-                .position(ratatui::widgets::block::Position::Bottom)
-            );
-
-            let list = List::new(list_items)
-            .block(block)
-            .style(Style::default().fg(Color::Gray))
-            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
-            .repeat_highlight_symbol(true)
-            .direction(ListDirection::TopToBottom)
-            ;
-
-            frame.render_stateful_widget(
-                list,
-                area,
-                &mut list_state
-            );
-        })?;
+    // Output of the last run, if the boot variables are unchanged since
+    let digest = cache::digest();
+    let raw = match digest.as_deref().and_then(cache::load) {
+        Some(raw) => {
+            log::debug!("using cached efibootmgr output");
+            raw
+        }
+        None => {
+            let (raw, listed) = run_efibootmgr();
+            // Failures are not cached, so that they are tried again next time
+            if let (Some(digest), true) = (&digest, listed) {
+                cache::store(digest, &raw);
+            }
+            raw
+        }
+    };
 
+    // Parse results, noting BootNext and BootOrder for undo
+    let targets = parse_boot_targets(&raw);
+    undo::observe(&targets);
+    targets
+}
 
-        // Handle events
-        if event::poll(std::time::Duration::from_millis(16))? {
-            if let event::Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
-                    // Quit loop and UI with q or Escape
-                    if key.code == KeyCode::Char('q') || key.code == KeyCode::Esc {
-                        break;
-                    }
+fn try_shutdown() -> std::result::Result<(), CommandError> {
+    // TODO: Detail how to clear
+    reboot::reboot().map_err(|e| CommandError {
+        message: tr!("Unable to reboot ({}). Bootnext has been set, either reboot manually or clear", e.message),
+        ..e
+    })
+}
 
-                    // Allow quit with CTRL+C
-                    if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
-                        break;
-                    }
+fn try_set_next_boot(target: &BootTarget) -> std::result::Result<(), CommandError> {
+    undo::checkpoint();
 
-                    // Navigate list with up/down
-                    if key.code == KeyCode::Down {
-                        if list_state.selected().unwrap_or(0) >= item_count -1 { // Wrap to top
-                            list_state.select_first();
-                        } else {
-                            list_state.select_next();
-                        }
-                    }
-                    if key.code == KeyCode::Up {
-                        if list_state.selected().unwrap_or(0) <= 0 { // Wrap to bottom
-                            list_state.select_last()
-                        } else {
-                            list_state.select_previous();
-                        }
-                    }
+    #[cfg(windows)]
+    if !demo::is_enabled() {
+        return efibootmgr::check_command("bcdedit", bcdedit::set_next_boot(target))
+            .map_err(|e| e.context(tr!("Could not set boot target")));
+    }
+    #[cfg(target_os = "macos")]
+    if !demo::is_enabled() {
+        return efibootmgr::check_command("bless", bless::set_next_boot(target))
+            .map_err(|e| e.context(tr!("Could not set boot target")));
+    }
 
-                    // Navigate fast with home/end
-                    if key.code == KeyCode::Home {
-                        list_state.select_first();
-                    }
-                    if key.code == KeyCode::End {
-                        list_state.select_last();
-                    }
+    efibootmgr::check(set_next_boot(target))
+        .map_err(|e| e.context(tr!("Could not set boot target")))
+}
 
-                    // Reboot to target with Enter
-                    if key.code == KeyCode::Enter {
-                        let selected =  list_state.selected();
-                        if selected.is_some_and(|index| index < item_count) {
-                            let index = selected.expect("Selected index is guaranteed to be Some here");
-                            let target = targets.targets.get(index);
-                            if target.is_some() {
-                                action = ChosenAction::RebootTo(target.expect("Target guaranteed to be valid here"));
-                            }
-                        }
-                        break;
-                    }
+fn try_clear_next_boot() -> std::result::Result<(), CommandError> {
+    undo::checkpoint();
 
-                    // Set target as next with n
-                    if key.code == KeyCode::Char('n') {
-                        let selected =  list_state.selected();
-                        if selected.is_some_and(|index| index < item_count) {
-                            let index = selected.expect("Selected index is guaranteed to be Some here");
-                            let target = targets.targets.get(index);
-                            if target.is_some() {
-                                action = ChosenAction::SetNext(target.expect("Target guaranteed to be valid here"));
-                            }
-                        }
-                        break;
-                    }
+    #[cfg(windows)]
+    if !demo::is_enabled() {
+        return efibootmgr::check_command("bcdedit", bcdedit::clear_next_boot())
+            .map_err(|e| e.context(tr!("Could not clear next boot")));
+    }
 
+    efibootmgr::check(efibootmgr::clear_next_boot())
+        .map_err(|e| e.context(tr!("Could not clear next boot")))
+}
 
-                }
-            }
+/// Resolve the entries given to `order set`, each a <DEST> or a comma-separated
+/// list of them, to a BootOrder
+fn parse_order(targets: &BootTargets, entries: &[String]) -> std::result::Result<Vec<u16>, Exit> {
+    let mut order = vec![];
+    for dest in entries.iter().flat_map(|e| e.split(',')).map(str::trim).filter(|d| !d.is_empty()) {
+        let target = targets.lookup(dest).map_err(|e| {
+            eprintln!("{}", e);
+            e.exit()
+        })?;
+        if order.contains(&target.id) {
+            eprintln!("{}", tr!("Entry {} \"{}\" appears more than once", format_id(target.id), target.name));
+            return Err(Exit::Failure);
         }
+        order.push(target.id);
     }
 
-    // Clean up screen
-    stdout().execute(LeaveAlternateScreen)?;
-    disable_raw_mode()?;
+    Ok(order)
+}
+
+fn try_set_order(order: &[u16]) -> std::result::Result<(), CommandError> {
+    undo::checkpoint();
+
+    efibootmgr::check(efibootmgr::set_order(order))
+        .map_err(|e| e.context(tr!("Could not set BootOrder")))
+}
 
-    // Handle action
-    match action {
-        ChosenAction::None => (),
-        ChosenAction::RebootTo(target) => reboot_to(target),
-        ChosenAction::SetNext(target) => set_next_boot_wrapper(target),
+/// Put back BootNext and BootOrder from before the last change, returning what was done
+fn try_undo() -> std::result::Result<Vec<String>, CommandError> {
+    let failure = |message: String| CommandError { message, permission_denied: false };
+    let snapshot = undo::last().ok_or(failure(String::from(tr!("Nothing to undo"))))?;
+    let targets = get_boot_targets();
+    if snapshot.order == targets.order && snapshot.next == targets.next {
+        return Err(failure(String::from(tr!("Nothing to undo, BootNext and BootOrder are as before the last change"))));
     }
 
-    Ok(())
+    // Saving the values replaced makes undoing again redo the change
+    undo::checkpoint();
+    let mut done = vec![];
+    if snapshot.order != targets.order {
+        efibootmgr::check(efibootmgr::set_order(&snapshot.order))
+            .map_err(|e| e.context(tr!("Could not set BootOrder")))?;
+        done.push(tr!("BootOrder set to {}", format_order(&snapshot.order)));
+    }
+    if snapshot.next != targets.next {
+        match snapshot.next.map(|id| targets.by_id(id).ok_or(id)) {
+            Some(Ok(target)) => {
+                efibootmgr::check(set_next_boot(target))
+                    .map_err(|e| e.context(tr!("Could not set boot target")))?;
+                done.push(tr!("Next boot set to \"{}\"", target.name));
+            }
+            Some(Err(id)) => return Err(failure(tr!("Could not restore BootNext, entry {} no longer exists", format_id(id)))),
+            None => {
+                efibootmgr::check(efibootmgr::clear_next_boot())
+                    .map_err(|e| e.context(tr!("Could not clear next boot")))?;
+                done.push(String::from(tr!("Next boot cleared")));
+            }
+        }
+    }
+    Ok(done)
 }
 
-fn main() -> ExitCode {
-    let args = Arguments::parse();
+fn try_delete_entry(target: &BootTarget) -> std::result::Result<(), CommandError> {
+    efibootmgr::check(efibootmgr::delete_entry(target.id))
+        .map_err(|e| e.context(tr!("Could not delete boot entry")))
+}
 
+/// Delete the duplicates of an entry, as found in the current boot entries
+fn try_remove_duplicates(target: &BootTarget) -> std::result::Result<(), CommandError> {
     let targets = get_boot_targets();
+    let groups = duplicates::find(&targets);
 
-    if args.list.unwrap_or(false) {
-        targets.print_list();
+    for id in duplicates::group_of(&groups, target.id).map(|group| group.extras.as_slice()).unwrap_or_default() {
+        efibootmgr::check(efibootmgr::delete_entry(*id))
+            .map_err(|e| e.context(&tr!("Could not delete duplicate {}", format_id(*id))))?;
+    }
+    Ok(())
+}
 
-        return ExitCode::SUCCESS;
+/// Set the target as next boot entry, and reboot
+fn reboot_to(target: &BootTarget) -> Exit {
+    if let Err(e) = try_set_next_boot(target) {
+        journal::record(journal::Action::Reboot, target, journal::Outcome::Failure(&e.message));
+        say!("{}", e);
+        return Exit::from_command(&e, Exit::EfibootmgrFailed);
     }
-    
-    if let Some(dest) = args.reboot_to.as_deref() {
-        let target = targets.lookup(dest);
+    reboot_now(target)
+}
 
-        if target.is_some() {
-            reboot_to(target.expect("Target checked to be valid"));
-        } else {
-            eprintln!("Could not find UEFI boot entry from specifier \"{}\"", dest);
-            
-            return ExitCode::FAILURE;
+/// Reboot with BootNext set to the target
+fn reboot_now(target: &BootTarget) -> Exit {
+    history::record(target);
+
+    let result = try_shutdown();
+    journal::record_result(journal::Action::Reboot, target, &result);
+    match result {
+        Ok(()) => Exit::Success,
+        Err(e) => {
+            say!("{}", e);
+            Exit::from_command(&e, Exit::RebootFailed)
         }
+    }
+}
 
-        return ExitCode::SUCCESS;
+/// Reboot to the target after counting down `delay`, see `delay`
+fn reboot_to_after(targets: &BootTargets, target: &BootTarget, delay: Duration) -> Exit {
+    if !delay::catch_interrupt() {
+        delay::count_down(delay, target.label());
+        return reboot_to(target);
     }
 
-    if let Some(dest) = args.next.as_deref() {
-        let target = targets.lookup(dest);
+    if let Err(e) = try_set_next_boot(target) {
+        journal::record(journal::Action::Reboot, target, journal::Outcome::Failure(&e.message));
+        say!("{}", e);
+        return Exit::from_command(&e, Exit::EfibootmgrFailed);
+    }
+    if delay::count_down(delay, target.label()) {
+        return reboot_now(target);
+    }
+    journal::record(journal::Action::Reboot, target, journal::Outcome::Cancelled);
 
-        if target.is_some() {
-            set_next_boot_wrapper(target.expect("Target checked to be valid"));
-        } else {
-            eprintln!("Could not find UEFI boot entry from specifier \"{}\"", dest);
-            
-            return ExitCode::FAILURE;
+    let restored = match targets.next.and_then(|id| targets.by_id(id)) {
+        Some(previous) if previous.id == target.id => Ok(String::from(tr!("Reboot cancelled"))),
+        Some(previous) => try_set_next_boot(previous).map(|()| tr!("Reboot cancelled, next boot set back to \"{}\"", previous.label())),
+        None => try_clear_next_boot().map(|()| String::from(tr!("Reboot cancelled, next boot cleared"))),
+    };
+    match restored {
+        Ok(message) => {
+            say!("{}", message);
+            Exit::Cancelled
+        }
+        Err(e) => {
+            say!("{}", e);
+            Exit::from_command(&e, Exit::EfibootmgrFailed)
         }
+    }
+}
 
-        return ExitCode::SUCCESS;
+/// Ask a question on the terminal, returning None when input has ended
+fn prompt(question: &str) -> Option<String> {
+    if logger::quiet() {
+        eprint!("{} ", question);
+    } else {
+        print!("{} ", question);
+        let _ = stdout().flush();
     }
 
-    
-    tui_selection(&targets).expect("Error in TUI");
-    
-    ExitCode::SUCCESS
+    let mut answer = String::new();
+    match stdin().read_line(&mut answer) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(answer.trim().to_string()),
+    }
+}
+
+/// Ask a yes/no question on the terminal, defaulting to no
+fn confirm(question: &str) -> bool {
+    prompt(&tr!("{} [y/N]", question))
+        .is_some_and(|answer| matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Line printed by --get-next and --get-current for the given BootNext or
+/// BootCurrent: the entry name, or its ID with `ids_only`. None when unset.
+fn entry_output(targets: &BootTargets, id: Option<u16>, ids_only: bool) -> Option<String> {
+    let id = id?;
+    // An ID missing from the entries, e.g. of a deleted entry, has no name to print
+    match targets.by_id(id).filter(|_| !ids_only) {
+        Some(target) => Some(target.name.clone()),
+        None => Some(format_id(id)),
+    }
+}
+
+/// ID of the entry a <DEST> refers to, as printed by --resolve
+fn resolve(targets: &BootTargets, dest: &str) -> std::result::Result<String, LookupError> {
+    targets.lookup(dest).map(|target| format_id(target.id))
+}
+
+/// Look up the entry to delete, and unless `assume_yes` have the user type its
+/// name or ID to confirm, as answered by `ask`
+fn confirm_delete<'a>(targets: &'a BootTargets, dest: &str, assume_yes: bool, ask: impl FnOnce(&str) -> Option<String>) -> std::result::Result<&'a BootTarget, Exit> {
+    let target = targets.lookup(dest).map_err(|e| {
+        eprintln!("{}", e);
+        e.exit()
+    })?;
+
+    let confirmed = assume_yes || {
+        say!("{}", tr!("About to delete boot entry {} \"{}\". This cannot be undone.", format_id(target.id), target.name));
+        ask(tr!("Type the name or ID of the entry to confirm:"))
+            .is_some_and(|input| target.confirmed_by(&input))
+    };
+    if !confirmed {
+        eprintln!("{}", tr!("Confirmation did not match, not deleting"));
+
+        return Err(Exit::Cancelled);
+    }
+
+    Ok(target)
+}
+
+/// Check the label of the copy, then look up the entry to duplicate
+fn duplicate_source<'a>(targets: &'a BootTargets, dest: &str, label: &str) -> std::result::Result<&'a BootTarget, Exit> {
+    if let Err(e) = efibootmgr::check_label(label) {
+        eprintln!("{}", tr!("Could not duplicate boot entry: {}", e));
+
+        return Err(Exit::Failure);
+    }
+
+    targets.lookup(dest).map_err(|e| {
+        eprintln!("{}", e);
+        e.exit()
+    })
+}
+
+/// Look up the entry to rename, checking the new label before anything is
+/// deleted to recreate it
+fn rename_target<'a>(targets: &'a BootTargets, dest: &str, label: &str) -> std::result::Result<&'a BootTarget, Exit> {
+    let target = targets.lookup(dest).map_err(|e| {
+        eprintln!("{}", e);
+        e.exit()
+    })?;
+
+    if let Err(e) = efibootmgr::check_label(label) {
+        eprintln!("{}", tr!("Could not rename boot entry: {}", e));
+
+        return Err(Exit::Failure);
+    }
+
+    Ok(target)
+}
+
+fn delete_entry_wrapper(target: &BootTarget) -> Exit {
+    match try_delete_entry(target) {
+        Ok(()) => {
+            say!("{}", tr!("Deleted boot entry \"{}\"", target.name));
+            Exit::Success
+        }
+        Err(e) => {
+            say!("{}", e);
+            Exit::from_command(&e, Exit::EfibootmgrFailed)
+        }
+    }
+}
+
+fn set_next_boot_wrapper(target: &BootTarget) -> Exit {
+    let result = try_set_next_boot(target);
+    journal::record_result(journal::Action::SetNext, target, &result);
+    match result {
+        Ok(()) => Exit::Success,
+        Err(e) => {
+            say!("{}", e);
+            Exit::from_command(&e, Exit::EfibootmgrFailed)
+        }
+    }
+}
+
+
+/// Find duplicate entries, and hide the extras if `collapse` is set
+fn find_duplicates(targets: &mut BootTargets, collapse: bool) -> Vec<duplicates::Group> {
+    let groups = duplicates::find(targets);
+    if collapse {
+        duplicates::collapse(targets, &groups);
+    }
+    groups
+}
+
+/// Reload entries, keeping the selected entry selected
+#[cfg(feature = "tui")]
+fn refresh_targets(targets: &mut BootTargets, groups: &mut Vec<duplicates::Group>, config: &Config, hide_network: bool, list_state: &mut ListState) {
+    let selected_id = list_state.selected()
+        .and_then(|index| targets.targets.get(index))
+        .map(|target| target.id);
+
+    *targets = get_boot_targets();
+    verify::verify_targets(targets, !config.keep_names);
+    *groups = find_duplicates(targets, config.collapse_duplicates);
+    sections::sort(targets);
+    if hide_network {
+        sections::hide_network(targets);
+    }
+
+    let index = selected_id
+        .and_then(|id| targets.targets.iter().position(|target| target.id == id))
+        .unwrap_or(0);
+    list_state.select(Some(index));
+}
+
+/// BootOrder for messages, like "0001,0000,0002"
+fn format_order(order: &[u16]) -> String {
+    order.iter().map(|id| format_id(*id)).collect::<Vec<String>>().join(",")
+}
+
+/// Start rebooting to the target from the TUI after the safety checks,
+/// asking first if there are warnings or the entry is protected
+#[cfg(feature = "tui")]
+fn tui_reboot(target: &BootTarget, assume_yes: bool, protected: bool, pending: &mut Option<PendingAction>, events: &Events, confirm_prompt: &mut Option<ConfirmPrompt>, status_line: &mut Option<StatusLine>) {
+    let blockers = if assume_yes { vec![] } else { safety::reboot_blockers() };
+    let mut warnings: Vec<String> = if assume_yes { vec![] } else { safety::loader_warning(target).into_iter().chain(safety::reboot_warnings()).collect() };
+    if protected {
+        warnings.insert(0, protected_warning(target));
+    }
+    if let Some(blocker) = blockers.first() {
+        *status_line = StatusLine::error(tr!("Refusing to reboot: {}", blocker));
+    } else if warnings.is_empty() {
+        *pending = Some(PendingAction::spawn(BackgroundAction::Reboot, target, events));
+    } else {
+        *confirm_prompt = Some(ConfirmPrompt { action: BackgroundAction::Reboot, target: target.clone(), lines: warnings, question: tr!("Continue anyway?") });
+    }
+}
+
+/// Set the next boot from the TUI, asking first if the loader looks broken or
+/// the entry is protected
+#[cfg(feature = "tui")]
+fn tui_set_next(target: &BootTarget, assume_yes: bool, protected: bool, pending: &mut Option<PendingAction>, events: &Events, confirm_prompt: &mut Option<ConfirmPrompt>) {
+    let lines: Vec<String> = protected.then(|| protected_warning(target)).into_iter()
+        .chain(safety::loader_warning(target).filter(|_| !assume_yes))
+        .collect();
+    if lines.is_empty() {
+        *pending = Some(PendingAction::spawn(BackgroundAction::SetNext, target, events));
+    } else {
+        *confirm_prompt = Some(ConfirmPrompt { action: BackgroundAction::SetNext, target: target.clone(), lines, question: tr!("Continue anyway?") });
+    }
+}
+
+/// Ask before using an entry on a disk that is not present on the command line
+fn confirm_disk(target: &BootTarget, assume_yes: bool) -> bool {
+    if assume_yes || !verify::disk_missing(target) {
+        return true;
+    }
+    say!("{}", safety::missing_disk_warning(target));
+    confirm(tr!("Continue anyway?"))
+}
+
+/// Reason to confirm using a protected entry
+fn protected_warning(target: &BootTarget) -> String {
+    tr!("\"{}\" is a protected entry", target.name)
+}
+
+/// Ask before using a protected entry on the command line, even with --yes
+fn confirm_protected(target: &BootTarget, config: &Config) -> bool {
+    if !config.is_protected(target) {
+        return true;
+    }
+    say!("{}", protected_warning(target));
+    confirm(tr!("Continue anyway?"))
+}
+
+/// Move the selection a page of `page` entries up or down, scrolling the list
+/// along so the selection keeps its place in the window. Near either end the
+/// window stops at the end, and the selection continues to the first or last entry.
+#[cfg(feature = "tui")]
+fn move_page(list_state: &mut ListState, item_count: usize, page: usize, down: bool) {
+    if item_count == 0 {
+        return;
+    }
+
+    let selected = list_state.selected().unwrap_or(0).min(item_count - 1);
+    let offset = list_state.offset();
+    if down {
+        list_state.select(Some((selected + page).min(item_count - 1)));
+        *list_state.offset_mut() = (offset + page).min(item_count.saturating_sub(page));
+    } else {
+        list_state.select(Some(selected.saturating_sub(page)));
+        *list_state.offset_mut() = offset.saturating_sub(page);
+    }
+}
+
+/// Name of the machine, for the TUI title
+fn hostname() -> String {
+    let name = ["/proc/sys/kernel/hostname", "/etc/hostname"].iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .or_else(|| Command::new("hostname").output().ok().map(|output| String::from_utf8_lossy(&output.stdout).into_owned()))
+        .unwrap_or_default();
+
+    match name.trim() {
+        "" => String::from("localhost"),
+        name => name.to_string(),
+    }
+}
+
+#[cfg(feature = "tui")]
+fn tui_selection(mut targets: BootTargets, assume_yes: bool, config: &Config, inline: bool) -> Result<()>{
+    let firmware = firmware::read().describe();
+    let esp = esp::find(&targets).map(|esp| esp.describe());
+    let secure_boot = efivar::secure_boot();
+    let firmware_updates = fwupd::pending_updates();
+    let icons = config.icons.for_terminal();
+    let theme = config.theme.theme();
+    let title = format!(" {} ", config.title.as_deref().unwrap_or(DEFAULT_TITLE).replace("{hostname}", &hostname()));
+    let keys = &config.keys;
+    let mut groups = find_duplicates(&mut targets, config.collapse_duplicates);
+    sections::sort(&mut targets);
+    let mut hide_network = config.hide_network;
+    if hide_network {
+        sections::hide_network(&mut targets);
+    }
+
+    let mut status_line: Option<StatusLine> = None;
+    let mut exit_message: Option<String> = None;
+    let mut pending: Option<PendingAction> = None;
+    let mut password_prompt: Option<PasswordPrompt> = None;
+    let mut pin_prompt: Option<PinPrompt> = None;
+    let mut confirm_prompt: Option<ConfirmPrompt> = None;
+
+    // Setup clear screen, or with --inline room below the prompt, log messages would be drawn over it
+    logger::hold();
+    enable_raw_mode()?;
+    let mut terminal = if inline {
+        // Room for the header, the tabs, the entries, their border and a status line, the list scrolls beyond that
+        let height = (ui::entry_rows(&targets).len() as u16 + 5).clamp(5, INLINE_MAX_HEIGHT);
+        Terminal::with_options(CrosstermBackend::new(stdout()), TerminalOptions { viewport: Viewport::Inline(height) })?
+    } else {
+        stdout().execute(EnterAlternateScreen)?;
+        Terminal::new(CrosstermBackend::new(stdout()))?
+    };
+    terminal.clear()?;
+
+    // List state, starting at the entry selected last time
+    let remembered = history::selection(&targets).and_then(|selected| targets.targets.iter().position(|target| target.id == selected.id));
+    let mut list_state = ListState::default().with_selected(Some(remembered.unwrap_or(0)));
+    let mut delete_prompt: Option<DeletePrompt> = None;
+
+    // Kiosk mode reboots to the default entry unless a key is pressed before the deadline
+    let mut countdown: Option<(u16, Instant)> = None;
+    if let Some(kiosk) = &config.kiosk {
+        match targets.lookup(&kiosk.default) {
+            Ok(target) => {
+                list_state.select(targets.targets.iter().position(|t| t.id == target.id));
+                countdown = Some((target.id, Instant::now() + Duration::from_secs(kiosk.countdown)));
+            }
+            Err(e) => status_line = StatusLine::error(tr!("Kiosk mode disabled: {}", e)),
+        }
+    }
+
+    // Quit by itself after a while without input, if configured
+    let mut last_input = Instant::now();
+
+    // Entries rebooted to before, numbered to select them quickly
+    let visits = history::load();
+
+    // The other tabs, and what happened so far for the Log tab
+    let mut tab = Tab::Entries;
+    let mut order_editor = OrderEditor::new(&targets.order);
+    let mut settings_editor = SettingsEditor { timeout: targets.timeout, modified: false };
+    let mut log: Vec<String> = vec![];
+
+    // Commands run by the actions, streamed into a pane that can be shown below the tabs
+    sudo::collect_transcript(true);
+    let mut commands: Vec<String> = vec![];
+    let mut show_commands = false;
+
+    // Key presses, boot variables changed by someone else and finished actions
+    let events = Events::start(AUTO_REFRESH_INTERVAL);
+
+    loop {
+        // Pick up the result of a finished background action
+        let finished = pending.as_ref().and_then(|p| p.result.try_recv().ok());
+        if let Some(result) = finished {
+            let done = pending.take().expect("Pending action is guaranteed to be Some here");
+            let description = done.description();
+            match (done.action, result) {
+                (action, Err(e)) if e.permission_denied => {
+                    // Offer to retry through sudo, forgetting a password that did not work
+                    let rejected = sudo::has_password();
+                    sudo::set_password(None);
+                    status_line = StatusLine::error(e.message);
+                    password_prompt = Some(PasswordPrompt { action, target: done.target, input: String::new(), rejected });
+                }
+                (_, Err(e)) => status_line = StatusLine::error(e.message),
+                (BackgroundAction::Reboot, Ok(())) => {
+                    exit_message = Some(tr!("Rebooting to \"{}\"...", done.target.name));
+                    break;
+                }
+                (BackgroundAction::SetNext, Ok(())) => {
+                    exit_message = Some(tr!("Next boot set to \"{}\"", done.target.name));
+                    break;
+                }
+                (BackgroundAction::Delete, Ok(())) => {
+                    status_line = StatusLine::info(tr!("Deleted boot entry \"{}\"", done.target.name));
+                    refresh_targets(&mut targets, &mut groups, config, hide_network, &mut list_state);
+                }
+                (BackgroundAction::RemoveDuplicates, Ok(())) => {
+                    status_line = StatusLine::info(tr!("Removed the duplicates of \"{}\"", done.target.name));
+                    refresh_targets(&mut targets, &mut groups, config, hide_network, &mut list_state);
+                }
+                (BackgroundAction::SetOrder(order), Ok(())) => {
+                    status_line = StatusLine::info(tr!("BootOrder set to {}", format_order(&order)));
+                    order_editor.modified = false;
+                    refresh_targets(&mut targets, &mut groups, config, hide_network, &mut list_state);
+                }
+                (BackgroundAction::SetTimeout(seconds), Ok(())) => {
+                    status_line = StatusLine::info(tr!("Set UEFI boot menu timeout to {} seconds", seconds));
+                    settings_editor.modified = false;
+                    refresh_targets(&mut targets, &mut groups, config, hide_network, &mut list_state);
+                }
+                (BackgroundAction::ClearNext, Ok(())) => {
+                    status_line = StatusLine::info(tr!("Next boot no longer set to \"{}\"", done.target.name));
+                    // Like for SetActive, the boot variables are read again once they change
+                    targets.next = None;
+                    undo::observe(&targets);
+                }
+                (BackgroundAction::Undo, Ok(())) => {
+                    status_line = StatusLine::info(String::from(tr!("Restored BootNext and BootOrder from before the last change")));
+                    refresh_targets(&mut targets, &mut groups, config, hide_network, &mut list_state);
+                }
+                (BackgroundAction::SetActive(active), Ok(())) => {
+                    status_line = StatusLine::info(if active {
+                        tr!("Activated \"{}\"", done.target.name)
+                    } else {
+                        tr!("Deactivated \"{}\"", done.target.name)
+                    });
+                    // Show the new state right away, the boot variables are read again once they change
+                    if let Some(target) = targets.targets.iter_mut().find(|target| target.id == done.target.id) {
+                        target.active = active;
+                    }
+                }
+            }
+            log.push(status_line.as_ref().map(|status| status.text.clone()).unwrap_or(tr!("{}: done", description)));
+        }
+        order_editor.sync(&targets.order);
+        settings_editor.sync(targets.timeout);
+
+        // Reboot once the time has run out
+        if let Some((id, deadline)) = countdown {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match targets.by_id(id) {
+                Some(target) if left.is_zero() => {
+                    countdown = None;
+                    tui_reboot(target, assume_yes, config.is_protected(target), &mut pending, &events, &mut confirm_prompt, &mut status_line);
+                }
+                Some(_) => (),
+                None => countdown = None,
+            }
+        }
+
+        // Leave the terminal alone again, but not halfway through an action
+        let idle = config.idle_timeout.filter(|seconds| last_input.elapsed() >= Duration::from_secs(*seconds));
+        if let (Some(seconds), None, None) = (idle, &pending, countdown) {
+            exit_message = Some(tr!("No key pressed for {} seconds, quitting without doing anything", seconds));
+            break;
+        }
+
+        commands.extend(sudo::transcript());
+        commands.drain(..commands.len().saturating_sub(MAX_COMMAND_LINES));
+
+        let item_count = targets.targets.len();
+        let recent: Vec<u16> = history::recent(&visits, &targets, RECENT_ENTRIES).iter().map(|target| target.id).collect();
+
+        // Draw UI
+        let view = ui::View {
+            targets: &targets,
+            duplicates: &groups,
+            keys,
+            title: &title,
+            firmware: firmware.as_deref(),
+            esp: esp.as_deref(),
+            secure_boot,
+            firmware_updates: &firmware_updates,
+            icons,
+            theme,
+            recent: &recent,
+            floating: config.floating,
+            tab,
+            order: &order_editor,
+            settings: &settings_editor,
+            log: &log,
+            commands: &commands,
+            show_commands,
+            network_hidden: hide_network,
+            status_line: status_line.as_ref(),
+            countdown: countdown.and_then(|(id, deadline)| targets.by_id(id).map(|target| ui::Countdown {
+                name: target.name.clone(),
+                left: deadline.saturating_duration_since(Instant::now()),
+                total: Duration::from_secs(config.kiosk.as_ref().map_or(0, |kiosk| kiosk.countdown)),
+            })),
+            delete_prompt: delete_prompt.as_ref(),
+            confirm_prompt: confirm_prompt.as_ref(),
+            password_prompt: password_prompt.as_ref(),
+            pin_prompt: pin_prompt.as_ref(),
+            spinner: pending.as_ref().map(|pending| ui::spinner_text(&pending.description(), pending.started.elapsed())),
+        };
+        terminal.draw(|frame| ui::draw(frame, &view, &mut list_state))?;
+        let page = ui::page_size(&view, terminal.get_frame().size());
+
+        // Sleep until something happens, or until the next frame of the spinner or the countdown
+        let wait = if pending.is_some() || countdown.is_some() {
+            Some(ui::FRAME_INTERVAL)
+        } else {
+            config.idle_timeout.map(|seconds| Duration::from_secs(seconds).saturating_sub(last_input.elapsed()))
+        };
+        let key = match events.next(wait)? {
+            Some(Event::Terminal(event::Event::Key(key))) if key.kind == KeyEventKind::Press => key,
+            // Plugged in or pulled disks change which entries can boot
+            Some(Event::BootVariablesChanged | Event::BlockDevicesChanged) => {
+                refresh_targets(&mut targets, &mut groups, config, hide_network, &mut list_state);
+                continue;
+            }
+            // Finished actions are picked up above, resizes and the rest only need a redraw
+            _ => continue,
+        };
+        last_input = Instant::now();
+
+        // Any key takes over from the kiosk countdown, and does what it normally does
+        countdown = None;
+
+        // While a backend command runs, only allow cancelling
+        if let Some(running) = &pending {
+            let ctrl_c = key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+            if key.code == KeyCode::Esc || ctrl_c {
+                running.cancelled.store(true, Ordering::SeqCst);
+                status_line = StatusLine::info(tr!("Cancelled. {} may still complete in the background", running.description()));
+                log.push(tr!("{}: {}", running.description(), tr!("cancelled")));
+                pending = None;
+            }
+            continue;
+        }
+
+        // Confirmation popup captures all keys
+        if let Some(prompt) = confirm_prompt.take() {
+            if key.code == KeyCode::Char('y') {
+                pending = Some(PendingAction::spawn(prompt.action, &prompt.target, &events));
+            } else if !matches!(key.code, KeyCode::Char('n') | KeyCode::Esc | KeyCode::Char('c') | KeyCode::Char('q')) {
+                // Ignore other keys, keep asking
+                confirm_prompt = Some(prompt);
+            }
+            continue;
+        }
+
+        // Password popup captures all keys
+        if let Some(prompt) = password_prompt.as_mut() {
+            let ctrl_c = key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+            match key.code {
+                KeyCode::Esc => password_prompt = None,
+                _ if ctrl_c => password_prompt = None,
+                KeyCode::Backspace => {
+                    prompt.input.pop();
+                }
+                KeyCode::Char(c) => prompt.input.push(c),
+                KeyCode::Enter => {
+                    sudo::set_password(Some(prompt.input.clone()));
+                    pending = Some(PendingAction::spawn(prompt.action.clone(), &prompt.target, &events));
+                    password_prompt = None;
+                }
+                _ => (),
+            }
+            continue;
+        }
+
+        // PIN popup captures all keys, and goes on to the usual checks once the PIN matches
+        if let Some(prompt) = pin_prompt.as_mut() {
+            let ctrl_c = key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+            match key.code {
+                KeyCode::Esc => pin_prompt = None,
+                _ if ctrl_c => pin_prompt = None,
+                KeyCode::Backspace => {
+                    prompt.input.pop();
+                }
+                KeyCode::Char(c) => prompt.input.push(c),
+                KeyCode::Enter if config.pin_sha256.as_deref() == Some(sha256::hex_digest(prompt.input.as_bytes()).as_str()) => {
+                    let protected = config.is_protected(&prompt.target);
+                    match prompt.action {
+                        BackgroundAction::Reboot => tui_reboot(&prompt.target, assume_yes, protected, &mut pending, &events, &mut confirm_prompt, &mut status_line),
+                        _ => tui_set_next(&prompt.target, assume_yes, protected, &mut pending, &events, &mut confirm_prompt),
+                    }
+                    pin_prompt = None;
+                }
+                KeyCode::Enter => {
+                    prompt.input.clear();
+                    prompt.rejected = true;
+                }
+                _ => (),
+            }
+            continue;
+        }
+
+        // Any key dismisses the previous status message
+        if delete_prompt.is_none() {
+            status_line = None;
+        }
+
+        // Typed confirmation popup captures all keys
+        if let Some(prompt) = delete_prompt.as_mut() {
+            let ctrl_c = key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+            match key.code {
+                KeyCode::Esc => delete_prompt = None,
+                _ if ctrl_c => delete_prompt = None,
+                KeyCode::Backspace => {
+                    prompt.input.pop();
+                }
+                KeyCode::Char(c) => {
+                    prompt.input.push(c);
+                    prompt.mismatch = false;
+                }
+                KeyCode::Enter => {
+                    let target = list_state.selected().and_then(|index| targets.targets.get(index));
+                    match target {
+                        Some(target) if target.confirmed_by(&prompt.input) => {
+                            pending = Some(PendingAction::spawn(BackgroundAction::Delete, target, &events));
+                            delete_prompt = None;
+                        }
+                        _ => {
+                            prompt.input.clear();
+                            prompt.mismatch = true;
+                        }
+                    }
+                }
+                _ => (),
+            }
+            continue;
+        }
+
+        let action = keys.action(&key);
+
+        // Quit loop and UI
+        if action == Some(Action::Quit) {
+            break;
+        }
+
+        // Allow quit with CTRL+C
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            break;
+        }
+
+        if action == Some(Action::ToggleCommands) {
+            show_commands = !show_commands;
+            continue;
+        }
+
+        if action == Some(Action::ToggleNetwork) {
+            hide_network = !hide_network;
+            refresh_targets(&mut targets, &mut groups, config, hide_network, &mut list_state);
+            status_line = StatusLine::info(String::from(if hide_network { tr!("Network boot entries hidden") } else { tr!("Network boot entries shown") }));
+            continue;
+        }
+
+        // Switch tabs, directly with the number of a tab
+        let switch = match (action, key.code) {
+            (Some(Action::NextTab), _) => Some(tab.next()),
+            (Some(Action::PreviousTab), _) => Some(tab.previous()),
+            (None, KeyCode::Char(digit @ '1'..='4')) if !key.modifiers.contains(KeyModifiers::ALT) => Some(Tab::ALL[digit as usize - '1' as usize]),
+            _ => None,
+        };
+        if let Some(switch) = switch {
+            tab = switch;
+            continue;
+        }
+
+        // Reload entries, throwing away unsaved changes on the other tabs
+        if action == Some(Action::Refresh) {
+            refresh_targets(&mut targets, &mut groups, config, hide_network, &mut list_state);
+            order_editor = OrderEditor::new(&targets.order);
+            settings_editor = SettingsEditor { timeout: targets.timeout, modified: false };
+            continue;
+        }
+
+        match tab {
+            Tab::Entries => (),
+            Tab::Order => {
+                match (action, key.code) {
+                    (Some(Action::Up), _) => order_editor.selected = order_editor.selected.saturating_sub(1),
+                    (Some(Action::Down), _) => order_editor.selected = (order_editor.selected + 1).min(order_editor.order.len().saturating_sub(1)),
+                    (None, KeyCode::Char('+')) => order_editor.move_selected(false),
+                    (None, KeyCode::Char('-')) => order_editor.move_selected(true),
+                    (None, KeyCode::Char('s')) if order_editor.modified => {
+                        // The entry booted first stands for the whole order in messages
+                        let first = order_editor.order.first().and_then(|id| targets.by_id(*id)).or(targets.targets.first());
+                        if let Some(first) = first {
+                            pending = Some(PendingAction::spawn(BackgroundAction::SetOrder(order_editor.order.clone()), first, &events));
+                        }
+                    }
+                    _ => (),
+                }
+                continue;
+            }
+            Tab::Settings => {
+                match key.code {
+                    KeyCode::Char('+') => settings_editor.adjust(true),
+                    KeyCode::Char('-') => settings_editor.adjust(false),
+                    KeyCode::Char('s') if settings_editor.modified => {
+                        let first = targets.order.first().and_then(|id| targets.by_id(*id)).or(targets.targets.first());
+                        if let (Some(seconds), Some(first)) = (settings_editor.timeout, first) {
+                            pending = Some(PendingAction::spawn(BackgroundAction::SetTimeout(seconds), first, &events));
+                        }
+                    }
+                    _ => (),
+                }
+                continue;
+            }
+            Tab::Log => continue,
+        }
+
+        // Move the selected entry within BootOrder, saved after confirming the new order
+        if matches!(action, Some(Action::Up | Action::Down)) && key.modifiers.contains(KeyModifiers::SHIFT) {
+            if let Some(target) = list_state.selected().and_then(|index| targets.targets.get(index)) {
+                status_line = if order_editor.move_entry(target.id, action == Some(Action::Down)) {
+                    StatusLine::info(tr!("BootOrder: {} (not saved, s saves, {} discards)", format_order(&order_editor.order), keys.short_label(Action::Refresh)))
+                } else {
+                    StatusLine::error(tr!("\"{}\" is not in BootOrder", target.name))
+                };
+            }
+            continue;
+        }
+        if let (None, KeyCode::Char('s'), true) = (action, key.code, order_editor.modified) {
+            let first = order_editor.order.first().and_then(|id| targets.by_id(*id)).or(targets.targets.first());
+            if let Some(first) = first {
+                let lines = vec![tr!("BootOrder: {} → {}", format_order(&targets.order), format_order(&order_editor.order))];
+                confirm_prompt = Some(ConfirmPrompt { action: BackgroundAction::SetOrder(order_editor.order.clone()), target: first.clone(), lines, question: tr!("Save the new BootOrder?") });
+            }
+            continue;
+        }
+
+        // Navigate list
+        if action == Some(Action::Down) {
+            if list_state.selected().unwrap_or(0) >= item_count.saturating_sub(1) { // Wrap to top
+                list_state.select_first();
+            } else {
+                list_state.select_next();
+            }
+        }
+        if action == Some(Action::Up) {
+            if list_state.selected().unwrap_or(0) == 0 { // Wrap to bottom
+                list_state.select_last()
+            } else {
+                list_state.select_previous();
+            }
+        }
+
+        // Navigate fast to either end
+        if action == Some(Action::First) {
+            list_state.select_first();
+        }
+        if action == Some(Action::Last) {
+            list_state.select_last();
+        }
+
+        // Navigate a page at a time, a page being the entries that fit in the list
+        if matches!(action, Some(Action::PageUp | Action::PageDown)) {
+            move_page(&mut list_state, item_count, page, action == Some(Action::PageDown));
+        }
+
+        // Jump to a recent entry by Alt and its number
+        if let (None, KeyCode::Char(digit @ '1'..='9'), true) = (action, key.code, key.modifiers.contains(KeyModifiers::ALT)) {
+            let id = recent.get(digit as usize - '1' as usize);
+            if let Some(index) = id.and_then(|id| targets.targets.iter().position(|target| target.id == *id)) {
+                list_state.select(Some(index));
+            }
+        }
+
+        // Reboot to target
+        if action == Some(Action::Reboot) {
+            let selected =  list_state.selected();
+            if selected.is_some_and(|index| index < item_count) {
+                let index = selected.expect("Selected index is guaranteed to be Some here");
+                if let Some(target) = targets.targets.get(index) {
+                    match &config.pin_sha256 {
+                        Some(_) => pin_prompt = Some(PinPrompt { action: BackgroundAction::Reboot, target: target.clone(), input: String::new(), rejected: false }),
+                        None => tui_reboot(target, assume_yes, config.is_protected(target), &mut pending, &events, &mut confirm_prompt, &mut status_line),
+                    }
+                }
+            }
+        }
+
+        // Set target as next
+        if action == Some(Action::SetNext) {
+            let selected =  list_state.selected();
+            if selected.is_some_and(|index| index < item_count) {
+                let index = selected.expect("Selected index is guaranteed to be Some here");
+                if let Some(target) = targets.targets.get(index) {
+                    match &config.pin_sha256 {
+                        Some(_) => pin_prompt = Some(PinPrompt { action: BackgroundAction::SetNext, target: target.clone(), input: String::new(), rejected: false }),
+                        None => tui_set_next(target, assume_yes, config.is_protected(target), &mut pending, &events, &mut confirm_prompt),
+                    }
+                }
+            }
+        }
+
+        // Move target to the front of BootOrder, after showing the old and new order
+        if action == Some(Action::SetDefault) {
+            if let Some(target) = list_state.selected().and_then(|index| targets.targets.get(index)) {
+                if targets.order.first() == Some(&target.id) {
+                    status_line = StatusLine::info(tr!("\"{}\" is already the default", target.name));
+                } else {
+                    let mut order = targets.order.clone();
+                    order.retain(|id| *id != target.id);
+                    order.insert(0, target.id);
+                    let lines = vec![
+                        tr!("\"{}\" will be booted by default.", target.name),
+                        tr!("BootOrder: {} → {}", format_order(&targets.order), format_order(&order)),
+                    ];
+                    confirm_prompt = Some(ConfirmPrompt { action: BackgroundAction::SetOrder(order), target: target.clone(), lines, question: tr!("Boot this entry by default?") });
+                }
+            }
+        }
+
+        // Put back the boot variables changed last, by this or an earlier run
+        if action == Some(Action::Undo) {
+            let target = list_state.selected().and_then(|index| targets.targets.get(index)).or(targets.targets.first());
+            if let Some(target) = target {
+                pending = Some(PendingAction::spawn(BackgroundAction::Undo, target, &events));
+            }
+        }
+
+        // Boot the default order again instead of the pending BootNext
+        if action == Some(Action::ClearNext) {
+            match targets.next {
+                Some(id) => {
+                    let target = targets.by_id(id).cloned().unwrap_or(BootTarget {
+                        id, name: format_id(id), active: true, device_path: None, loader: LoaderStatus::Unchecked, device: None, os_name: None, removable: false,
+                    });
+                    pending = Some(PendingAction::spawn(BackgroundAction::ClearNext, &target, &events));
+                }
+                None => status_line = StatusLine::info(String::from(tr!("No next boot is set"))),
+            }
+        }
+
+        // Flip whether the firmware boot menu offers the target
+        if action == Some(Action::ToggleActive) {
+            if let Some(target) = list_state.selected().and_then(|index| targets.targets.get(index)) {
+                pending = Some(PendingAction::spawn(BackgroundAction::SetActive(!target.active), target, &events));
+            }
+        }
+
+        // Delete target, after typed confirmation
+        if action == Some(Action::Delete) && list_state.selected().is_some_and(|index| index < item_count) {
+            delete_prompt = Some(DeletePrompt { input: String::new(), mismatch: false });
+        }
+
+        // Delete the duplicates of the target, after listing them
+        if action == Some(Action::RemoveDuplicates) {
+            let target = list_state.selected().and_then(|index| targets.targets.get(index));
+            match target.and_then(|target| duplicates::group_of(&groups, target.id).map(|group| (target, group))) {
+                Some((target, group)) => {
+                    let mut lines = vec![String::from(tr!("These duplicate entries will be deleted:"))];
+                    lines.extend(group.extras.iter().map(|id| format!("  {}", targets.describe(*id))));
+                    if group.keep != target.id {
+                        lines.push(tr!("{} is kept.", targets.describe(group.keep)));
+                    }
+                    confirm_prompt = Some(ConfirmPrompt { action: BackgroundAction::RemoveDuplicates, target: target.clone(), lines, question: tr!("Delete them?") });
+                }
+                None => status_line = StatusLine::info(String::from(tr!("The selected entry has no duplicates"))),
+            }
+        }
+
+    }
+    drop(events);
+
+    // Clean up screen, leaving the cursor where the inline TUI started
+    if inline {
+        terminal.clear()?;
+        let area = terminal.get_frame().size();
+        terminal.set_cursor(area.x, area.y)?;
+        terminal.show_cursor()?;
+    } else {
+        stdout().execute(LeaveAlternateScreen)?;
+    }
+    disable_raw_mode()?;
+    logger::release();
+    sudo::collect_transcript(false);
+
+    if let Some(target) = list_state.selected().and_then(|index| targets.targets.get(index)) {
+        history::save_selection(target);
+    }
+
+    if let Some(message) = exit_message {
+        say!("{}", message);
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    i18n::init();
+    let matches = i18n::localize_command(Arguments::command()).get_matches();
+    let args = Arguments::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    run(args).into()
+}
+
+/// Read a <DEST> given as "-" from the next line of stdin, passed as `input`
+fn dest_from_stdin(dest: &mut String, mut input: impl BufRead) -> std::result::Result<(), String> {
+    if dest != "-" {
+        return Ok(());
+    }
+
+    let mut line = String::new();
+    match input.read_line(&mut line) {
+        Ok(_) if !line.trim().is_empty() => {
+            *dest = line.trim().to_string();
+            Ok(())
+        }
+        Ok(_) => Err(String::from(tr!("Expected a boot entry on standard input, but got nothing"))),
+        Err(e) => Err(tr!("Could not read boot entry from standard input: {}", e)),
+    }
+}
+
+fn run(mut args: Arguments) -> Exit {
+    logger::init(if args.debug.unwrap_or(false) { log::LevelFilter::Debug } else { log::LevelFilter::Warn });
+    // Responses have stdout to themselves
+    logger::set_quiet(args.quiet.unwrap_or(false) || args.serve_stdio.unwrap_or(false));
+    process::set_timeout(args.command_timeout);
+
+    let mock = std::env::var("REBOOT_TO_MOCK").ok();
+    if args.demo.unwrap_or(false) || mock.is_some() {
+        if let Err(e) = demo::enable(mock.as_deref()) {
+            eprintln!("{}", tr!("Could not load demo entries: {}", e));
+
+            return Exit::Failure;
+        }
+    }
+
+    let dests = [args.resolve.as_mut(), args.reboot_to.as_mut(), args.next.as_mut(), args.delete.as_mut(), args.verify.as_mut().and_then(Option::as_mut)]
+        .into_iter()
+        .chain([args.duplicate.as_mut(), args.rename.as_mut()].map(|values| values.and_then(|values| values.first_mut())));
+    for dest in dests.flatten() {
+        if let Err(e) = dest_from_stdin(dest, stdin().lock()) {
+            eprintln!("{}", e);
+
+            return Exit::Failure;
+        }
+    }
+
+    if let Some(Commands::Doctor) = &args.command {
+        let checks = doctor::run();
+        doctor::print(&checks);
+        return if checks.iter().all(|check| check.passed) { Exit::Success } else { Exit::Failure };
+    }
+
+    if let Some(Commands::DebugDump) = &args.command {
+        print!("{}", dump::collect());
+        return Exit::Success;
+    }
+
+    // Without UEFI there are no boot entries, and efibootmgr would only fail confusingly
+    if !firmware::is_uefi() {
+        eprintln!("{}", tr!("This system was booted in legacy BIOS mode (CSM), {} does not exist, so there are no UEFI boot entries to choose from.", firmware::EFI_DIR));
+        eprintln!("{}", tr!("With GRUB, grub-reboot picks the menu entry for the next boot instead. To use reboot-to, enable UEFI boot in the firmware settings, which may need the OS to be reinstalled."));
+        return Exit::NotUefi;
+    }
+
+    let mut targets = get_boot_targets();
+    let unattended = args.unattended.unwrap_or(false);
+    let assume_yes = args.yes.unwrap_or(false) || unattended;
+    let config = if args.no_config.unwrap_or(false) {
+        Config::default()
+    } else {
+        config::load(args.profile.as_deref(), args.config.as_deref())
+    };
+    if config.end_session {
+        session::enable();
+    }
+
+    #[cfg(unix)]
+    if let Some(path) = &args.serve_socket {
+        let path = path.clone().unwrap_or_else(daemon::default_path);
+        return match daemon::serve(&path, &config) {
+            Ok(()) => Exit::Success,
+            Err(e) => {
+                eprintln!("{}", tr!("Could not listen on {}: {}", path.display(), e));
+                Exit::Failure
+            }
+        };
+    }
+
+    if args.serve_stdio.unwrap_or(false) {
+        return match rpc::serve(stdin().lock(), stdout().lock(), &config) {
+            Ok(()) => Exit::Success,
+            Err(e) => {
+                eprintln!("{}", tr!("Could not answer requests: {}", e));
+                Exit::Failure
+            }
+        };
+    }
+
+    if let Some(Commands::Backup { file }) = &args.command {
+        return match backup::write(&targets, file) {
+            Ok(()) => {
+                say!("{}", tr!("Backed up {} boot entries to {}", targets.targets.len(), file.display()));
+                Exit::Success
+            }
+            Err(e) => {
+                eprintln!("{}", tr!("Could not write backup to {}: {}", file.display(), e));
+                Exit::Failure
+            }
+        };
+    }
+
+    if let Some(Commands::Restore { file, dry_run }) = &args.command {
+        let backup = match restore::load(file) {
+            Ok(backup) => backup,
+            Err(e) => {
+                eprintln!("{}", tr!("Could not load backup: {}", e));
+                return Exit::Failure;
+            }
+        };
+
+        let changes = restore::plan(&backup, &targets);
+        if changes.is_empty() {
+            say!("{}", tr!("Boot configuration already matches the backup, nothing to do"));
+            return Exit::Success;
+        }
+
+        restore::print_plan(&changes, &targets);
+        if *dry_run {
+            return Exit::Success;
+        }
+        if !(assume_yes || confirm(tr!("Apply these changes?"))) {
+            return Exit::Cancelled;
+        }
+
+        let failures = restore::apply(&changes);
+        return match failures.iter().find(|e| e.permission_denied).or(failures.first()) {
+            Some(e) => Exit::from_command(e, Exit::EfibootmgrFailed),
+            None => Exit::Success,
+        };
+    }
+
+    if let Some(Commands::Create) = &args.command {
+        return wizard::run(&targets);
+    }
+
+    if let Some(Commands::Jobs { cancel }) = &args.command {
+        if let Some(job) = cancel.as_deref() {
+            return match schedule::cancel(job) {
+                Ok(()) => {
+                    say!("{}", tr!("Cancelled {}", job));
+                    Exit::Success
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    Exit::from_command(&e, Exit::Failure)
+                }
+            };
+        }
+
+        return match schedule::jobs() {
+            Ok(jobs) if jobs.is_empty() => {
+                say!("{}", tr!("No reboots scheduled"));
+                Exit::Success
+            }
+            Ok(jobs) => {
+                for job in jobs {
+                    println!("{} \t {} \t {}", job.name, job.next, job.description);
+                }
+                Exit::Success
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                Exit::from_command(&e, Exit::Failure)
+            }
+        };
+    }
+
+    if let Some(Commands::Schedule { action }) = &args.command {
+        let result = match action {
+            ScheduleAction::Add { when, dest } => {
+                let target = match targets.lookup(dest) {
+                    Ok(target) => target,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return e.exit();
+                    }
+                };
+                if !(confirm_disk(target, assume_yes) && confirm_protected(target, &config)) {
+                    return Exit::Cancelled;
+                }
+                schedule::add(target, when, config.is_protected(target))
+                    .map(|name| say!("{}", tr!("Reboot to \"{}\" scheduled for {} as {}", target.name, when, name)))
+            }
+            ScheduleAction::List => {
+                for schedule in schedule::schedules() {
+                    println!("{} \t {} \t {}", schedule.name, schedule.calendar, schedule.entry);
+                }
+                Ok(())
+            }
+            ScheduleAction::Remove { name } => schedule::remove(name)
+                .map(|schedule| say!("{}", tr!("Removed {}, the reboot to \"{}\" on {}", schedule.name, schedule.entry, schedule.calendar))),
+        };
+
+        return match result {
+            Ok(()) => Exit::Success,
+            Err(e) => {
+                eprintln!("{}", e);
+                Exit::from_command(&e, Exit::Failure)
+            }
+        };
+    }
+
+    if let Some(Commands::Order { action }) = &args.command {
+        match action {
+            OrderAction::Show => {
+                for id in targets.order.iter() {
+                    println!("{} \t {}", format_id(*id), targets.by_id(*id).map(|t| t.name.as_str()).unwrap_or(""));
+                }
+            }
+            OrderAction::Set { entries } => {
+                let order = match parse_order(&targets, entries) {
+                    Ok(order) => order,
+                    Err(exit) => return exit,
+                };
+
+                if let Err(e) = try_set_order(&order) {
+                    eprintln!("{}", e);
+                    return Exit::from_command(&e, Exit::EfibootmgrFailed);
+                }
+                say!("{}", tr!("BootOrder set to {}", format_order(&order)));
+            }
+        }
+
+        return Exit::Success;
+    }
+
+    if let Some(Commands::Status) = &args.command {
+        targets.print_status();
+        if let Some(secure_boot) = efivar::secure_boot() {
+            println!("{}", tr!("Secure Boot:  {}", secure_boot.describe()));
+        }
+        if let Some(firmware) = firmware::read().describe() {
+            println!("{}", tr!("Firmware:     {}", firmware));
+        }
+        if let Some(esp) = esp::find(&targets) {
+            println!("{}", tr!("ESP:          {}", esp.describe()));
+        }
+        let updates = fwupd::pending_updates();
+        if !updates.is_empty() {
+            println!("{}", tr!("Updates:      {} (installed by the firmware on the next reboot, before the boot entry starts)", updates.join(", ")));
+        }
+
+        return Exit::Success;
+    }
+
+    if args.get_timeout.unwrap_or(false) {
+        return match targets.timeout {
+            Some(timeout) => {
+                println!("{}", timeout);
+                Exit::Success
+            }
+            None => {
+                eprintln!("{}", tr!("No UEFI boot menu timeout is set"));
+                Exit::Failure
+            }
+        };
+    }
+
+    if args.get_next.unwrap_or(false) || args.get_current.unwrap_or(false) {
+        let (id, missing) = match args.get_next.unwrap_or(false) {
+            true => (targets.next, tr!("No next boot entry is set")),
+            false => (targets.current, tr!("The currently booted entry is unknown")),
+        };
+
+        return match entry_output(&targets, id, args.id.unwrap_or(false)) {
+            Some(output) => {
+                println!("{}", output);
+                Exit::Success
+            }
+            None => {
+                eprintln!("{}", missing);
+                Exit::Failure
+            }
+        };
+    }
+
+    if let Some(seconds) = args.set_timeout {
+        return match efibootmgr::check(efibootmgr::set_timeout(seconds)) {
+            Ok(()) => {
+                say!("{}", tr!("Set UEFI boot menu timeout to {} seconds", seconds));
+                Exit::Success
+            }
+            Err(e) => {
+                eprintln!("{}", tr!("Could not set timeout: {}", e));
+                Exit::from_command(&e, Exit::EfibootmgrFailed)
+            }
+        };
+    }
+
+    if args.undo.unwrap_or(false) {
+        return match try_undo() {
+            Ok(done) => {
+                done.iter().for_each(|line| say!("{}", line));
+                Exit::Success
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                Exit::from_command(&e, Exit::Failure)
+            }
+        };
+    }
+
+    if args.list.unwrap_or(false) || args.format.is_status() {
+        print!("{}", list::render(&targets, args.sort, args.format, args.verbose.unwrap_or(false), list::use_color()));
+
+        return Exit::Success;
+    }
+
+    if let Some(dest) = args.resolve.as_deref() {
+        return match resolve(&targets, dest) {
+            Ok(id) => {
+                println!("{}", id);
+                Exit::Success
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                e.exit()
+            }
+        };
+    }
+    
+    if args.last.unwrap_or(false) {
+        match history::recent(&history::load(), &targets, 1).first() {
+            Some(target) => args.reboot_to = Some(format_id(target.id)),
+            None => {
+                eprintln!("{}", tr!("No previous reboot to an existing entry found in the history"));
+
+                return Exit::NotFound;
+            }
+        }
+    }
+
+    if let Some(dest) = args.reboot_to.as_deref() {
+        let found = match args.expect_name.as_deref() {
+            Some(name) => targets.lookup_named(dest, name),
+            None => targets.lookup(dest),
+        };
+        let target = match found {
+            Ok(target) => target,
+            Err(e) => {
+                eprintln!("{}", e);
+
+                return e.exit();
+            }
+        };
+
+        // The scheduled reboot checks for running package managers itself, when it is due
+        if let Some(when) = args.at.as_deref() {
+            if !(confirm_disk(target, assume_yes) && confirm_protected(target, &config)) {
+                return Exit::Cancelled;
+            }
+
+            return match schedule::at(target, when, config.is_protected(target)) {
+                Ok(job) => {
+                    say!("{}", tr!("Reboot to \"{}\" scheduled as {}", target.name, job));
+                    Exit::Success
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    Exit::from_command(&e, Exit::Failure)
+                }
+            };
+        }
+
+        if !assume_yes || unattended {
+            let blockers = safety::reboot_blockers();
+            if !blockers.is_empty() {
+                blockers.iter().for_each(|blocker| eprintln!("{}", blocker));
+                eprintln!("{}", tr!("Refusing to reboot, use --yes to reboot anyway"));
+
+                return Exit::Cancelled;
+            }
+        }
+
+        if !assume_yes {
+            let warnings = safety::reboot_warnings();
+            if !warnings.is_empty() {
+                warnings.iter().for_each(|warning| say!("{}", warning));
+                if !confirm(tr!("Reboot anyway?")) {
+                    return Exit::Cancelled;
+                }
+            }
+        }
+
+        if !(confirm_disk(target, assume_yes) && confirm_protected(target, &config)) {
+            return Exit::Cancelled;
+        }
+
+        return match args.delay {
+            Some(delay) => reboot_to_after(&targets, target, delay),
+            None => reboot_to(target),
+        };
+    }
+
+    if let Some(dest) = args.next.as_deref() {
+        let target = match targets.lookup(dest) {
+            Ok(target) => target,
+            Err(e) => {
+                eprintln!("{}", e);
+
+                return e.exit();
+            }
+        };
+
+        if !(confirm_disk(target, assume_yes) && confirm_protected(target, &config)) {
+            return Exit::Cancelled;
+        }
+
+        return set_next_boot_wrapper(target);
+    }
+
+    if let Some(dest) = args.delete.as_deref() {
+        return match confirm_delete(&targets, dest, assume_yes, prompt) {
+            Ok(target) => delete_entry_wrapper(target),
+            Err(exit) => exit,
+        };
+    }
+
+    if let Some([dest, label]) = args.duplicate.as_deref() {
+        let target = match duplicate_source(&targets, dest, label) {
+            Ok(target) => target,
+            Err(exit) => return exit,
+        };
+
+        let raw = efivar::read_efivar(&format!("Boot{}", format_id(target.id)));
+        let source = efibootmgr::EntrySource { device_path: target.device_path.as_ref().map(DevicePath::as_str), raw: raw.as_deref() };
+
+        return match efibootmgr::recreate_entry(None, label, &source, false) {
+            Ok(()) => {
+                say!("{}", tr!("Created \"{}\" as a copy of \"{}\"", label, target.name));
+                Exit::Success
+            }
+            Err(e) => {
+                eprintln!("{}", tr!("Could not duplicate boot entry: {}", e));
+                Exit::from_command(&e, Exit::EfibootmgrFailed)
+            }
+        };
+    }
+
+    if let Some([dest, label]) = args.rename.as_deref() {
+        let target = match rename_target(&targets, dest, label) {
+            Ok(target) => target,
+            Err(exit) => return exit,
+        };
+
+        return match efibootmgr::rename_entry(&targets, target, label) {
+            Ok(()) => {
+                say!("{}", tr!("Renamed \"{}\" to \"{}\"", target.name, label));
+                Exit::Success
+            }
+            Err(e) => {
+                eprintln!("{}", tr!("Could not rename boot entry: {}", e));
+                Exit::from_command(&e, Exit::EfibootmgrFailed)
+            }
+        };
+    }
+
+    if args.remove_duplicates.unwrap_or(false) {
+        let groups = duplicates::find(&targets);
+        if groups.is_empty() {
+            say!("{}", tr!("No duplicate boot entries found"));
+
+            return Exit::Success;
+        }
+
+        for group in groups.iter() {
+            say!("{}", tr!("Keeping {}, deleting:", targets.describe(group.keep)));
+            group.extras.iter().for_each(|id| say!("  {}", targets.describe(*id)));
+        }
+        if !(assume_yes || confirm(tr!("Delete these entries?"))) {
+            return Exit::Cancelled;
+        }
+
+        for id in groups.iter().flat_map(|group| group.extras.iter()) {
+            if let Err(e) = efibootmgr::check(efibootmgr::delete_entry(*id)).map_err(|e| e.context(&tr!("Could not delete duplicate {}", format_id(*id)))) {
+                eprintln!("{}", e);
+
+                return Exit::from_command(&e, Exit::EfibootmgrFailed);
+            }
+        }
+        say!("{}", tr!("Deleted {} duplicate boot entries", groups.iter().map(|group| group.extras.len()).sum::<usize>()));
+
+        return Exit::Success;
+    }
+
+    if let Some(dest) = args.verify {
+        verify::verify_targets(&mut targets, false);
+
+        let target = match dest.as_deref() {
+            None => None,
+            Some(dest) => match targets.lookup(dest) {
+                Ok(target) => Some(target),
+                Err(e) => {
+                    eprintln!("{}", e);
+
+                    return e.exit();
+                }
+            },
+        };
+
+        targets.print_verification(target);
+
+        let broken = targets.targets.iter()
+            .filter(|t| target.is_none_or(|o| o.id == t.id))
+            .any(|t| t.loader.needs_attention());
+        return if broken { Exit::Failure } else { Exit::Success };
+    }
+
+    // Without a terminal (piped, cron, CI) the TUI would only corrupt the output
+    if !stdin().is_terminal() || !stdout().is_terminal() {
+        if targets.targets.is_empty() {
+            eprintln!("{}", tr!("No UEFI boot entries found"));
+
+            return Exit::Failure;
+        }
+        print!("{}", list::render(&targets, args.sort, args.format, args.verbose.unwrap_or(false), list::use_color()));
+
+        return Exit::Success;
+    }
+
+    // Built without the TUI, the list is all there is to show
+    #[cfg(not(feature = "tui"))]
+    {
+        print!("{}", list::render(&targets, args.sort, args.format, args.verbose.unwrap_or(false), list::use_color()));
+        Exit::Success
+    }
+
+    #[cfg(feature = "tui")]
+    {
+        tui_or_accessible(targets, args, config, assume_yes)
+    }
+}
+
+/// Let the user pick an entry in the TUI, or in accessible mode
+#[cfg(feature = "tui")]
+fn tui_or_accessible(mut targets: BootTargets, args: Arguments, mut config: Config, assume_yes: bool) -> Exit {
+    // Mark broken entries in the TUI
+    verify::verify_targets(&mut targets, !config.keep_names);
+
+    if args.title.is_some() {
+        config.title = args.title;
+    }
+    if let Some(theme) = args.theme {
+        config.theme = theme;
+    }
+    if args.accessible.unwrap_or(false) {
+        return accessible::run(targets, assume_yes, &config).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            Exit::Failure
+        });
+    }
+    
+    tui_selection(targets, assume_yes, &config, args.inline.unwrap_or(false)).expect("Error in TUI");
+    
+    Exit::Success
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Entries as efibootmgr lists them, with two Fedora entries sharing a prefix
+    fn targets() -> BootTargets {
+        let path = "HD(1,GPT,0f1c2d3e-4b5a-6978-8a9b-0c1d2e3f4a5b,0x800,0x100000)/File";
+        parse_boot_targets(&format!("BootCurrent: 0001\nTimeout: 5 seconds\nBootOrder: 0003,0001,0002\n\
+            Boot0001* ubuntu\t{path}(\\EFI\\ubuntu\\shimx64.efi)\n\
+            Boot0002* Windows Boot Manager\t{path}(\\EFI\\Microsoft\\Boot\\bootmgfw.efi)\n\
+            Boot0003* Fedora 40\t{path}(\\EFI\\fedora\\shimx64.efi)\n\
+            Boot0004  Fedora 41\t{path}(\\EFI\\fedora41\\shimx64.efi)\n"))
+    }
+
+    #[test]
+    fn confirms_deletion() {
+        let targets = targets();
+        let deleted = |dest: &str, assume_yes: bool, answer: Option<&str>| {
+            confirm_delete(&targets, dest, assume_yes, |_| answer.map(String::from)).map(|target| target.id)
+        };
+
+        assert_eq!(deleted("Windows", false, Some("Windows Boot Manager")), Ok(0x0002));
+        assert_eq!(deleted("Windows", false, Some("0002")), Ok(0x0002));
+        assert_eq!(deleted("Windows", false, Some("2")), Ok(0x0002));
+        // The prefix used to find the entry is not enough to confirm it
+        assert_eq!(deleted("Windows", false, Some("Windows")), Err(Exit::Cancelled));
+        assert_eq!(deleted("Windows", false, Some("0001")), Err(Exit::Cancelled));
+        assert_eq!(deleted("Windows", false, None), Err(Exit::Cancelled));
+        assert_eq!(deleted("macOS", false, Some("macOS")), Err(Exit::NotFound));
+        assert_eq!(deleted("Fedora", false, Some("Fedora 40")), Err(Exit::Ambiguous));
+
+        let unasked = confirm_delete(&targets, "ubuntu", true, |_| panic!("asked to confirm despite --yes"));
+        assert_eq!(unasked.map(|target| target.id), Ok(0x0001));
+    }
+
+    #[test]
+    fn checks_labels() {
+        assert!(efibootmgr::check_label("Windows 11").is_ok());
+        assert!(efibootmgr::check_label("Système de secours").is_ok());
+        assert!(efibootmgr::check_label("").is_err());
+        assert!(efibootmgr::check_label("   ").is_err());
+        assert!(efibootmgr::check_label("ubuntu\tHD(1,GPT)").is_err());
+        assert!(efibootmgr::check_label("two\nlines").is_err());
+        assert!(efibootmgr::check_label("Arch 🐧").is_err());
+    }
+
+    #[test]
+    fn resolves_entry_to_rename() {
+        let targets = targets();
+        let renamed = |dest: &str, label: &str| rename_target(&targets, dest, label).map(|target| target.id);
+
+        assert_eq!(renamed("0002", "Windows 11"), Ok(0x0002));
+        assert_eq!(renamed("Windows", "Windows 11"), Ok(0x0002));
+        assert_eq!(renamed("Fedora 41", "Fedora"), Ok(0x0004));
+        assert_eq!(renamed("Fedora", "Fedora"), Err(Exit::Ambiguous));
+        assert_eq!(renamed("macOS", "Mac"), Err(Exit::NotFound));
+        assert_eq!(renamed("ubuntu", ""), Err(Exit::Failure));
+        assert_eq!(renamed("ubuntu", "ubuntu\n"), Err(Exit::Failure));
+    }
+
+    #[test]
+    fn checks_label_of_duplicate() {
+        let targets = targets();
+        let source = |dest: &str, label: &str| duplicate_source(&targets, dest, label).map(|target| target.id);
+
+        assert_eq!(source("Windows", "Windows (safe mode)"), Ok(0x0002));
+        assert_eq!(source("0001", "ubuntu"), Ok(0x0001));
+        assert_eq!(source("Fedora", "Fedora"), Err(Exit::Ambiguous));
+        assert_eq!(source("macOS", "Mac"), Err(Exit::NotFound));
+        // The label is checked first, whatever the entry
+        assert_eq!(source("ubuntu", " "), Err(Exit::Failure));
+        assert_eq!(source("macOS", "ubuntu\tHD(1,GPT)"), Err(Exit::Failure));
+        assert_eq!(source("ubuntu", "Arch 🐧"), Err(Exit::Failure));
+    }
+
+    #[test]
+    fn parses_order() {
+        let targets = targets();
+        let order = |entries: &[&str]| parse_order(&targets, &entries.iter().map(|e| e.to_string()).collect::<Vec<String>>());
+
+        assert_eq!(order(&["0002,ubuntu", "Fedora 41"]), Ok(vec![0x0002, 0x0001, 0x0004]));
+        assert_eq!(order(&[" 3 , Windows ,", "1"]), Ok(vec![0x0003, 0x0002, 0x0001]));
+        assert_eq!(order(&["ubuntu,macOS"]), Err(Exit::NotFound));
+        assert_eq!(order(&["ubuntu,0009"]), Err(Exit::NotFound));
+        assert_eq!(order(&["ubuntu,Fedora"]), Err(Exit::Ambiguous));
+        assert_eq!(order(&["ubuntu,0001"]), Err(Exit::Failure));
+        assert_eq!(order(&["Windows", "Windows Boot Manager"]), Err(Exit::Failure));
+    }
+
+    #[test]
+    fn exits_with_documented_codes() {
+        // As listed under "Exit codes" in the README, 2 being clap's
+        let command = |permission_denied| CommandError { message: String::from("efibootmgr failed"), permission_denied };
+        let codes = [
+            (Exit::Success, 0),
+            (Exit::Failure, 1),
+            (Exit::NotFound, 3),
+            (LookupError::NotFound(String::from("macOS")).exit(), 3),
+            (Exit::Ambiguous, 4),
+            (LookupError::Ambiguous(String::from("Fedora"), vec![String::from("Fedora 40"), String::from("Fedora 41")]).exit(), 4),
+            (Exit::EfibootmgrFailed, 5),
+            (Exit::from_command(&command(false), Exit::EfibootmgrFailed), 5),
+            (Exit::PermissionDenied, 6),
+            (Exit::from_command(&command(true), Exit::EfibootmgrFailed), 6),
+            (Exit::from_command(&command(true), Exit::Failure), 6),
+            (Exit::RebootFailed, 7),
+            (Exit::Cancelled, 8),
+            (Exit::NotUefi, 9),
+        ];
+
+        for (exit, code) in codes {
+            assert_eq!(exit as u8, code, "{:?}", exit);
+            assert_eq!(ExitCode::from(exit), ExitCode::from(code));
+        }
+    }
+
+    #[test]
+    fn reads_dest_from_stdin() {
+        let read = |dest: &str, input: &str| {
+            let mut dest = String::from(dest);
+            dest_from_stdin(&mut dest, input.as_bytes()).map(|()| dest)
+        };
+
+        assert_eq!(read("-", "Windows Boot Manager\n"), Ok(String::from("Windows Boot Manager")));
+        assert_eq!(read("-", "0002\r\n"), Ok(String::from("0002")));
+        assert_eq!(read("-", "  ubuntu  "), Ok(String::from("ubuntu")));
+        assert!(read("-", "").is_err());
+        assert!(read("-", "\n").is_err());
+        assert!(read("-", " \nubuntu\n").is_err());
+        // Anything but "-" is taken as given, without reading
+        assert_eq!(read("ubuntu", ""), Ok(String::from("ubuntu")));
+
+        // Each "-" takes one line, leaving the rest for the next
+        let mut input = "ubuntu\nFedora 40\n\n".as_bytes();
+        let mut first = String::from("-");
+        let mut second = String::from("-");
+        let mut third = String::from("-");
+        dest_from_stdin(&mut first, &mut input).expect("first line is an entry");
+        dest_from_stdin(&mut second, &mut input).expect("second line is an entry");
+        assert_eq!((first.as_str(), second.as_str()), ("ubuntu", "Fedora 40"));
+        assert!(dest_from_stdin(&mut third, &mut input).is_err());
+    }
+
+    #[test]
+    fn sorts_by_each_key() {
+        // An inactive entry in BootOrder, and entries outside it whose names differ only in case
+        let targets = parse_boot_targets("BootOrder: 0005,0003,0001\n\
+            Boot0001* ubuntu\tHD(1,GPT,0f1c2d3e-4b5a-6978-8a9b-0c1d2e3f4a5b,0x800,0x100000)/File(\\EFI\\ubuntu\\shimx64.efi)\n\
+            Boot0002* Windows Boot Manager\tHD(1,GPT,0f1c2d3e-4b5a-6978-8a9b-0c1d2e3f4a5b,0x800,0x100000)/File(\\EFI\\Microsoft\\Boot\\bootmgfw.efi)\n\
+            Boot0003  EFI Shell\tFv(7cb8bdc9-f8eb-4f34-aaea-3ee4af6516a1)/FvFile(7c04a583-9e3e-4f1c-ad65-e05268d0b4d1)\n\
+            Boot0004* Arch\tHD(1,GPT,0f1c2d3e-4b5a-6978-8a9b-0c1d2e3f4a5b,0x800,0x100000)/File(\\EFI\\arch\\grubx64.efi)\n\
+            Boot0005* arch\tHD(1,GPT,0f1c2d3e-4b5a-6978-8a9b-0c1d2e3f4a5b,0x800,0x100000)/File(\\EFI\\arch\\BOOTx64.efi)\n");
+        let ids = |key| targets.sorted(key).iter().map(|target| target.id).collect::<Vec<u16>>();
+
+        assert_eq!(ids(SortKey::Order), [0x0005, 0x0003, 0x0001, 0x0002, 0x0004]);
+        assert_eq!(ids(SortKey::Id), [0x0001, 0x0002, 0x0003, 0x0004, 0x0005]);
+        assert_eq!(ids(SortKey::Name), [0x0004, 0x0005, 0x0003, 0x0001, 0x0002]);
+        assert_eq!(ids(SortKey::Active), [0x0005, 0x0001, 0x0002, 0x0004, 0x0003]);
+    }
+
+    #[test]
+    fn resolves_ids_and_labels() {
+        let targets = targets();
+        let resolved = |dest| resolve(&targets, dest).map_err(|e| e.exit());
+
+        assert_eq!(resolved("0002"), Ok(String::from("0002")));
+        assert_eq!(resolved("2"), Ok(String::from("0002")));
+        assert_eq!(resolved("Windows Boot Manager"), Ok(String::from("0002")));
+        assert_eq!(resolved("Windows"), Ok(String::from("0002")));
+        assert_eq!(resolved("Fedora 41"), Ok(String::from("0004")));
+        assert_eq!(resolved("windows"), Err(Exit::NotFound));
+        assert_eq!(resolved("0009"), Err(Exit::NotFound));
+
+        match resolve(&targets, "Fedora") {
+            Err(LookupError::Ambiguous(query, names)) => {
+                assert_eq!(query, "Fedora");
+                assert_eq!(names, ["Fedora 40", "Fedora 41"]);
+            }
+            other => panic!("expected an ambiguous match, got {:?}", other),
+        }
+
+        // An ID wins over a name that is also valid hexadecimal, which only resolves by name when no entry has that ID
+        let hex = parse_boot_targets("Boot0001* Add\tHD(1,GPT,0f1c2d3e-4b5a-6978-8a9b-0c1d2e3f4a5b,0x800,0x100000)/File(\\EFI\\add.efi)\n\
+            Boot0ADD* Other\tHD(1,GPT,0f1c2d3e-4b5a-6978-8a9b-0c1d2e3f4a5b,0x800,0x100000)/File(\\EFI\\other.efi)\n");
+        assert_eq!(resolve(&hex, "Add").ok(), Some(String::from("0ADD")));
+        assert_eq!(resolve(&hex, "Ad").ok(), Some(String::from("0001")));
+    }
+
+    #[test]
+    fn looks_up_expected_names_exactly() {
+        let path = "HD(1,GPT,0f1c2d3e-4b5a-6978-8a9b-0c1d2e3f4a5b,0x800,0x100000)/File";
+        let targets = parse_boot_targets(&format!("BootOrder: 0001,0003\n\
+            Boot0001* Windows Boot Manager\t{path}(\\EFI\\Microsoft\\Boot\\bootmgfw.efi)\n\
+            Boot0002* ubuntu\t{path}(\\EFI\\ubuntu\\shimx64.efi)\n\
+            Boot0003* Windows Boot Manager\t{path}(\\EFI\\Microsoft\\Boot\\bootmgfw.efi)\n\
+            Boot0004* Windows Boot Manager (old)\t{path}(\\EFI\\Microsoft\\Boot\\bootmgfw.efi)\n"));
+        let found = |query: &str, name: &str| targets.lookup_named(query, name).map(|target| target.id).map_err(|e| e.exit());
+
+        assert_eq!(found("0003", "Windows Boot Manager"), Ok(0x0003));
+        assert_eq!(found("0002", "ubuntu"), Ok(0x0002));
+        // The ID now has another entry or none, so the one entry with that exact name is used
+        assert_eq!(found("0001", "ubuntu"), Ok(0x0002));
+        assert_eq!(found("0009", "ubuntu"), Ok(0x0002));
+        assert_eq!(found("0009", "Windows Boot Manager"), Err(Exit::Ambiguous));
+        assert_eq!(found("0009", "Windows Boot Manager (old"), Err(Exit::NotFound));
+        assert_eq!(found("0009", "ubu"), Err(Exit::NotFound));
+    }
+
+    #[test]
+    fn prints_next_and_current_entries() {
+        let mut targets = targets();
+        assert_eq!(targets.next, None);
+        assert_eq!(entry_output(&targets, targets.next, false), None);
+        assert_eq!(entry_output(&targets, targets.next, true), None);
+        assert_eq!(entry_output(&targets, targets.current, false).as_deref(), Some("ubuntu"));
+        assert_eq!(entry_output(&targets, targets.current, true).as_deref(), Some("0001"));
+
+        targets.next = Some(0x0002);
+        assert_eq!(entry_output(&targets, targets.next, false).as_deref(), Some("Windows Boot Manager"));
+        assert_eq!(entry_output(&targets, targets.next, true).as_deref(), Some("0002"));
+
+        // BootNext pointing at a deleted entry
+        targets.next = Some(0x000A);
+        assert_eq!(entry_output(&targets, targets.next, false).as_deref(), Some("000A"));
+    }
 }
@@ -0,0 +1,189 @@
+/***
+ * Operating system detection
+ *
+ * Guesses what a boot entry starts from its name and the path of its loader,
+ * e.g. `\EFI\fedora\shimx64.efi`, for the icons in front of the entries.
+ * Entries that don't give anything away are left unrecognized.
+ ***/
+use std::env;
+
+use crate::BootTarget;
+
+/// What a boot entry starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Os {
+    Windows,
+    /// macOS
+    Mac,
+    Ubuntu,
+    Fedora,
+    Arch,
+    Debian,
+    OpenSuse,
+    Mint,
+    Manjaro,
+    /// NixOS
+    Nix,
+    /// Pop!_OS
+    Pop,
+    Gentoo,
+    /// CentOS
+    Centos,
+    RedHat,
+    /// Any other Linux, or a Linux boot loader
+    Linux,
+    /// UEFI shell
+    Shell,
+    /// Network boot
+    Network,
+}
+
+/// Words in the name or loader path giving away the OS, in order of precedence
+const PATTERNS: [(&str, Os); 25] = [
+    ("windows", Os::Windows),
+    ("\\microsoft\\", Os::Windows),
+    ("mac os", Os::Mac),
+    ("macos", Os::Mac),
+    ("ubuntu", Os::Ubuntu),
+    ("fedora", Os::Fedora),
+    ("manjaro", Os::Manjaro),
+    ("arch linux", Os::Arch),
+    ("\\arch\\", Os::Arch),
+    ("debian", Os::Debian),
+    ("opensuse", Os::OpenSuse),
+    ("suse", Os::OpenSuse),
+    ("mint", Os::Mint),
+    ("nixos", Os::Nix),
+    ("pop!_os", Os::Pop),
+    ("\\pop\\", Os::Pop),
+    ("gentoo", Os::Gentoo),
+    ("centos", Os::Centos),
+    ("red hat", Os::RedHat),
+    ("\\redhat\\", Os::RedHat),
+    ("shell", Os::Shell),
+    ("pxe", Os::Network),
+    ("http", Os::Network),
+    ("linux", Os::Linux),
+    ("grub", Os::Linux),
+];
+
+/// Best guess at what the entry starts
+pub fn detect(target: &BootTarget) -> Option<Os> {
+    let name = format!("{} {}", target.os_name.as_deref().unwrap_or_default(), target.name).to_lowercase();
+    let loader = target.device_path.as_ref().and_then(|path| path.file()).unwrap_or_default().to_lowercase();
+
+    PATTERNS.iter()
+        .find(|(pattern, _)| name.contains(pattern) || loader.contains(pattern))
+        .map(|(_, os)| *os)
+        .or_else(|| target.device_path.as_ref().filter(|path| path.as_str().contains("MAC(")).map(|_| Os::Network))
+}
+
+/// How entries are marked with what they start
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Icons {
+    #[default]
+    None,
+    /// Glyphs from a Nerd Font
+    Nerd,
+    /// Short tags like "[win]", for consoles without such a font
+    Ascii,
+}
+
+impl Os {
+    /// Nerd Font glyph
+    fn glyph(self) -> &'static str {
+        match self {
+            Os::Windows => "\u{f17a}",
+            Os::Mac => "\u{f179}",
+            Os::Ubuntu => "\u{f31b}",
+            Os::Fedora => "\u{f30a}",
+            Os::Arch => "\u{f303}",
+            Os::Debian => "\u{f306}",
+            Os::OpenSuse => "\u{f314}",
+            Os::Mint => "\u{f30e}",
+            Os::Manjaro => "\u{f312}",
+            Os::Nix => "\u{f313}",
+            Os::Pop => "\u{f32a}",
+            Os::Gentoo => "\u{f30d}",
+            Os::Centos => "\u{f304}",
+            Os::RedHat => "\u{f316}",
+            Os::Linux => "\u{f17c}",
+            Os::Shell => "\u{f489}",
+            Os::Network => "\u{f0ac}",
+        }
+    }
+
+    /// Three letter tag for the ASCII icons
+    fn tag(self) -> &'static str {
+        match self {
+            Os::Windows => "win",
+            Os::Mac => "mac",
+            Os::Ubuntu => "ubu",
+            Os::Fedora => "fed",
+            Os::Arch => "arc",
+            Os::Debian => "deb",
+            Os::OpenSuse => "sus",
+            Os::Mint => "mnt",
+            Os::Manjaro => "mjr",
+            Os::Nix => "nix",
+            Os::Pop => "pop",
+            Os::Gentoo => "gen",
+            Os::Centos => "cen",
+            Os::RedHat => "rhl",
+            Os::Linux => "lnx",
+            Os::Shell => "sh",
+            Os::Network => "net",
+        }
+    }
+}
+
+impl Icons {
+    /// Parse the `icons` setting
+    pub fn parse(text: &str) -> Option<Icons> {
+        match text {
+            "none" => Some(Icons::None),
+            "nerd" => Some(Icons::Nerd),
+            "ascii" => Some(Icons::Ascii),
+            _ => None,
+        }
+    }
+
+    /// The icons to use in this terminal. The Linux console has no fonts with
+    /// the Nerd Font glyphs, so it gets the ASCII tags instead.
+    pub fn for_terminal(self) -> Icons {
+        match self {
+            Icons::Nerd if env::var("TERM").is_ok_and(|term| term == "linux") => Icons::Ascii,
+            icons => icons,
+        }
+    }
+
+    /// Icon and a space to put in front of an entry, the same width for all
+    /// entries so that the names line up. Empty without icons.
+    pub fn prefix(self, os: Option<Os>) -> String {
+        match self {
+            Icons::None => String::new(),
+            Icons::Nerd => format!("{} ", os.map(Os::glyph).unwrap_or(" ")),
+            Icons::Ascii => format!("{:<6}", os.map(|os| format!("[{}]", os.tag())).unwrap_or_default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_boot_targets;
+
+    #[test]
+    fn detects_os_from_name_and_loader() {
+        let targets = parse_boot_targets("Boot0000* Windows Boot Manager\n\
+            Boot0001* Linux Boot Manager\tHD(1,GPT,4f3c2a1b-9e8d-4c7b-a6f5-0e1d2c3b4a59,0x800,0x82000)/File(\\EFI\\systemd\\systemd-bootx64.efi)\n\
+            Boot0002* shim\tHD(1,GPT,4f3c2a1b-9e8d-4c7b-a6f5-0e1d2c3b4a59,0x800,0x82000)/File(\\EFI\\fedora\\shimx64.efi)\n\
+            Boot0003* UEFI PXEv4 (MAC:3C7C3F1A2B4D)\n\
+            Boot0004* Samsung SSD 980\n");
+
+        let detected: Vec<Option<Os>> = targets.targets.iter().map(detect).collect();
+        assert_eq!(detected, vec![Some(Os::Windows), Some(Os::Linux), Some(Os::Fedora), Some(Os::Network), None]);
+        assert_eq!(Icons::Ascii.prefix(Some(Os::Fedora)), "[fed] ");
+        assert_eq!(Icons::Ascii.prefix(None), "      ");
+    }
+}
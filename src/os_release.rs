@@ -0,0 +1,144 @@
+/***
+ * Installed systems
+ *
+ * Names the Linux installed on a partition from its os-release file, like
+ * "Fedora Linux 40" or "Arch Linux", for entries whose own name says little,
+ * like "Linux Boot Manager" or "UEFI OS". Roots in Btrfs subvolumes, as
+ * Ubuntu, openSUSE and Fedora set them up, are found under `@` and `root`.
+ *
+ * Windows is named after the version of its boot manager on the ESP, which
+ * Windows updates along with itself, so "Windows Boot Manager" can be listed
+ * as "Windows 11" without reading the registry off an NTFS partition.
+ ***/
+use std::{fs, path::Path};
+
+/// Where the root filesystem may be below the top of a partition
+const SUBVOLUMES: [&str; 3] = ["", "@", "root"];
+
+/// A Linux installation, as described by its os-release file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OsRelease {
+    /// Lowercase identifier, like "fedora", also used for its directory on the ESP
+    pub id: String,
+    /// Like "Fedora Linux 40"
+    pub name: String,
+}
+
+/// Value of a variable in os-release, unquoted
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    let inner = ['"', '\''].iter()
+        .find_map(|quote| value.strip_prefix(*quote).and_then(|value| value.strip_suffix(*quote)))
+        .unwrap_or(value);
+    inner.replace("\\\"", "\"").replace("\\\\", "\\")
+}
+
+/// Parse the contents of an os-release file
+fn parse(text: &str) -> Option<OsRelease> {
+    let variables: Vec<(&str, String)> = text.lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim(), unquote(value)))
+        .collect();
+    let get = |key: &str| variables.iter().find(|(k, _)| *k == key).map(|(_, value)| value.clone()).filter(|value| !value.is_empty());
+
+    // PRETTY_NAME tends to carry the edition too, like "(Workstation Edition)"
+    let name = match (get("NAME"), get("VERSION_ID")) {
+        (Some(name), Some(version)) => format!("{} {}", name, version),
+        (Some(name), None) => name,
+        (None, _) => get("PRETTY_NAME")?,
+    };
+    let id = get("ID").unwrap_or_else(|| String::from("linux")).to_lowercase();
+
+    Some(OsRelease { id, name })
+}
+
+/// The Linux installed in the filesystem mounted at `root`, if any
+pub fn read(root: &Path) -> Option<OsRelease> {
+    SUBVOLUMES.iter().map(|subvolume| root.join(subvolume)).find_map(|root| {
+        // /etc/os-release may be an absolute link, which would lead out of `root`
+        let etc = root.join("etc/os-release");
+        let etc = etc.symlink_metadata().is_ok_and(|metadata| metadata.is_file()).then_some(etc);
+
+        [Some(root.join("usr/lib/os-release")), etc].into_iter().flatten()
+            .find_map(|path| fs::read_to_string(path).ok())
+            .and_then(|text| parse(&text))
+    })
+}
+
+/// Signature of the VS_FIXEDFILEINFO structure in the version resource of a PE image
+const FIXED_FILE_INFO_SIGNATURE: [u8; 4] = 0xfeef04bd_u32.to_le_bytes();
+
+/// First build of Windows 11, which still calls itself version 10.0
+const WINDOWS_11_BUILD: u32 = 22000;
+
+/// Major, minor and build number in the version resource of a PE image
+fn file_version(image: &[u8]) -> Option<(u32, u32, u32)> {
+    let start = image.windows(4).position(|window| window == FIXED_FILE_INFO_SIGNATURE)?;
+    let u32_at = |offset: usize| image.get(start + offset..start + offset + 4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+
+    // After the signature and structure version, each half of the file version holds two 16 bit numbers
+    let (high, low) = (u32_at(8)?, u32_at(12)?);
+    Some((high >> 16, high & 0xffff, low >> 16))
+}
+
+/// Windows release a boot manager of this version belongs to, like "Windows 11"
+fn windows_release(image: &[u8]) -> Option<&'static str> {
+    match file_version(image)? {
+        (10, 0, build) if build >= WINDOWS_11_BUILD => Some("Windows 11"),
+        (10, 0, _) => Some("Windows 10"),
+        (6, 3, _) => Some("Windows 8.1"),
+        (6, 2, _) => Some("Windows 8"),
+        (6, 1, _) => Some("Windows 7"),
+        _ => None,
+    }
+}
+
+/// Entry names that say nothing about the system started, as set by
+/// systemd-boot, firmware for the fallback loader, and some installers
+const VAGUE_NAMES: [&str; 8] = ["Linux Boot Manager", "Linux", "UEFI OS", "UEFI Boot", "EFI Boot", "Boot", "GRUB", "Default"];
+
+/// Whether an entry name is too vague to list the entry by, see `VAGUE_NAMES`
+pub fn is_vague(name: &str) -> bool {
+    VAGUE_NAMES.iter().any(|vague| vague.eq_ignore_ascii_case(name.trim()))
+}
+
+/// The Windows the boot manager at `path`, like `\EFI\Microsoft\Boot\bootmgfw.efi`, starts
+pub fn windows(path: &Path) -> Option<String> {
+    fs::read(path).ok().and_then(|image| windows_release(&image)).map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_installed_systems() {
+        let fedora = "NAME=\"Fedora Linux\"\nVERSION=\"40 (Workstation Edition)\"\nID=fedora\nVERSION_ID=40\n\
+            PRETTY_NAME=\"Fedora Linux 40 (Workstation Edition)\"\n";
+        assert_eq!(parse(fedora), Some(OsRelease { id: String::from("fedora"), name: String::from("Fedora Linux 40") }));
+
+        let arch = "# Rolling release\nNAME='Arch Linux'\nPRETTY_NAME=\"Arch Linux\"\nID=arch\nBUILD_ID=rolling\n";
+        assert_eq!(parse(arch), Some(OsRelease { id: String::from("arch"), name: String::from("Arch Linux") }));
+
+        assert_eq!(parse("PRETTY_NAME=\"Linux\"\n"), Some(OsRelease { id: String::from("linux"), name: String::from("Linux") }));
+        assert_eq!(parse("ID=\n"), None);
+    }
+
+    #[test]
+    fn names_windows_by_its_boot_manager() {
+        let image = |major: u32, minor: u32, build: u32| {
+            let mut image = b"MZ padding".to_vec();
+            image.extend(FIXED_FILE_INFO_SIGNATURE);
+            image.extend(0x10000_u32.to_le_bytes());
+            image.extend(((major << 16) | minor).to_le_bytes());
+            image.extend((build << 16).to_le_bytes());
+            image
+        };
+
+        assert_eq!(windows_release(&image(10, 0, 22621)), Some("Windows 11"));
+        assert_eq!(windows_release(&image(10, 0, 19045)), Some("Windows 10"));
+        assert_eq!(windows_release(&image(6, 1, 7601)), Some("Windows 7"));
+        assert_eq!(windows_release(b"MZ no version resource"), None);
+    }
+}
@@ -0,0 +1,471 @@
+/***
+ * efibootmgr output parser
+ *
+ * Parses the output of `efibootmgr -v` line by line. Unknown keys are ignored
+ * so newer efibootmgr versions keep working, and lines that cannot be
+ * understood are reported with their line number instead of silently dropped.
+ ***/
+use std::fmt;
+
+use crate::efibootmgr::parse_id;
+
+/// A boot entry line, e.g. `Boot0001* ubuntu\tHD(1,GPT,...)/File(\EFI\ubuntu\shimx64.efi)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub id: u16,
+    pub name: String,
+    pub active: bool,
+    pub device_path: Option<DevicePath>,
+}
+
+/// Device path node, e.g. `HD(1,GPT,0f1c...,0x800,0x32000)` has kind `HD` and
+/// five arguments. A bare `\EFI\...` file path is a `File` node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub kind: String,
+    pub args: Vec<String>,
+}
+
+/// Textual device path as printed by `efibootmgr -v`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DevicePath {
+    raw: String,
+    pub nodes: Vec<Node>,
+    /// Optional data printed directly after the last node, e.g. `WINDOWS.........`
+    pub optional_data: Option<String>,
+}
+
+/// Split node arguments on commas outside of nested parentheses
+fn split_args(args: &str) -> Vec<String> {
+    let mut result = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in args.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(args[start..i].trim().to_string());
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    if !args.trim().is_empty() {
+        result.push(args[start..].trim().to_string());
+    }
+
+    result
+}
+
+impl DevicePath {
+    pub fn parse(raw: &str) -> DevicePath {
+        let mut nodes = vec![];
+        let mut optional_data = None;
+        let mut rest = raw.trim();
+
+        loop {
+            rest = rest.trim_start_matches('/');
+            if rest.is_empty() {
+                break;
+            }
+
+            // Newer efibootmgr versions print file paths without File()
+            if rest.starts_with('\\') {
+                let end = rest.find('/').unwrap_or(rest.len());
+                nodes.push(Node { kind: String::from("File"), args: vec![rest[..end].to_string()] });
+                rest = &rest[end..];
+                continue;
+            }
+
+            let open = match rest.find(['(', '/']) {
+                Some(i) if rest[i..].starts_with('(') => i,
+                end => {
+                    let end = end.unwrap_or(rest.len());
+                    nodes.push(Node { kind: rest[..end].to_string(), args: vec![] });
+                    rest = &rest[end..];
+                    continue;
+                }
+            };
+
+            let mut depth = 0;
+            let close = rest[open..].char_indices()
+                .find(|(_, c)| {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        _ => (),
+                    }
+                    depth == 0
+                })
+                .map(|(i, _)| open + i)
+                .unwrap_or(rest.len());
+
+            nodes.push(Node { kind: rest[..open].to_string(), args: split_args(&rest[open + 1..close]) });
+            rest = rest.get(close + 1..).unwrap_or("");
+
+            if !rest.is_empty() && !rest.starts_with('/') {
+                optional_data = Some(rest.to_string());
+                break;
+            }
+        }
+
+        DevicePath { raw: raw.trim().to_string(), nodes, optional_data }
+    }
+
+    /// The device path as printed by efibootmgr
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    fn node(&self, kind: &str) -> Option<&Node> {
+        self.nodes.iter().find(|node| node.kind == kind)
+    }
+
+    /// Partition number and GUID of a `HD(n,GPT,guid,...)` node
+    pub fn gpt_partition(&self) -> Option<(u32, &str)> {
+        let hd = self.node("HD")?;
+        match hd.args.as_slice() {
+            [number, kind, guid, ..] if kind == "GPT" => Some((number.parse().ok()?, guid.as_str())),
+            _ => None,
+        }
+    }
+
+    /// Path of the file loaded by the entry, e.g. `\EFI\ubuntu\shimx64.efi`
+    pub fn file(&self) -> Option<&str> {
+        self.node("File")?.args.first().map(String::as_str)
+    }
+}
+
+/// Line of the output that could not be parsed
+#[derive(Debug, PartialEq, Eq)]
+pub struct LineError {
+    /// 1-based line number
+    pub line: usize,
+    pub text: String,
+    pub reason: String,
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}: {:?}", self.line, self.reason, self.text)
+    }
+}
+
+/// Everything understood from the efibootmgr output
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Parsed {
+    pub current: Option<u16>,
+    pub next: Option<u16>,
+    pub order: Vec<u16>,
+    pub timeout: Option<u16>,
+    pub entries: Vec<Entry>,
+    pub errors: Vec<LineError>,
+}
+
+/// Device path nodes a device path can start with. Some efibootmgr versions
+/// separate the name from the device path with spaces instead of a tab.
+const DEVICE_PATH_NODES: &[&str] = &[
+    "HD(", "PciRoot(", "Pci(", "Acpi(", "VenHw(", "VenMsg(", "VenMedia(", "BBS(", "MAC(", "Uri(",
+    "FvVol(", "FvFile(", "File(", "USB(", "Sata(", "NVMe(", "CDROM(", "MemoryMapped(",
+];
+
+/// Make a label safe to print: control characters (e.g. UTF-16 leftovers)
+/// would garble the terminal, so they are replaced
+fn sanitize_name(name: &str) -> String {
+    let name: String = name.chars()
+        .filter(|c| *c != '\0')
+        .map(|c| if c.is_control() { char::REPLACEMENT_CHARACTER } else { c })
+        .collect();
+
+    match name.trim() {
+        "" => String::from("(unnamed)"),
+        name => name.to_string(),
+    }
+}
+
+/// Split the text after the entry ID into name and device path
+fn split_name(rest: &str) -> (String, Option<DevicePath>) {
+    if let Some((name, path)) = rest.split_once('\t') {
+        return (sanitize_name(name), Some(path.trim()).filter(|path| !path.is_empty()).map(DevicePath::parse));
+    }
+
+    let start = rest.char_indices()
+        .filter(|(_, c)| c.is_whitespace())
+        .map(|(i, c)| i + c.len_utf8())
+        .find(|i| DEVICE_PATH_NODES.iter().any(|node| rest[*i..].starts_with(node)));
+
+    match start {
+        Some(i) => (sanitize_name(&rest[..i]), Some(DevicePath::parse(&rest[i..]))),
+        None => (sanitize_name(rest), None),
+    }
+}
+
+/// Parse a line starting with "Boot" that is not a `Key: value` line
+fn parse_entry(line: &str) -> Result<Entry, String> {
+    let rest = &line["Boot".len()..];
+    let digits = rest.chars().take_while(char::is_ascii_hexdigit).count();
+    if digits != 4 {
+        return Err(String::from("boot entry ID is not 4 hexadecimal digits"));
+    }
+    let id = parse_id(&rest[..4]).ok_or("invalid boot entry ID")?;
+
+    let (active, rest) = match rest[4..].strip_prefix('*') {
+        Some(rest) => (true, rest),
+        None => (false, &rest[4..]),
+    };
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return Err(String::from("unexpected text after boot entry ID"));
+    }
+
+    let (name, device_path) = split_name(rest);
+    Ok(Entry { id, name, active, device_path })
+}
+
+/// Whether the line starts like a boot entry, with `Boot` and a 4 digit ID
+fn is_entry(line: &str) -> bool {
+    line.strip_prefix("Boot").is_some_and(|rest| rest.chars().take(4).filter(char::is_ascii_hexdigit).count() == 4)
+}
+
+/// Split a `Key: value` line. FreeBSD pads keys, e.g. `Timeout    : 2 seconds`,
+/// and has keys of multiple words, e.g. `Boot to FW : false`.
+fn split_option(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+    let key = key.trim_end();
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ') {
+        return None;
+    }
+
+    Some((key, value.trim()))
+}
+
+/// Parse the output of `efibootmgr` or `efibootmgr -v`
+pub fn parse(raw: &str) -> Parsed {
+    let mut result = Parsed::default();
+
+    for (index, line) in raw.lines().enumerate() {
+        let error = |reason: &str| LineError { line: index + 1, text: line.to_string(), reason: reason.to_string() };
+
+        // FreeBSD marks the current entry with + and indents the others by one space
+        let line = match line.strip_prefix(['+', ' ']) {
+            Some(entry) if entry.starts_with("Boot") => entry,
+            _ => line,
+        };
+
+        // Blank lines, and continuation lines with details of the previous entry
+        if line.trim().is_empty() || line.starts_with(char::is_whitespace) {
+            continue;
+        }
+
+        if is_entry(line) {
+            match parse_entry(line) {
+                Ok(entry) => result.entries.push(entry),
+                Err(reason) => result.errors.push(error(&reason)),
+            }
+            continue;
+        }
+
+        if let Some((key, value)) = split_option(line) {
+            match key {
+                "BootCurrent" | "BootNext" => match parse_id(value) {
+                    Some(id) if key == "BootCurrent" => result.current = Some(id),
+                    Some(id) => result.next = Some(id),
+                    None => result.errors.push(error("invalid boot entry ID")),
+                },
+                "BootOrder" => {
+                    let ids: Vec<Option<u16>> = value.split(',').filter(|id| !id.trim().is_empty()).map(parse_id).collect();
+                    if ids.iter().any(Option::is_none) {
+                        result.errors.push(error("invalid boot entry ID in BootOrder"));
+                    }
+                    result.order = ids.into_iter().flatten().collect();
+                }
+                "Timeout" => match value.split_whitespace().next().and_then(|t| t.parse::<u16>().ok()) {
+                    Some(timeout) => result.timeout = Some(timeout),
+                    None => result.errors.push(error("invalid timeout")),
+                },
+                _ => (),
+            }
+            continue;
+        }
+
+        if line.starts_with("Boot") {
+            result.errors.push(error("boot entry ID is not 4 hexadecimal digits"));
+            continue;
+        }
+
+        result.errors.push(error("unrecognized line"));
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: u16, name: &str, active: bool, device_path: Option<&str>) -> Entry {
+        Entry { id, name: name.to_string(), active, device_path: device_path.map(DevicePath::parse) }
+    }
+
+    #[test]
+    fn parses_verbose_output() {
+        let parsed = parse("BootCurrent: 0001\n\
+            Timeout: 1 seconds\n\
+            BootOrder: 0001,0000,0002\n\
+            Boot0000* Windows Boot Manager\tHD(1,GPT,0c3f0a52-5b6c-4f1e-9d2a-7b1a3c4d5e6f,0x800,0x32000)/File(\\EFI\\Microsoft\\Boot\\bootmgfw.efi)\n\
+            Boot0001* ubuntu\tHD(1,GPT,0c3f0a52-5b6c-4f1e-9d2a-7b1a3c4d5e6f,0x800,0x32000)/File(\\EFI\\ubuntu\\shimx64.efi)\n");
+
+        assert_eq!(parsed.current, Some(1));
+        assert_eq!(parsed.next, None);
+        assert_eq!(parsed.timeout, Some(1));
+        assert_eq!(parsed.order, vec![1, 0, 2]);
+        assert_eq!(parsed.entries.len(), 2);
+        assert_eq!(parsed.entries[0], entry(0, "Windows Boot Manager", true,
+            Some("HD(1,GPT,0c3f0a52-5b6c-4f1e-9d2a-7b1a3c4d5e6f,0x800,0x32000)/File(\\EFI\\Microsoft\\Boot\\bootmgfw.efi)")));
+        assert!(parsed.errors.is_empty());
+    }
+
+    #[test]
+    fn parses_hex_ids() {
+        let parsed = parse("BootNext: 000A\nBootOrder: 000A,001F\nBoot000A* Fedora\nBoot001F* Arch\n");
+
+        assert_eq!(parsed.next, Some(0x000A));
+        assert_eq!(parsed.order, vec![0x000A, 0x001F]);
+        assert_eq!(parsed.entries, vec![entry(0x000A, "Fedora", true, None), entry(0x001F, "Arch", true, None)]);
+    }
+
+    #[test]
+    fn parses_inactive_entries() {
+        let parsed = parse("Boot0003  EFI Network\tPciRoot(0x0)/Pci(0x1c,0x0)/MAC(001122334455,0)\n");
+
+        assert_eq!(parsed.entries, vec![entry(3, "EFI Network", false, Some("PciRoot(0x0)/Pci(0x1c,0x0)/MAC(001122334455,0)"))]);
+    }
+
+    #[test]
+    fn parses_entries_without_device_path() {
+        let parsed = parse("Boot0000* Windows Boot Manager\nBoot0001* ubuntu\t\n");
+
+        assert_eq!(parsed.entries, vec![entry(0, "Windows Boot Manager", true, None), entry(1, "ubuntu", true, None)]);
+        assert!(parsed.errors.is_empty());
+    }
+
+    #[test]
+    fn splits_device_path_separated_by_spaces() {
+        let parsed = parse("Boot0002* UEFI OS  HD(2,GPT,0c3f0a52-5b6c-4f1e-9d2a-7b1a3c4d5e6f,0x800,0x32000)/File(\\EFI\\BOOT\\BOOTX64.EFI)\n");
+
+        assert_eq!(parsed.entries[0].name, "UEFI OS");
+        assert_eq!(parsed.entries[0].device_path.as_ref().map(DevicePath::as_str), Some("HD(2,GPT,0c3f0a52-5b6c-4f1e-9d2a-7b1a3c4d5e6f,0x800,0x32000)/File(\\EFI\\BOOT\\BOOTX64.EFI)"));
+    }
+
+    #[test]
+    fn keeps_parentheses_in_names() {
+        let parsed = parse("Boot0002* UEFI PXEv4 (MAC:001122334455)\n");
+
+        assert_eq!(parsed.entries, vec![entry(2, "UEFI PXEv4 (MAC:001122334455)", true, None)]);
+    }
+
+    #[test]
+    fn handles_boot0000_as_current() {
+        let parsed = parse("BootCurrent: 0000\nBoot0000* Windows Boot Manager\n");
+
+        assert_eq!(parsed.current, Some(0));
+        assert_eq!(parsed.entries[0].id, 0);
+    }
+
+    #[test]
+    fn ignores_unknown_keys_and_localized_values() {
+        let parsed = parse("MirroredPercentageAbove4G: 0.00\nMirrorMemoryBelow4GB: false\nTimeout: 5 Sekunden\n");
+
+        assert_eq!(parsed.timeout, Some(5));
+        assert!(parsed.errors.is_empty());
+    }
+
+    #[test]
+    fn ignores_continuation_lines() {
+        let parsed = parse("Boot0000* Windows Boot Manager\n      dp: 04 01 2a 00\n    data: 57 49 4e 44\n");
+
+        assert_eq!(parsed.entries.len(), 1);
+        assert!(parsed.errors.is_empty());
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        let parsed = parse("BootCurrent: zzzz\nBoot12* Short ID\nBootOrder: 0001,xyz\nsomething else\nBoot0001* ok\n");
+
+        let lines: Vec<usize> = parsed.errors.iter().map(|error| error.line).collect();
+        assert_eq!(lines, vec![1, 2, 3, 4]);
+        assert_eq!(parsed.current, None);
+        assert_eq!(parsed.order, vec![1]);
+        assert_eq!(parsed.entries, vec![entry(1, "ok", true, None)]);
+    }
+
+    #[test]
+    fn parses_device_path_nodes() {
+        let path = DevicePath::parse("HD(1,GPT,0c3f0a52-5b6c-4f1e-9d2a-7b1a3c4d5e6f,0x800,0x32000)/File(\\EFI\\ubuntu\\shimx64.efi)");
+
+        assert_eq!(path.nodes.len(), 2);
+        assert_eq!(path.nodes[0].kind, "HD");
+        assert_eq!(path.nodes[0].args, vec!["1", "GPT", "0c3f0a52-5b6c-4f1e-9d2a-7b1a3c4d5e6f", "0x800", "0x32000"]);
+        assert_eq!(path.gpt_partition(), Some((1, "0c3f0a52-5b6c-4f1e-9d2a-7b1a3c4d5e6f")));
+        assert_eq!(path.file(), Some("\\EFI\\ubuntu\\shimx64.efi"));
+        assert_eq!(path.optional_data, None);
+    }
+
+    #[test]
+    fn parses_bare_file_paths() {
+        let path = DevicePath::parse("HD(2,GPT,0c3f0a52-5b6c-4f1e-9d2a-7b1a3c4d5e6f,0x800,0x32000)/\\EFI\\fedora\\shimx64.efi");
+
+        assert_eq!(path.file(), Some("\\EFI\\fedora\\shimx64.efi"));
+    }
+
+    #[test]
+    fn separates_optional_data() {
+        let path = DevicePath::parse("HD(1,GPT,0c3f0a52-5b6c-4f1e-9d2a-7b1a3c4d5e6f,0x800,0x32000)/File(\\EFI\\Microsoft\\Boot\\bootmgfw.efi)WINDOWS.........");
+
+        assert_eq!(path.file(), Some("\\EFI\\Microsoft\\Boot\\bootmgfw.efi"));
+        assert_eq!(path.optional_data.as_deref(), Some("WINDOWS........."));
+    }
+
+    #[test]
+    fn parses_network_device_paths() {
+        let path = DevicePath::parse("PciRoot(0x0)/Pci(0x1c,0x0)/MAC(001122334455,0)/IPv4(0.0.0.0,0,DHCP)");
+
+        let kinds: Vec<&str> = path.nodes.iter().map(|node| node.kind.as_str()).collect();
+        assert_eq!(kinds, vec!["PciRoot", "Pci", "MAC", "IPv4"]);
+        assert_eq!(path.gpt_partition(), None);
+        assert_eq!(path.file(), None);
+    }
+
+    #[test]
+    fn sanitizes_odd_labels() {
+        let raw = String::from_utf8_lossy(b"Boot0000* W\0i\0n\0\x1b[2J\xff\xfe\nBoot0001* \0\0\n");
+        let parsed = parse(&raw);
+
+        assert_eq!(parsed.entries[0].name, "Win\u{FFFD}[2J\u{FFFD}\u{FFFD}");
+        assert_eq!(parsed.entries[1].name, "(unnamed)");
+        assert!(parsed.errors.is_empty());
+    }
+
+    #[test]
+    fn parses_freebsd_output() {
+        let parsed = parse("Boot to FW : false\n\
+            BootCurrent: 0004\n\
+            Timeout    : 2 seconds\n\
+            BootOrder  : 0004, 0000, 0001\n\
+            +Boot0004* FreeBSD HD(1,GPT,0c3f0a52-5b6c-4f1e-9d2a-7b1a3c4d5e6f,0x28,0x82000)/File(\\efi\\freebsd\\loader.efi)\n\
+            \x20                     nvd0p1:/efi/freebsd/loader.efi /boot/efi//efi/freebsd/loader.efi\n\
+            \x20Boot0000* Windows Boot Manager HD(2,GPT,8a7b6c5d-4e3f-4a1b-9c8d-7e6f5a4b3c2d,0x82800,0x32000)/File(\\EFI\\Microsoft\\Boot\\bootmgfw.efi)\n\
+            \x20Boot0001  UEFI Shell\n");
+
+        assert_eq!(parsed.current, Some(4));
+        assert_eq!(parsed.timeout, Some(2));
+        assert_eq!(parsed.order, vec![4, 0, 1]);
+        assert_eq!(parsed.entries.len(), 3);
+        assert_eq!(parsed.entries[0].name, "FreeBSD");
+        assert_eq!(parsed.entries[0].device_path.as_ref().and_then(DevicePath::file), Some("\\efi\\freebsd\\loader.efi"));
+        assert_eq!(parsed.entries[1].name, "Windows Boot Manager");
+        assert_eq!(parsed.entries[2], entry(1, "UEFI Shell", false, None));
+        assert!(parsed.errors.is_empty());
+    }
+}
@@ -0,0 +1,105 @@
+/***
+ * Subprocess execution
+ *
+ * Runs commands with a timeout, so a hanging efibootmgr (buggy firmware, slow
+ * NVRAM) cannot freeze the TUI, and retries failed writes with backoff.
+ ***/
+use std::{env, io::{self, Read, Write}, path::PathBuf, process::{Command, Output, Stdio}, sync::atomic::{AtomicU64, Ordering}, thread::{self, JoinHandle}, time::{Duration, Instant}};
+
+use crate::{demo, sudo};
+
+/// Default number of seconds a command may take before it is killed
+pub const DEFAULT_TIMEOUT: u64 = 10;
+
+/// Number of attempts for a failing write
+const ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry, doubled for every following one
+const BACKOFF: Duration = Duration::from_millis(250);
+
+static TIMEOUT: AtomicU64 = AtomicU64::new(DEFAULT_TIMEOUT);
+
+/// Change the number of seconds a command may take
+pub fn set_timeout(seconds: u64) {
+    TIMEOUT.store(seconds, Ordering::Relaxed);
+}
+
+/// Full path of a program on PATH
+pub fn find_program(name: &str) -> Option<PathBuf> {
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+fn read_pipe(pipe: Option<impl Read + Send + 'static>) -> Option<JoinHandle<Vec<u8>>> {
+    pipe.map(|mut pipe| thread::spawn(move || {
+        let mut buffer = vec![];
+        let _ = pipe.read_to_end(&mut buffer);
+        buffer
+    }))
+}
+
+fn join_pipe(reader: Option<JoinHandle<Vec<u8>>>) -> Vec<u8> {
+    reader.and_then(|reader| reader.join().ok()).unwrap_or_default()
+}
+
+/// Run a command and capture its output, writing `input` to its stdin. The
+/// command is killed when it does not finish in time.
+pub fn output(command: &mut Command, input: Option<&str>) -> io::Result<Output> {
+    if demo::is_enabled() {
+        return Ok(demo::output(command));
+    }
+
+    let mut child = command
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        let _ = writeln!(stdin, "{}", input);
+    }
+
+    // Read both pipes while waiting, a full pipe would block the command
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    let seconds = TIMEOUT.load(Ordering::Relaxed);
+    let deadline = Instant::now() + Duration::from_secs(seconds);
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(io::ErrorKind::TimedOut, format!("timed out after {} seconds", seconds)));
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    Ok(Output { status, stdout: join_pipe(stdout), stderr: join_pipe(stderr) })
+}
+
+/// Run a write until it succeeds, at most `ATTEMPTS` times. Missing privileges
+/// will not go away by retrying, so those failures are returned right away.
+pub fn retry(mut run: impl FnMut() -> io::Result<Output>) -> io::Result<Output> {
+    let mut delay = BACKOFF;
+    let mut attempt = 1;
+
+    loop {
+        let output = run();
+        let retryable = match &output {
+            Ok(o) => !o.status.success() && !sudo::is_permission_denied(o),
+            Err(e) => e.kind() == io::ErrorKind::TimedOut,
+        };
+        if !retryable || attempt == ATTEMPTS {
+            return output;
+        }
+
+        log::debug!("attempt {} of {} failed, retrying in {} ms", attempt, ATTEMPTS, delay.as_millis());
+        thread::sleep(delay);
+        delay *= 2;
+        attempt += 1;
+    }
+}
@@ -0,0 +1,116 @@
+/***
+ * Rebooting
+ *
+ * The reboot itself is left to `shutdown -r now`, or `systemctl reboot` on
+ * systems without shutdown. BusyBox, as on Alpine and embedded systems, has
+ * no shutdown applet that takes these options but a `reboot` one, which is
+ * used next. Initramfs shells and rescue containers may have none of them,
+ * so on Linux the last resort is to sync the filesystems and call reboot(2)
+ * directly, which needs root. When enabled, a desktop session is asked to
+ * end and reboot before any of them, see `session`.
+ ***/
+use std::{fs, io, path::{Path, PathBuf}, process::{Command, Output}};
+
+use crate::{efibootmgr::{self, CommandError}, i18n::tr, process::find_program, session, sudo};
+
+/// Commands that reboot with their arguments, tried in this order until one exists
+const COMMANDS: [(&str, &[&str]); 3] = [
+    ("shutdown", &["-r", "now"]),
+    ("systemctl", &["reboot"]),
+    // BusyBox and sysvinit
+    ("reboot", &[]),
+];
+
+/// Whether a program is BusyBox, whose applets are links to it
+fn is_busybox(path: &Path) -> bool {
+    fs::canonicalize(path).is_ok_and(|path| path.file_name().is_some_and(|name| name.to_string_lossy().starts_with("busybox")))
+}
+
+/// Whether a command found at `path` takes the arguments it is run with. A
+/// shutdown that is BusyBox doesn't know `-r now`.
+fn usable(name: &str, path: &Path) -> bool {
+    name != "shutdown" || !is_busybox(path)
+}
+
+/// Whether a command could not be run because it does not exist
+fn missing(output: &io::Result<Output>) -> bool {
+    matches!(output, Err(e) if e.kind() == io::ErrorKind::NotFound)
+}
+
+/// Command `reboot` runs, with its arguments, or None when it falls back to reboot(2)
+pub fn command() -> Option<(PathBuf, &'static [&'static str])> {
+    COMMANDS.iter().find_map(|(name, args)| find_program(name).filter(|path| usable(name, path)).map(|path| (path, *args)))
+}
+
+/// Reboot with the first of the reboot commands that is available, or reboot(2)
+pub fn reboot() -> Result<(), CommandError> {
+    if cfg!(windows) {
+        return efibootmgr::check_command("shutdown", sudo::run(Command::new("shutdown").args(["/r", "/t", "0"])));
+    }
+    if session::end_and_reboot() {
+        return Ok(());
+    }
+
+    for (name, args) in COMMANDS {
+        if find_program(name).is_some_and(|path| !usable(name, &path)) {
+            log::info!("{} is BusyBox, trying the next way to reboot", name);
+            continue;
+        }
+
+        let output = sudo::run(Command::new(name).args(args));
+        if !missing(&output) {
+            return efibootmgr::check_command(name, output);
+        }
+    }
+
+    log::info!("no command to reboot with found, rebooting with reboot(2)");
+    syscall()
+}
+
+#[cfg(target_os = "linux")]
+fn syscall() -> Result<(), CommandError> {
+    // SAFETY: neither call takes pointers, and reboot(2) only returns on failure
+    let result = unsafe {
+        libc::sync();
+        libc::reboot(libc::RB_AUTOBOOT)
+    };
+    if result == 0 {
+        return Ok(());
+    }
+
+    let e = io::Error::last_os_error();
+    Err(CommandError {
+        message: tr!("could not reboot with reboot(2): {}", e),
+        permission_denied: e.kind() == io::ErrorKind::PermissionDenied,
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn syscall() -> Result<(), CommandError> {
+    Err(CommandError { message: String::from(tr!("neither shutdown, systemctl nor reboot found")), permission_denied: false })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// Directory removed when the test ends, also when an assertion fails
+    struct TestDir(std::path::PathBuf);
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn passes_over_busybox_shutdown() {
+        let dir = TestDir(crate::tempdir::create("test").expect("create test directory"));
+        fs::write(dir.0.join("busybox"), "").expect("write fake busybox");
+        std::os::unix::fs::symlink("busybox", dir.0.join("shutdown")).expect("link shutdown to busybox");
+
+        assert!(!usable("shutdown", &dir.0.join("shutdown")));
+        assert!(usable("reboot", &dir.0.join("busybox")));
+        assert!(usable("shutdown", &dir.0.join("missing")));
+    }
+}
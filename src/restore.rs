@@ -0,0 +1,213 @@
+/***
+ * Boot configuration restore
+ *
+ * Compares a backup made by `reboot-to backup` with the current configuration,
+ * and recreates missing entries, BootOrder and timeout using efibootmgr.
+ ***/
+use std::{fs, path::Path};
+
+use crate::{efibootmgr::{self, check, format_id, parse_id, CommandError, EntrySource}, i18n::tr, json, parser::DevicePath, BootTargets};
+
+#[derive(Debug)]
+pub struct BackupEntry {
+    pub id: u16,
+    pub name: String,
+    pub device_path: Option<String>,
+    pub raw: Option<Vec<u8>>,
+}
+
+#[derive(Debug)]
+pub struct Backup {
+    pub timeout: Option<u16>,
+    pub order: Vec<u16>,
+    pub entries: Vec<BackupEntry>,
+}
+
+fn json_id(value: &json::Value) -> Option<u16> {
+    parse_id(value.as_str()?)
+}
+
+/// Bytes of a hex string, as the raw load options are stored in backups
+fn from_hex(hex: &str) -> Result<Vec<u8>, String> {
+    // Checked first, as slicing in the middle of a character would panic, and from_str_radix takes a sign
+    if !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) || !hex.len().is_multiple_of(2) {
+        return Err(String::from("raw data is not an even number of hex digits"));
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| e.to_string())).collect()
+}
+
+/// Read a backup file
+pub fn load(path: &Path) -> Result<Backup, String> {
+    let text = fs::read_to_string(path).map_err(|e| tr!("could not read {}: {}", path.display(), e))?;
+    let document = json::parse(&text).map_err(|e| tr!("invalid JSON in {}: {}", path.display(), e))?;
+
+    let entries = document.get("entries").and_then(|e| e.as_array())
+        .ok_or("backup contains no entries")?
+        .iter()
+        .map(|entry| {
+            Ok(BackupEntry {
+                id: entry.get("id").and_then(json_id).ok_or("entry without valid id")?,
+                name: entry.get("name").and_then(|n| n.as_str()).ok_or("entry without name")?.to_string(),
+                device_path: entry.get("device_path").and_then(|d| d.as_str()).map(String::from),
+                raw: entry.get("raw").and_then(|r| r.as_str()).map(from_hex).transpose()?,
+            })
+        })
+        .collect::<Result<Vec<BackupEntry>, String>>()?;
+
+    Ok(Backup {
+        timeout: document.get("timeout").and_then(|t| t.as_u64()).and_then(|t| u16::try_from(t).ok()),
+        order: document.get("boot_order").and_then(|o| o.as_array())
+            .map(|order| order.iter().filter_map(json_id).collect())
+            .unwrap_or_default(),
+        entries,
+    })
+}
+
+#[derive(Debug)]
+pub enum Change<'a> {
+    Create(&'a BackupEntry),
+    Replace(&'a BackupEntry),
+    Order(Vec<u16>),
+    Timeout(u16),
+}
+
+/// Work out which changes are needed to bring the current configuration back
+/// to the backup. Entries not present in the backup are left alone.
+pub fn plan<'a>(backup: &'a Backup, current: &BootTargets) -> Vec<Change<'a>> {
+    let mut changes = vec![];
+
+    for entry in backup.entries.iter() {
+        match current.targets.iter().find(|target| target.id == entry.id) {
+            None => changes.push(Change::Create(entry)),
+            Some(target) if target.name != entry.name || target.device_path.as_ref().map(DevicePath::as_str) != entry.device_path.as_deref() => {
+                changes.push(Change::Replace(entry))
+            }
+            Some(_) => (),
+        }
+    }
+
+    if !backup.order.is_empty() && backup.order != current.order {
+        changes.push(Change::Order(backup.order.clone()));
+    }
+
+    if let Some(timeout) = backup.timeout {
+        if current.timeout != Some(timeout) {
+            changes.push(Change::Timeout(timeout));
+        }
+    }
+
+    changes
+}
+
+/// Print the changes as a diff against the current configuration
+pub fn print_plan(changes: &[Change], current: &BootTargets) {
+    let format_order = |order: &[u16]| order.iter().map(|id| format_id(*id)).collect::<Vec<String>>().join(",");
+
+    for change in changes {
+        match change {
+            Change::Create(entry) => println!("+ Boot{} {}", format_id(entry.id), entry.name),
+            Change::Replace(entry) => {
+                let old = current.targets.iter().find(|target| target.id == entry.id);
+                println!("- Boot{} {}", format_id(entry.id), old.map(|t| t.name.as_str()).unwrap_or(""));
+                println!("+ Boot{} {}", format_id(entry.id), entry.name);
+            }
+            Change::Order(order) => {
+                println!("- BootOrder: {}", format_order(&current.order));
+                println!("+ BootOrder: {}", format_order(order));
+            }
+            Change::Timeout(timeout) => {
+                println!("- Timeout: {}", current.timeout.map(|t| t.to_string()).unwrap_or(String::from(tr!("unset"))));
+                println!("+ Timeout: {}", timeout);
+            }
+        }
+    }
+}
+
+fn create(entry: &BackupEntry) -> Result<(), CommandError> {
+    efibootmgr::recreate_entry(Some(entry.id), &entry.name, &EntrySource {
+        device_path: entry.device_path.as_deref(),
+        raw: entry.raw.as_deref(),
+    }, false)
+}
+
+/// Apply the changes, going on after a failed one, returning the failures
+pub fn apply(changes: &[Change]) -> Vec<CommandError> {
+    let mut failures = vec![];
+
+    for change in changes {
+        let result = match change {
+            Change::Create(entry) => create(entry),
+            Change::Replace(entry) => efibootmgr::resolve_location(entry.device_path.as_deref()).map_err(CommandError::from)
+                .and_then(|_| check(efibootmgr::delete_entry(entry.id)))
+                .and_then(|_| create(entry)),
+            Change::Order(order) => check(efibootmgr::set_order(order)),
+            Change::Timeout(timeout) => check(efibootmgr::set_timeout(*timeout)),
+        };
+
+        if let Err(e) = result {
+            let what = match change {
+                Change::Create(entry) | Change::Replace(entry) => format!("Boot{} {}", format_id(entry.id), entry.name),
+                Change::Order(_) => String::from("BootOrder"),
+                Change::Timeout(_) => String::from("Timeout"),
+            };
+            eprintln!("{}", tr!("Could not restore {}: {}", what, e));
+            failures.push(e);
+        }
+    }
+
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_hex() {
+        assert_eq!(from_hex("0a1BfF"), Ok(vec![0x0a, 0x1b, 0xff]));
+        assert_eq!(from_hex(""), Ok(vec![]));
+        assert!(from_hex("abc").is_err());
+        assert!(from_hex("zz").is_err());
+        assert!(from_hex("+f").is_err());
+        assert!(from_hex("é").is_err());
+        assert!(from_hex("0é").is_err());
+    }
+
+    #[test]
+    fn plans_changes_against_current_entries() {
+        let current = crate::parse_boot_targets("BootCurrent: 0001\nTimeout: 5 seconds\nBootOrder: 0001,0002\n\
+            Boot0001* ubuntu\tHD(1,GPT,0f1c2d3e-4b5a-6978-8a9b-0c1d2e3f4a5b,0x800,0x100000)/File(\\EFI\\ubuntu\\shimx64.efi)\n\
+            Boot0002* Windows Boot Manager\tHD(1,GPT,0f1c2d3e-4b5a-6978-8a9b-0c1d2e3f4a5b,0x800,0x100000)/File(\\EFI\\Microsoft\\Boot\\bootmgfw.efi)\n\
+            Boot0005* Left alone\tHD(1,GPT,0f1c2d3e-4b5a-6978-8a9b-0c1d2e3f4a5b,0x800,0x100000)/File(\\EFI\\other.efi)\n");
+        let entry = |id: u16, name: &str, loader: &str| BackupEntry {
+            id,
+            name: String::from(name),
+            device_path: Some(format!("HD(1,GPT,0f1c2d3e-4b5a-6978-8a9b-0c1d2e3f4a5b,0x800,0x100000)/File({})", loader)),
+            raw: None,
+        };
+
+        let unchanged = Backup {
+            timeout: Some(5),
+            order: vec![0x0001, 0x0002],
+            entries: vec![entry(0x0001, "ubuntu", "\\EFI\\ubuntu\\shimx64.efi")],
+        };
+        assert!(plan(&unchanged, &current).is_empty());
+
+        let backup = Backup {
+            timeout: Some(3),
+            order: vec![0x0002, 0x0001, 0x0003],
+            entries: vec![
+                entry(0x0001, "ubuntu", "\\EFI\\ubuntu\\grubx64.efi"),
+                entry(0x0002, "Windows Boot Manager", "\\EFI\\Microsoft\\Boot\\bootmgfw.efi"),
+                entry(0x0003, "Fedora", "\\EFI\\fedora\\shimx64.efi"),
+            ],
+        };
+        let changes: Vec<String> = plan(&backup, &current).iter().map(|change| match change {
+            Change::Create(entry) => format!("create {}", format_id(entry.id)),
+            Change::Replace(entry) => format!("replace {}", format_id(entry.id)),
+            Change::Order(order) => format!("order {:?}", order),
+            Change::Timeout(timeout) => format!("timeout {}", timeout),
+        }).collect();
+        assert_eq!(changes, ["replace 0001", "create 0003", "order [2, 1, 3]", "timeout 3"]);
+    }
+}
@@ -0,0 +1,330 @@
+/***
+ * JSON-RPC
+ *
+ * `--serve-stdio` takes JSON-RPC 2.0 requests, one per line on stdin, and
+ * answers each on a line of stdout, so that graphical frontends and editor
+ * plugins can drive reboot-to as a subprocess instead of reading its text
+ * output. The methods are:
+ *
+ * - `list`: the entries, with BootCurrent, BootNext and BootOrder
+ * - `resolve`, with `{"dest": ...}`: the entry a <DEST> names
+ * - `set_next`, with `{"dest": ..., "force": false}`: set BootNext
+ * - `reboot`, with `{"dest": ..., "force": false}`: set BootNext and reboot
+ * - `clear`: clear BootNext
+ *
+ * Entries are objects with the fields of the --list CSV columns, and after
+ * `set_next` and `reboot` the answer is the entry as read again. Errors carry
+ * the exit code of the same failure on the command line, like 3 for an entry
+ * not found. Where the command line would ask first, `set_next` and `reboot`
+ * fail with 8 and the reasons in `data.reasons`, and go ahead once called
+ * again with `force`, after the user has agreed. A protected entry, which the
+ * command line asks about even with --yes, also needs `confirm_protected`,
+ * so that a frontend always forcing its calls can't skip that question.
+ * Batches are not supported.
+ ***/
+use std::{io::{self, BufRead, Read, Write}, sync::Mutex};
+
+use crate::{
+    config::Config, efibootmgr::{format_id, CommandError}, get_boot_targets, history, i18n::tr, journal, json::{self, Value},
+    protected_warning, safety, try_clear_next_boot, try_set_next_boot, try_shutdown, verify, BootTarget, BootTargets, Exit,
+};
+
+/// Error codes of JSON-RPC itself
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Error {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl Error {
+    fn new(code: i64, message: impl Into<String>) -> Error {
+        Error { code, message: message.into(), data: None }
+    }
+
+    /// A failed command, with the exit code it would have on the command line
+    fn command(error: CommandError, otherwise: Exit) -> Error {
+        Error::new(Exit::from_command(&error, otherwise) as i64, error.message)
+    }
+
+    /// Things to confirm first, like the command line asks about, with
+    /// whether they include the entry being protected
+    fn unconfirmed(reasons: Vec<String>, protected: bool) -> Error {
+        let message = match protected {
+            true => tr!("Not confirmed, call again with \"confirm_protected\" once the user has agreed to use a protected entry"),
+            false => tr!("Not confirmed, call again with \"force\" once the user has agreed"),
+        };
+        Error {
+            code: Exit::Cancelled as i64,
+            message: String::from(message),
+            data: Some(Value::object([
+                ("reasons", Value::Array(reasons.into_iter().map(Value::from).collect())),
+                ("protected", protected.into()),
+            ])),
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        let mut error = vec![("code", Value::Number(self.code as f64)), ("message", Value::from(self.message.as_str()))];
+        error.extend(self.data.clone().map(|data| ("data", data)));
+        Value::Object(error.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+    }
+}
+
+fn id_value(id: Option<u16>) -> Value {
+    id.map(|id| Value::from(format_id(id))).unwrap_or(Value::Null)
+}
+
+/// An entry, with the fields of the --list CSV columns
+fn entry(targets: &BootTargets, target: &BootTarget) -> Value {
+    let path = target.device_path.as_ref();
+    let position = targets.order.iter().position(|id| *id == target.id);
+
+    Value::object([
+        ("id", format_id(target.id).into()),
+        ("name", target.name.as_str().into()),
+        ("active", target.active.into()),
+        ("order", position.map(|i| Value::from(i as u64 + 1)).unwrap_or(Value::Null)),
+        ("current", (targets.current == Some(target.id)).into()),
+        ("next", (targets.next == Some(target.id)).into()),
+        ("loader", path.and_then(|path| path.file()).map(Value::from).unwrap_or(Value::Null)),
+        ("device_path", path.map(|path| Value::from(path.as_str())).unwrap_or(Value::Null)),
+    ])
+}
+
+/// An entry as read again after a change to it
+fn reread(target: &BootTarget) -> Value {
+    let targets = get_boot_targets();
+    match targets.by_id(target.id) {
+        Some(target) => entry(&targets, target),
+        None => Value::Null,
+    }
+}
+
+fn list(targets: &BootTargets) -> Value {
+    Value::object([
+        ("current", id_value(targets.current)),
+        ("next", id_value(targets.next)),
+        ("order", Value::Array(targets.order.iter().map(|id| Value::from(format_id(*id))).collect())),
+        ("entries", Value::Array(targets.targets.iter().map(|target| entry(targets, target)).collect())),
+    ])
+}
+
+/// The entry named by the "dest" parameter
+fn target<'a>(targets: &'a BootTargets, params: Option<&Value>) -> Result<&'a BootTarget, Error> {
+    let dest = params.and_then(|params| params.get("dest")).and_then(Value::as_str)
+        .ok_or(Error::new(INVALID_PARAMS, tr!("\"dest\" should be the ID or name of an entry")))?;
+    targets.lookup(dest).map_err(|e| Error::new(e.exit() as i64, e.to_string()))
+}
+
+/// Whether a parameter like "force" is true
+fn flag(params: Option<&Value>, name: &str) -> bool {
+    params.and_then(|params| params.get(name)) == Some(&Value::Bool(true))
+}
+
+/// What is left to confirm before using the target, if anything. `force`
+/// confirms the `checks`, but only `confirm_protected` a protected entry.
+fn confirmation(target: &BootTarget, params: Option<&Value>, config: &Config, checks: impl FnOnce() -> Vec<String>) -> Result<(), Error> {
+    let protected = config.is_protected(target) && !flag(params, "confirm_protected");
+    let mut reasons: Vec<String> = protected.then(|| protected_warning(target)).into_iter().collect();
+    if !flag(params, "force") {
+        reasons.extend(checks());
+    }
+
+    match reasons.is_empty() {
+        true => Ok(()),
+        false => Err(Error::unconfirmed(reasons, protected)),
+    }
+}
+
+fn set_next(params: Option<&Value>, config: &Config) -> Result<Value, Error> {
+    let mut targets = get_boot_targets();
+    verify::verify_targets(&mut targets, false);
+    let target = target(&targets, params)?;
+
+    confirmation(target, params, config, || safety::loader_warning(target).into_iter().collect())?;
+
+    let result = try_set_next_boot(target);
+    journal::record_result(journal::Action::SetNext, target, &result);
+    result.map_err(|e| Error::command(e, Exit::EfibootmgrFailed))?;
+    Ok(reread(target))
+}
+
+fn reboot(params: Option<&Value>, config: &Config) -> Result<Value, Error> {
+    let mut targets = get_boot_targets();
+    verify::verify_targets(&mut targets, false);
+    let target = target(&targets, params)?;
+
+    confirmation(target, params, config, || {
+        safety::reboot_blockers().into_iter().chain(safety::loader_warning(target)).chain(safety::reboot_warnings()).collect()
+    })?;
+
+    if let Err(e) = try_set_next_boot(target) {
+        journal::record(journal::Action::Reboot, target, journal::Outcome::Failure(&e.message));
+        return Err(Error::command(e, Exit::EfibootmgrFailed));
+    }
+    history::record(target);
+    let result = try_shutdown();
+    journal::record_result(journal::Action::Reboot, target, &result);
+    result.map_err(|e| Error::command(e, Exit::RebootFailed))?;
+    Ok(reread(target))
+}
+
+/// Held while a method runs. The daemon answers connections in parallel,
+/// but changes to the boot variables are made one at a time.
+static CALLING: Mutex<()> = Mutex::new(());
+
+/// Run a method
+fn call(method: &str, params: Option<&Value>, config: &Config) -> Result<Value, Error> {
+    let _calling = CALLING.lock().expect("call lock should not be poisoned");
+    match method {
+        "list" => Ok(list(&get_boot_targets())),
+        "resolve" => {
+            let targets = get_boot_targets();
+            target(&targets, params).map(|target| entry(&targets, target))
+        }
+        "set_next" => set_next(params, config),
+        "reboot" => reboot(params, config),
+        "clear" => try_clear_next_boot().map(|()| Value::Null).map_err(|e| Error::command(e, Exit::EfibootmgrFailed)),
+        _ => Err(Error::new(METHOD_NOT_FOUND, tr!("Unknown method \"{}\"", method))),
+    }
+}
+
+fn response(id: Value, outcome: Result<Value, Error>) -> String {
+    let (key, value) = match outcome {
+        Ok(result) => ("result", result),
+        Err(error) => ("error", error.to_value()),
+    };
+    Value::object([("jsonrpc", "2.0".into()), ("id", id), (key, value)]).to_compact()
+}
+
+/// Answer a line with a request, or None for a notification, which has no id
+pub fn respond(line: &str, config: &Config) -> Option<String> {
+    let request = match json::parse(line) {
+        Ok(request) => request,
+        Err(e) => return Some(response(Value::Null, Err(Error::new(PARSE_ERROR, e)))),
+    };
+    let id = request.get("id").cloned();
+    let method = match (&request, request.get("method").and_then(Value::as_str)) {
+        (Value::Object(_), Some(method)) => method,
+        _ => return Some(response(id.unwrap_or(Value::Null), Err(Error::new(INVALID_REQUEST, tr!("A request should be an object with a \"method\"")))))
+    };
+
+    let outcome = call(method, request.get("params"), config);
+    if let Err(e) = &outcome {
+        log::info!("{} failed: {}", method, e.message);
+    }
+    id.map(|id| response(id, outcome))
+}
+
+/// Longest request read, so that a client can't take up all memory
+const MAX_REQUEST: u64 = 1 << 20;
+
+/// Skip the rest of a line that is too long
+fn skip_line(input: &mut impl BufRead) -> io::Result<()> {
+    loop {
+        let buffer = input.fill_buf()?;
+        let (consumed, end) = match buffer.iter().position(|byte| *byte == b'\n') {
+            Some(newline) => (newline + 1, true),
+            None => (buffer.len(), buffer.is_empty()),
+        };
+        input.consume(consumed);
+        if end {
+            return Ok(());
+        }
+    }
+}
+
+/// Answer requests, one per line, until the input ends
+pub fn serve(mut input: impl BufRead, mut output: impl Write, config: &Config) -> io::Result<()> {
+    loop {
+        let mut line = vec![];
+        if Read::take(&mut input, MAX_REQUEST).read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+
+        let response = if line.len() as u64 == MAX_REQUEST && !line.ends_with(b"\n") {
+            skip_line(&mut input)?;
+            Some(response(Value::Null, Err(Error::new(INVALID_REQUEST, tr!("Requests can be at most {} bytes long", MAX_REQUEST)))))
+        } else {
+            match String::from_utf8(line) {
+                Ok(line) if line.trim().is_empty() => None,
+                Ok(line) => respond(&line, config),
+                Err(_) => Some(response(Value::Null, Err(Error::new(PARSE_ERROR, tr!("Requests should be UTF-8"))))),
+            }
+        };
+        if let Some(response) = response {
+            writeln!(output, "{}", response)?;
+            output.flush()?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_boot_targets;
+
+    #[test]
+    fn answers_requests() {
+        let config = Config::default();
+        assert_eq!(respond("{\"jsonrpc\": \"2.0\", \"id\": 1, \"method\": \"shutdown\"}", &config).as_deref(),
+            Some("{\"error\":{\"code\":-32601,\"message\":\"Unknown method \\\"shutdown\\\"\"},\"id\":1,\"jsonrpc\":\"2.0\"}"));
+        assert!(respond("{\"jsonrpc\": \"2.0\", \"method\": \"shutdown\"}", &config).is_none());
+        assert!(respond("[1, 2]", &config).is_some_and(|response| response.contains("-32600")));
+        assert!(respond("{\"id\": ", &config).is_some_and(|response| response.contains("-32700")));
+
+        let targets = parse_boot_targets("BootCurrent: 0001\nBootNext: 0002\nBootOrder: 0001,0002\n\
+            Boot0001* ubuntu\tHD(1,GPT,0f1c2d3e-4b5a-6978-8a9b-0c1d2e3f4a5b,0x800,0x100000)/File(\\EFI\\ubuntu\\shimx64.efi)\n\
+            Boot0002  Windows Boot Manager\tHD(1,GPT,0f1c2d3e-4b5a-6978-8a9b-0c1d2e3f4a5b,0x800,0x100000)/File(\\EFI\\Microsoft\\Boot\\bootmgfw.efi)\n");
+        let listed = list(&targets);
+        assert_eq!(listed.get("next").and_then(Value::as_str), Some("0002"));
+        let entries = listed.get("entries").and_then(Value::as_array).expect("entries should be listed");
+        assert_eq!(entries[1].get("name").and_then(Value::as_str), Some("Windows Boot Manager"));
+        assert_eq!(entries[1].get("active"), Some(&Value::Bool(false)));
+        assert_eq!(entries[0].get("loader").and_then(Value::as_str), Some("\\EFI\\ubuntu\\shimx64.efi"));
+
+        let params = Value::object([("dest", "Windows".into())]);
+        assert_eq!(target(&targets, Some(&params)).map(|target| target.id), Ok(0x0002));
+        assert_eq!(target(&targets, None).map(|target| target.id).map_err(|e| e.code), Err(INVALID_PARAMS));
+    }
+
+    #[test]
+    fn limits_request_length() {
+        let mut input = vec![b'['; MAX_REQUEST as usize + 10];
+        input.extend(b"\n\xff\n\n{\"jsonrpc\": \"2.0\", \"id\": 2, \"method\": \"nothing\"}\n");
+        let mut output = vec![];
+        serve(io::Cursor::new(input), &mut output, &Config::default()).expect("serving from memory should not fail");
+
+        let output = String::from_utf8(output).expect("responses should be UTF-8");
+        let responses: Vec<&str> = output.lines().collect();
+        assert_eq!(responses.len(), 3);
+        assert!(responses[0].contains("-32600"));
+        assert!(responses[1].contains("-32700"));
+        assert!(responses[2].contains("-32601") && responses[2].contains("\"id\":2"));
+    }
+
+    #[test]
+    fn asks_for_protected_entries_even_when_forced() {
+        let targets = parse_boot_targets("BootCurrent: 0001\nBootOrder: 0001,0002\nBoot0001* ubuntu\tHD(1)\nBoot0002* Windows Boot Manager\tHD(1)\n");
+        let config = Config { protected: vec![String::from("Windows Boot Manager")], ..Config::default() };
+        let windows = &targets.targets[1];
+        let checks = || vec![String::from("Other users are logged in:")];
+        let reasons = |outcome: Result<(), Error>| outcome.map_err(|e| (e.code, e.data.and_then(|data| data.get("reasons").cloned())));
+        let params = |force: bool, confirm_protected: bool| Value::object([("force", force.into()), ("confirm_protected", confirm_protected.into())]);
+
+        assert_eq!(reasons(confirmation(&targets.targets[0], None, &config, checks)),
+            Err((Exit::Cancelled as i64, Some(Value::Array(vec!["Other users are logged in:".into()])))));
+        assert_eq!(confirmation(&targets.targets[0], Some(&params(true, false)), &config, checks), Ok(()));
+        assert_eq!(reasons(confirmation(windows, Some(&params(true, false)), &config, checks)),
+            Err((Exit::Cancelled as i64, Some(Value::Array(vec!["\"Windows Boot Manager\" is a protected entry".into()])))));
+        assert_eq!(confirmation(windows, Some(&params(false, true)), &config, checks).map_err(|e| e.data.and_then(|data| data.get("protected").cloned())),
+            Err(Some(Value::Bool(false))));
+        assert_eq!(confirmation(windows, Some(&params(true, true)), &config, checks), Ok(()));
+    }
+}
@@ -0,0 +1,175 @@
+/***
+ * Pre-reboot safety checks
+ *
+ * Collects reasons to think twice before rebooting, such as other users being
+ * logged in, a hibernated system on this machine or a loader Secure Boot will
+ * refuse, and reasons not to reboot at all, such as a running package manager.
+ ***/
+use std::{fs, path::Path, process::Command};
+
+use crate::{hibernation, i18n::tr, process, verify::LoaderStatus, BootTarget};
+
+/// A login session of another user
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub user: String,
+    pub line: String,
+    pub host: Option<String>,
+}
+
+impl Session {
+    pub fn describe(&self) -> String {
+        match &self.host {
+            Some(host) => tr!("{} on {} (remote, from {})", self.user, self.line, host),
+            None => tr!("{} on {}", self.user, self.line),
+        }
+    }
+}
+
+/// Terminal line of this process, e.g. `pts/3`
+fn own_line() -> Option<String> {
+    let tty = fs::read_link("/proc/self/fd/0").ok()?;
+    Some(tty.to_string_lossy().trim_start_matches("/dev/").to_string())
+}
+
+/// Parse the output of `who`, e.g. `alice    pts/1        2024-08-01 10:00 (10.0.0.5)`
+fn parse_who(raw: &str) -> Vec<Session> {
+    raw.lines().filter_map(|line| {
+        let mut fields = line.split_whitespace();
+        let user = fields.next()?.to_string();
+        let line_name = fields.next()?.to_string();
+        let host = line.rfind('(')
+            .and_then(|start| line[start + 1..].strip_suffix(')'))
+            .filter(|host| !host.is_empty() && !host.starts_with(':')) // Local X displays
+            .map(String::from);
+
+        Some(Session { user, line: line_name, host })
+    }).collect()
+}
+
+/// Sessions other than our own, according to `who`
+pub fn other_sessions() -> Vec<Session> {
+    let output = match process::output(&mut Command::new("who"), None) {
+        Ok(output) if output.status.success() => output,
+        _ => return vec![],
+    };
+
+    let own = own_line();
+    parse_who(&String::from_utf8_lossy(&output.stdout))
+        .into_iter()
+        .filter(|session| own.as_deref() != Some(session.line.as_str()))
+        .collect()
+}
+
+/// Warnings to confirm before rebooting, one line each. Empty when there is
+/// nothing to worry about.
+pub fn reboot_warnings() -> Vec<String> {
+    let mut warnings = vec![];
+
+    let sessions = other_sessions();
+    if !sessions.is_empty() {
+        warnings.push(String::from(tr!("Other users are logged in:")));
+        warnings.extend(sessions.iter().map(|session| format!("  {}", session.describe())));
+    }
+
+    let hiberfiles = hibernation::windows_hiberfiles();
+    if !hiberfiles.is_empty() {
+        warnings.push(String::from(tr!("Windows is hibernated, and its files may be lost if another system changes them:")));
+        warnings.extend(hiberfiles.iter().map(|path| format!("  {}", path.display())));
+    }
+    let images = hibernation::linux_images();
+    if !images.is_empty() {
+        warnings.push(String::from(tr!("A hibernated Linux system may lose its state if another system mounts its filesystems or uses its swap:")));
+        warnings.extend(images.iter().map(|path| format!("  {}", path.display())));
+    }
+
+    warnings
+}
+
+/// Warning for an entry the firmware is unlikely to start, see `verify`
+pub fn loader_warning(target: &BootTarget) -> Option<String> {
+    match target.loader {
+        LoaderStatus::Unsigned => Some(tr!("\"{}\" has an unsigned loader, which Secure Boot will likely refuse to start", target.name)),
+        LoaderStatus::NoDisk => Some(missing_disk_warning(target)),
+        _ => None,
+    }
+}
+
+/// Warning for an entry on a disk that is not present. The firmware skips
+/// such an entry without a word and boots the next one in BootOrder.
+pub fn missing_disk_warning(target: &BootTarget) -> String {
+    tr!("\"{}\" is on a disk that is not present, the firmware will skip it and boot the next entry in BootOrder instead", target.name)
+}
+
+/// Process names of package managers and firmware updaters, as found in
+/// /proc/<pid>/comm (truncated to 15 characters)
+const PACKAGE_MANAGERS: [&str; 13] = [
+    "pacman", "dpkg", "apt", "apt-get", "aptitude", "unattended-upgr", "dnf", "dnf5",
+    "yum", "rpm", "zypper", "fwupdmgr", "dnf-automatic",
+];
+
+/// Lock files that only exist while a package manager is running
+const PACKAGE_LOCKS: [&str; 1] = ["/var/lib/pacman/db.lck"];
+
+/// Lock files that always exist, and are locked with fcntl(2) while dpkg or
+/// rpm, and with it apt, dnf or zypper, is running. The rpm database moved
+/// to /usr/lib/sysimage on newer systems.
+const HELD_LOCKS: [&str; 4] = [
+    "/var/lib/dpkg/lock-frontend",
+    "/var/lib/dpkg/lock",
+    "/var/lib/rpm/.rpm.lock",
+    "/usr/lib/sysimage/rpm/.rpm.lock",
+];
+
+/// Process holding a lock on the file, if any
+#[cfg(target_os = "linux")]
+fn lock_holder(path: &str) -> Option<i32> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = fs::File::open(path).ok()?;
+    // SAFETY: flock is plain data, for which all zeros is valid
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = libc::F_WRLCK as libc::c_short;
+    lock.l_whence = libc::SEEK_SET as libc::c_short;
+    // SAFETY: only asks about locks on a file this process has open, filling in `lock`
+    let result = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETLK, &mut lock) };
+    (result == 0 && lock.l_type != libc::F_UNLCK as libc::c_short).then_some(lock.l_pid)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn lock_holder(_path: &str) -> Option<i32> {
+    None
+}
+
+/// Running package manager processes, as "name (pid N)"
+fn package_manager_processes() -> Vec<String> {
+    fs::read_dir("/proc").into_iter().flatten()
+        .filter_map(|e| e.ok())
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_string_lossy().parse::<u32>().ok()?;
+            let comm = fs::read_to_string(entry.path().join("comm")).ok()?;
+            let comm = comm.trim();
+
+            PACKAGE_MANAGERS.contains(&comm).then(|| format!("{} (pid {})", comm, pid))
+        })
+        .collect()
+}
+
+/// Reasons not to reboot at all, unless forced. Empty when rebooting is safe.
+pub fn reboot_blockers() -> Vec<String> {
+    let mut blockers = vec![];
+
+    let processes = package_manager_processes();
+    if !processes.is_empty() {
+        blockers.push(tr!("A package manager or firmware updater is running: {}", processes.join(", ")));
+    }
+
+    for lock in PACKAGE_LOCKS.iter().filter(|lock| Path::new(lock).exists()) {
+        blockers.push(tr!("Package manager lock file {} exists, an update may be in progress", lock));
+    }
+    for (lock, pid) in HELD_LOCKS.iter().filter_map(|lock| Some((lock, lock_holder(lock)?))) {
+        blockers.push(tr!("Package manager lock {} is held by pid {}, an update may be in progress", lock, pid));
+    }
+
+    blockers
+}
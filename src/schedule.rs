@@ -0,0 +1,292 @@
+/***
+ * Scheduled reboots
+ *
+ * `--at` leaves the reboot to a transient systemd timer, created with
+ * systemd-run, which runs `reboot-to --unattended --reboot-to <ID>` at the
+ * given time as a service of the system manager, so that it happens even
+ * when the terminal has been closed or the user logged out. Nobody may be
+ * there to answer questions by then, so what there is to confirm about the
+ * entry, a missing disk or its protection, is asked when scheduling. A
+ * package manager or firmware update running when the reboot is due still
+ * stops it. Pending reboots are the timers named `reboot-to-at-*`, which
+ * `reboot-to jobs` lists and cancels.
+ *
+ * Recurring reboots, like every Sunday at 03:00 for a Windows that has to
+ * install its updates, run the same command from timer and service units
+ * installed to /etc/systemd/system as `reboot-to-schedule-<n>`, which stay
+ * until removed with `reboot-to schedule remove`. As firmware and Windows
+ * updates may recreate an entry under another ID, they pass its name along
+ * with `--expect-name`, and reboot to the one entry with exactly that name
+ * once the ID has another or none. The name is kept in the timer as
+ * `X-Reboot-To-Entry` for listing.
+ ***/
+use std::{env, fs, io, path::{Path, PathBuf}, process::Command, time::{SystemTime, UNIX_EPOCH}};
+
+use crate::{
+    delay, demo, efibootmgr::{self, format_id, CommandError}, i18n::tr, process::{self, find_program}, sudo, tempdir, BootTarget,
+};
+
+/// Start of the names of the units of reboots scheduled with --at
+const AT_PREFIX: &str = "reboot-to-at-";
+
+/// Directory the units of recurring reboots are written to
+const UNIT_DIR: &str = "/etc/systemd/system";
+
+/// Start of the names of the units of recurring reboots
+const SCHEDULE_PREFIX: &str = "reboot-to-schedule-";
+
+/// Key in the timer of a recurring reboot with the name of the entry
+const ENTRY_KEY: &str = "X-Reboot-To-Entry";
+
+/// A pending scheduled reboot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    /// Name of its timer, without ".timer"
+    pub name: String,
+    /// Like "Reboot to \"Windows Boot Manager\""
+    pub description: String,
+    /// When the timer elapses, as systemctl shows it
+    pub next: String,
+}
+
+fn failure(message: String) -> CommandError {
+    CommandError { message, permission_denied: false }
+}
+
+/// The systemd-run arguments for when the timer elapses: a delay like "30m"
+/// counts from now, anything else is a calendar time like "03:00"
+fn timer_arguments(when: &str) -> Vec<String> {
+    let trigger = match delay::parse(when) {
+        Ok(delay) => format!("--on-active={}s", delay.as_secs()),
+        Err(_) => format!("--on-calendar={}", when),
+    };
+    // Timers are only accurate to a minute otherwise
+    vec![trigger, String::from("--timer-property=AccuracySec=1s")]
+}
+
+/// Schedule a reboot to the target at `when`, returning the name of the job.
+/// A protected entry is only scheduled once confirmed, so the reboot won't ask again.
+pub fn at(target: &BootTarget, when: &str, protected: bool) -> Result<String, CommandError> {
+    if find_program("systemd-run").is_none() {
+        return Err(failure(String::from(tr!("Scheduling reboots needs systemd, systemd-run was not found"))));
+    }
+    let program = env::current_exe().map_err(|e| failure(tr!("Could not find reboot-to itself: {}", e)))?;
+
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default();
+    let name = format!("{}{}-{}", AT_PREFIX, format_id(target.id), seconds);
+    let mut command = Command::new("systemd-run");
+    command.arg(format!("--unit={}", name))
+        .arg(format!("--description={}", tr!("Reboot to \"{}\"", target.name)))
+        .args(timer_arguments(when));
+    if protected {
+        command.arg("--setenv=REBOOT_TO_LIST_PROTECTED=[]");
+    }
+    command.arg("--").arg(program).args(["--unattended", "--reboot-to", &format_id(target.id)]);
+
+    efibootmgr::check_command("systemd-run", sudo::run(&mut command))
+        .map_err(|e| e.context(tr!("Could not schedule the reboot")))?;
+    Ok(name)
+}
+
+/// Parse the output of `systemctl show` for timers, blocks of "Key=value"
+/// lines separated by empty lines
+fn parse_jobs(text: &str) -> Vec<Job> {
+    text.split("\n\n").filter_map(|block| {
+        let get = |key: &str| block.lines().find_map(|line| line.strip_prefix(key)?.strip_prefix('=')).unwrap_or_default();
+        let name = get("Id").strip_suffix(".timer")?.to_string();
+        Some(Job { name, description: get("Description").to_string(), next: get("NextElapseUSecRealtime").to_string() })
+    }).collect()
+}
+
+/// Reboots scheduled with --at that have yet to happen
+pub fn jobs() -> Result<Vec<Job>, CommandError> {
+    if find_program("systemctl").is_none() {
+        return Ok(vec![]);
+    }
+
+    let output = sudo::run(Command::new("systemctl")
+        .args(["show", "--property=Id,Description,NextElapseUSecRealtime"])
+        .arg(format!("{}*.timer", AT_PREFIX)));
+    let output = output.map_err(|e| failure(tr!("Could not run systemctl: {}", e)))?;
+    if !output.status.success() {
+        return Err(failure(tr!("Could not list scheduled reboots: {}", String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    // Elapsed timers stay around until their service is done
+    Ok(parse_jobs(&String::from_utf8_lossy(&output.stdout)).into_iter().filter(|job| !job.next.is_empty()).collect())
+}
+
+/// Cancel a scheduled reboot by the name `jobs` lists
+pub fn cancel(name: &str) -> Result<(), CommandError> {
+    let name = name.strip_suffix(".timer").unwrap_or(name);
+    if !name.starts_with(AT_PREFIX) {
+        return Err(failure(tr!("\"{}\" is not a scheduled reboot, see reboot-to jobs", name)));
+    }
+
+    efibootmgr::check_command("systemctl", sudo::run(Command::new("systemctl").arg("stop").arg(format!("{}.timer", name))))
+        .map_err(|e| e.context(&tr!("Could not cancel {}", name)))
+}
+
+/// A recurring reboot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    /// Name of its units, without ".timer" or ".service"
+    pub name: String,
+    /// systemd calendar event, like "Sun 03:00"
+    pub calendar: String,
+    /// Name of the entry to reboot to
+    pub entry: String,
+}
+
+/// A command line argument as written in ExecStart, quoted and with the
+/// characters systemd would expand escaped
+fn quote_argument(argument: &str) -> String {
+    format!("\"{}\"", argument.replace('\\', "\\\\").replace('"', "\\\"").replace('%', "%%").replace('$', "$$"))
+}
+
+/// Contents of the timer and service units of a recurring reboot
+fn units(schedule: &Schedule, id: u16, program: &Path, protected: bool) -> (String, String) {
+    let description = tr!("Reboot to \"{}\"", schedule.entry).replace('%', "%%");
+    // Not Persistent, a reboot missed while the machine was off would happen right after booting
+    let timer = format!("# Written by reboot-to, remove with: reboot-to schedule remove {}\n\
+        [Unit]\nDescription={} ({})\n{}={}\n\n\
+        [Timer]\nOnCalendar={}\nAccuracySec=1s\n\n\
+        [Install]\nWantedBy=timers.target\n",
+        schedule.name, description, schedule.calendar.replace('%', "%%"), ENTRY_KEY, schedule.entry, schedule.calendar);
+
+    // A protected entry was confirmed when scheduling
+    let environment = if protected { "Environment=REBOOT_TO_LIST_PROTECTED=[]\n" } else { "" };
+    let service = format!("# Written by reboot-to, remove with: reboot-to schedule remove {}\n\
+        [Unit]\nDescription={}\n\n\
+        [Service]\nType=oneshot\n{}ExecStart={} --unattended --reboot-to {} --expect-name {}\n",
+        schedule.name, description, environment, quote_argument(&program.to_string_lossy()), format_id(id), quote_argument(&schedule.entry));
+
+    (timer, service)
+}
+
+/// Read the schedule back from its timer unit
+fn parse_timer(name: &str, text: &str) -> Option<Schedule> {
+    let get = |key: &str| text.lines().find_map(|line| line.strip_prefix(key)?.strip_prefix('=')).map(String::from);
+    Some(Schedule { name: name.to_string(), calendar: get("OnCalendar")?, entry: get(ENTRY_KEY)? })
+}
+
+fn unit_path(name: &str, kind: &str) -> PathBuf {
+    Path::new(UNIT_DIR).join(format!("{}.{}", name, kind))
+}
+
+fn write_failure(path: &Path, e: io::Error) -> CommandError {
+    CommandError { message: tr!("Could not write {}: {}", path.display(), e), permission_denied: e.kind() == io::ErrorKind::PermissionDenied }
+}
+
+/// Write a unit through `install`, which like systemctl runs through sudo
+/// when a password was entered, from a private copy made first
+fn install_unit(path: &Path, contents: &str) -> Result<(), CommandError> {
+    let dir = tempdir::create("unit").map_err(|e| write_failure(path, e))?;
+    let staged = dir.join("unit");
+    let result = fs::write(&staged, contents).map_err(|e| write_failure(&staged, e))
+        .and_then(|()| efibootmgr::check_command("install", sudo::run(Command::new("install").args(["-m", "0644", "--"]).arg(&staged).arg(path))));
+    let _ = fs::remove_dir_all(&dir);
+
+    result.map_err(|e| e.context(&tr!("Could not write {}", path.display())))
+}
+
+/// Recurring reboots, by name
+pub fn schedules() -> Vec<Schedule> {
+    let Ok(entries) = fs::read_dir(UNIT_DIR) else {
+        return vec![];
+    };
+
+    let mut schedules: Vec<Schedule> = entries.filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let name = file_name.strip_suffix(".timer").filter(|name| name.starts_with(SCHEDULE_PREFIX))?;
+            parse_timer(name, &fs::read_to_string(entry.path()).ok()?)
+        })
+        .collect();
+    schedules.sort_by_key(|schedule| (schedule.name.len(), schedule.name.clone()));
+    schedules
+}
+
+/// Check a calendar event with systemd-analyze, where it is installed
+fn check_calendar(calendar: &str) -> Result<(), CommandError> {
+    if find_program("systemd-analyze").is_none() {
+        return Ok(());
+    }
+    match process::output(Command::new("systemd-analyze").args(["calendar", calendar]), None) {
+        Ok(output) if !output.status.success() => Err(failure(tr!("\"{}\" is not a systemd calendar event, like \"Sun 03:00\" or \"*-*-01 04:00\"", calendar))),
+        _ => Ok(()),
+    }
+}
+
+/// Reboot to the target on a calendar schedule from now on, returning the
+/// name of the schedule. A protected entry is only scheduled once confirmed.
+pub fn add(target: &BootTarget, calendar: &str, protected: bool) -> Result<String, CommandError> {
+    if find_program("systemctl").is_none() {
+        return Err(failure(String::from(tr!("Scheduling reboots needs systemd, systemctl was not found"))));
+    }
+    check_calendar(calendar)?;
+    let program = env::current_exe().map_err(|e| failure(tr!("Could not find reboot-to itself: {}", e)))?;
+
+    let taken = schedules();
+    let number = (1..).find(|number| !taken.iter().any(|schedule| schedule.name == format!("{}{}", SCHEDULE_PREFIX, number))).unwrap_or_default();
+    let schedule = Schedule { name: format!("{}{}", SCHEDULE_PREFIX, number), calendar: calendar.to_string(), entry: target.name.clone() };
+    let (timer, service) = units(&schedule, target.id, &program, protected);
+
+    if !demo::is_enabled() {
+        for (path, contents) in [(unit_path(&schedule.name, "service"), service), (unit_path(&schedule.name, "timer"), timer)] {
+            install_unit(&path, &contents)?;
+        }
+    }
+    efibootmgr::check_command("systemctl", sudo::run(Command::new("systemctl").arg("daemon-reload")))?;
+    efibootmgr::check_command("systemctl", sudo::run(Command::new("systemctl").args(["enable", "--now"]).arg(format!("{}.timer", schedule.name))))
+        .map_err(|e| e.context(tr!("Could not start the timer")))?;
+    Ok(schedule.name)
+}
+
+/// Stop and delete a recurring reboot, by its name or number
+pub fn remove(name: &str) -> Result<Schedule, CommandError> {
+    let schedule = schedules().into_iter()
+        .find(|schedule| schedule.name == name || schedule.name.strip_prefix(SCHEDULE_PREFIX) == Some(name))
+        .ok_or(failure(tr!("No scheduled reboot named \"{}\", see reboot-to schedule list", name)))?;
+
+    efibootmgr::check_command("systemctl", sudo::run(Command::new("systemctl").args(["disable", "--now"]).arg(format!("{}.timer", schedule.name))))
+        .map_err(|e| e.context(tr!("Could not stop the timer")))?;
+    if !demo::is_enabled() {
+        for path in [unit_path(&schedule.name, "timer"), unit_path(&schedule.name, "service")] {
+            efibootmgr::check_command("rm", sudo::run(Command::new("rm").arg("--").arg(&path)))
+                .map_err(|e| e.context(&tr!("Could not remove {}", path.display())))?;
+        }
+    }
+    efibootmgr::check_command("systemctl", sudo::run(Command::new("systemctl").arg("daemon-reload")))?;
+    Ok(schedule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_scheduled_reboots() {
+        let shown = "Id=reboot-to-at-0000-1791000000.timer\nDescription=Reboot to \"Windows Boot Manager\"\n\
+            NextElapseUSecRealtime=Fri 2026-10-16 03:00:00 CEST\n\n\
+            Id=reboot-to-at-0004-1791000100.timer\nDescription=Reboot to \"Fedora\"\nNextElapseUSecRealtime=\n";
+        assert_eq!(parse_jobs(shown), [
+            Job { name: String::from("reboot-to-at-0000-1791000000"), description: String::from("Reboot to \"Windows Boot Manager\""), next: String::from("Fri 2026-10-16 03:00:00 CEST") },
+            Job { name: String::from("reboot-to-at-0004-1791000100"), description: String::from("Reboot to \"Fedora\""), next: String::new() },
+        ]);
+
+        assert_eq!(timer_arguments("30m")[0], "--on-active=1800s");
+        assert_eq!(timer_arguments("Sun 03:00")[0], "--on-calendar=Sun 03:00");
+    }
+
+    #[test]
+    fn writes_and_reads_recurring_reboots() {
+        let schedule = Schedule { name: String::from("reboot-to-schedule-1"), calendar: String::from("Sun 03:00"), entry: String::from("Windows \"100%\"") };
+        let (timer, service) = units(&schedule, 0x0002, Path::new("/usr/bin/reboot-to"), false);
+
+        assert!(timer.contains("\nOnCalendar=Sun 03:00\n"));
+        assert!(timer.contains("\nDescription=Reboot to \"Windows \"100%%\"\" (Sun 03:00)\n"));
+        assert!(service.contains("\nExecStart=\"/usr/bin/reboot-to\" --unattended --reboot-to 0002 --expect-name \"Windows \\\"100%%\\\"\"\n"));
+        assert!(!service.contains("REBOOT_TO_LIST_PROTECTED"));
+        assert_eq!(parse_timer("reboot-to-schedule-1", &timer), Some(schedule));
+    }
+}
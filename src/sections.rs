@@ -0,0 +1,102 @@
+/***
+ * Entry sections
+ *
+ * Sorts entries into operating systems, removable media, network boot and
+ * firmware tools by the nodes of their device paths, so that the TUI can
+ * list the systems first, under their own heading, instead of interleaved
+ * with "UEFI PXE IPv4 Intel(R) Ethernet..." entries the firmware adds for
+ * every network card. Entries without a device path count as systems, and
+ * ones on a disk that is removable, as seen by `verify`, as removable media.
+ ***/
+use crate::{i18n::tr, os::{self, Os}, BootTarget, BootTargets};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Section {
+    Systems,
+    Removable,
+    Network,
+    /// UEFI shell, firmware setup, diagnostics and the like
+    Firmware,
+}
+
+/// Device path nodes of network boot, like `MAC(...)/IPv4(...)`
+const NETWORK_NODES: [&str; 4] = ["MAC", "IPv4", "IPv6", "Uri"];
+
+/// Device path nodes of removable media
+const REMOVABLE_NODES: [&str; 5] = ["USB", "UsbClass", "UsbWwid", "CDROM", "SD"];
+
+/// Device path nodes of applications built into the firmware
+const FIRMWARE_NODES: [&str; 4] = ["FvVol", "FvFile", "Fv", "MemoryMapped"];
+
+impl Section {
+    /// Heading above the entries of the section
+    pub fn heading(self) -> &'static str {
+        match self {
+            Section::Systems => tr!("Operating systems"),
+            Section::Removable => tr!("Removable media"),
+            Section::Network => tr!("Network boot"),
+            Section::Firmware => tr!("Firmware tools"),
+        }
+    }
+}
+
+/// Section an entry belongs in
+pub fn classify(target: &BootTarget) -> Section {
+    let Some(device_path) = target.device_path.as_ref() else {
+        return Section::Systems;
+    };
+    let has = |kinds: &[&str]| device_path.nodes.iter().any(|node| kinds.contains(&node.kind.as_str()));
+
+    if has(&NETWORK_NODES) {
+        Section::Network
+    } else if has(&REMOVABLE_NODES) || target.removable {
+        Section::Removable
+    } else if has(&FIRMWARE_NODES) || os::detect(target) == Some(Os::Shell) {
+        Section::Firmware
+    } else {
+        Section::Systems
+    }
+}
+
+/// Put the entries in section order, keeping their order within each section
+pub fn sort(targets: &mut BootTargets) {
+    targets.targets.sort_by_key(classify);
+}
+
+/// Leave out the network boot entries
+pub fn hide_network(targets: &mut BootTargets) {
+    targets.targets.retain(|target| classify(target) != Section::Network);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_boot_targets;
+
+    #[test]
+    fn sorts_entries_into_sections() {
+        let mut targets = parse_boot_targets("BootCurrent: 0001\n\
+            Boot0000* UEFI PXE IPv4 Intel(R) Ethernet\tPciRoot(0x0)/Pci(0x1c,0x0)/MAC(001122334455,0)/IPv4(0.0.0.00.0.0.0,0,0)\n\
+            Boot0001* ubuntu\tHD(1,GPT,0f1c2d3e-4b5a-6978-8a9b-0c1d2e3f4a5b,0x800,0x100000)/File(\\EFI\\ubuntu\\shimx64.efi)\n\
+            Boot0002* EFI Shell\tFvVol(7cb8bdc9-f8eb-4f34-aaea-3ee4af6516a1)/FvFile(c57ad6b7-0515-40a8-9d21-551652854e37)\n\
+            Boot0003* UEFI: SanDisk\tPciRoot(0x0)/Pci(0x14,0x0)/USB(1,0)/HD(1,MBR,0x12345678,0x800,0x1000000)\n\
+            Boot0004* Windows Boot Manager\tHD(1,GPT,0f1c2d3e-4b5a-6978-8a9b-0c1d2e3f4a5b,0x800,0x100000)/File(\\EFI\\Microsoft\\Boot\\bootmgfw.efi)\n\
+            Boot0005* Fedora Live\tHD(1,GPT,7a6b5c4d-3e2f-4a1b-8c9d-0e1f2a3b4c5d,0x800,0x100000)/File(\\EFI\\BOOT\\BOOTX64.EFI)\n");
+        // On a USB disk the device path doesn't give away
+        targets.targets[5].removable = true;
+        sort(&mut targets);
+
+        let sections: Vec<(u16, Section)> = targets.targets.iter().map(|target| (target.id, classify(target))).collect();
+        assert_eq!(sections, [
+            (0x0001, Section::Systems),
+            (0x0004, Section::Systems),
+            (0x0003, Section::Removable),
+            (0x0005, Section::Removable),
+            (0x0000, Section::Network),
+            (0x0002, Section::Firmware),
+        ]);
+
+        hide_network(&mut targets);
+        assert_eq!(targets.targets.iter().map(|target| target.id).collect::<Vec<u16>>(), [0x0001, 0x0004, 0x0003, 0x0005, 0x0002]);
+    }
+}
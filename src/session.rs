@@ -0,0 +1,67 @@
+/***
+ * Desktop session
+ *
+ * With `end_session` set in `[reboot]`, the reboot is left to the desktop
+ * session when there is one: Plasma's ksmserver and GNOME's session manager
+ * ask every application to quit, so editors get to prompt about unsaved
+ * work, and reboot once the session is over. BootNext is set before, so the
+ * reboot still goes to the chosen entry. The request goes over the session
+ * bus with `dbus-send`, and without a desktop that takes it, the usual
+ * commands reboot instead.
+ ***/
+use std::{process::Command, sync::atomic::{AtomicBool, Ordering}};
+
+use crate::process::{self, find_program};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Service, object, method and arguments that end a session and reboot, tried in this order
+const REQUESTS: [(&str, &str, &str, &[&str]); 3] = [
+    // Plasma 5.25 and later
+    ("org.kde.Shutdown", "/Shutdown", "org.kde.Shutdown.logoutAndReboot", &[]),
+    // Earlier Plasma, without confirmation, rebooting once applications have agreed
+    ("org.kde.ksmserver", "/KSMServer", "org.kde.KSMServerInterface.logout", &["int32:0", "int32:1", "int32:1"]),
+    // GNOME shows its own restart dialog, listing applications with unsaved work
+    ("org.gnome.SessionManager", "/org/gnome/SessionManager", "org.gnome.SessionManager.Reboot", &[]),
+];
+
+/// Leave reboots to the desktop session from now on
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Ask the desktop session to end and reboot, returning whether one agreed
+/// to. Always false unless enabled.
+pub fn end_and_reboot() -> bool {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return false;
+    }
+    if find_program("dbus-send").is_none() {
+        log::info!("dbus-send not found, rebooting without ending the desktop session");
+        return false;
+    }
+
+    // Not through sudo, the session bus belongs to the user
+    let accepted = REQUESTS.iter().find(|(service, object, method, args)| {
+        let output = process::output(Command::new("dbus-send")
+            .args(["--session", "--print-reply", &format!("--dest={}", service), object, method])
+            .args(*args), None);
+        match output {
+            Ok(output) if output.status.success() => true,
+            Ok(output) => {
+                log::debug!("{} did not take the request: {}", service, String::from_utf8_lossy(&output.stderr).trim());
+                false
+            }
+            Err(e) => {
+                log::debug!("could not run dbus-send: {}", e);
+                false
+            }
+        }
+    });
+
+    match accepted {
+        Some((service, ..)) => log::info!("left the reboot to {}", service),
+        None => log::info!("no desktop session took the request to end, rebooting without it"),
+    }
+    accepted.is_some()
+}
@@ -0,0 +1,114 @@
+/***
+ * Privilege escalation
+ *
+ * Runs system commands through `sudo -S` once the user entered their password
+ * in the TUI, so an unprivileged session can retry failed writes. While the
+ * TUI shows them, the commands run and their output are also collected into a
+ * transcript.
+ ***/
+use std::{io::Result, process::{Command, Output}, sync::Mutex};
+
+use crate::process;
+
+/// Password entered in the TUI, if any
+static PASSWORD: Mutex<Option<String>> = Mutex::new(None);
+
+/// Lines of the commands run and their output not taken yet, when collected
+static TRANSCRIPT: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+pub fn set_password(password: Option<String>) {
+    *PASSWORD.lock().expect("sudo password lock should not be poisoned") = password;
+}
+
+pub fn has_password() -> bool {
+    PASSWORD.lock().expect("sudo password lock should not be poisoned").is_some()
+}
+
+/// Command line of a command, for logging
+pub fn command_line(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| arg.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Log the outcome of a command at debug level, including its stderr
+pub fn log_output(command: &Command, output: &Result<Output>) {
+    let program = command.get_program().to_string_lossy();
+    match output {
+        Ok(output) => {
+            log::debug!("{} exited with {}", program, output.status);
+            String::from_utf8_lossy(&output.stderr).lines()
+                .filter(|line| !line.trim().is_empty())
+                .for_each(|line| log::debug!("{} stderr: {}", program, line));
+        }
+        Err(e) => log::debug!("could not run {}: {}", program, e),
+    }
+}
+
+/// Start or stop collecting the commands run, see `transcript`
+pub fn collect_transcript(collect: bool) {
+    *TRANSCRIPT.lock().expect("transcript lock should not be poisoned") = if collect { Some(vec![]) } else { None };
+}
+
+/// Lines of the commands run and their output since the last call, while collected
+pub fn transcript() -> Vec<String> {
+    TRANSCRIPT.lock().expect("transcript lock should not be poisoned").as_mut().map(std::mem::take).unwrap_or_default()
+}
+
+fn add_to_transcript(lines: impl IntoIterator<Item = String>) {
+    if let Some(transcript) = TRANSCRIPT.lock().expect("transcript lock should not be poisoned").as_mut() {
+        transcript.extend(lines);
+    }
+}
+
+/// Output and exit status of a command, indented below its command line in the transcript
+fn transcript_output(output: &Result<Output>) -> Vec<String> {
+    match output {
+        Ok(output) => {
+            let text = [&output.stdout, &output.stderr].map(|bytes| String::from_utf8_lossy(bytes).into_owned());
+            text.iter().flat_map(|text| text.lines())
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| format!("  {}", line))
+                .chain([format!("  {}", output.status)])
+                .collect()
+        }
+        Err(e) => vec![format!("  {}", e)],
+    }
+}
+
+/// Run a command and capture its output. If a password was entered, the
+/// command is run through sudo, which reads the password from stdin.
+pub fn run(command: &mut Command) -> Result<Output> {
+    log::debug!("running {}", command_line(command));
+    add_to_transcript([format!("$ {}{}", if has_password() { "sudo " } else { "" }, command_line(command))]);
+    let output = run_captured(command);
+    log_output(command, &output);
+    add_to_transcript(transcript_output(&output));
+
+    output
+}
+
+fn run_captured(command: &mut Command) -> Result<Output> {
+    let password = PASSWORD.lock().expect("sudo password lock should not be poisoned").clone();
+
+    match password {
+        Some(password) => process::output(Command::new("sudo")
+            .args(["-S", "-p", "", "--"])
+            .arg(command.get_program())
+            .args(command.get_args()), Some(&password)),
+        None => process::output(command, None),
+    }
+}
+
+/// Whether a failed command's output indicates missing privileges, or a
+/// rejected sudo password
+pub fn is_permission_denied(output: &Output) -> bool {
+    let stderr = String::from_utf8_lossy(&output.stderr).to_lowercase();
+
+    ["permission denied", "operation not permitted", "access denied", "access is denied", "must be root",
+     "authentication required", "incorrect password", "sorry, try again"]
+        .iter()
+        .any(|needle| stderr.contains(needle))
+}
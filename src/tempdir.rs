@@ -0,0 +1,63 @@
+/***
+ * Private temporary directories
+ *
+ * Partitions mounted for a moment and files handed to efibootmgr are put,
+ * often as root, in a directory made fresh for them that only its owner can
+ * enter, like mkdtemp(3) does. Creating it fails if the name is taken, so a
+ * directory or symlink someone else left at the name is never used. Root
+ * gets them under /run, which other users cannot write to.
+ ***/
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Parent of the directories when it can be written to, as by root
+const RUN_DIR: &str = "/run";
+
+/// Directories created so far, to tell those of one process apart
+static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+/// Name for a directory, hard to guess ahead of time
+fn name(purpose: &str) -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.subsec_nanos()).unwrap_or_default();
+    format!("reboot-to-{}-{}-{}-{:08x}", purpose, process::id(), CREATED.fetch_add(1, Ordering::Relaxed), nanos)
+}
+
+#[cfg(unix)]
+fn create_private(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    fs::DirBuilder::new().mode(0o700).create(path)
+}
+
+#[cfg(not(unix))]
+fn create_private(path: &Path) -> io::Result<()> {
+    fs::DirBuilder::new().create(path)
+}
+
+/// Create a new directory only the current user can enter, which the caller
+/// removes once done with it
+pub fn create(purpose: &str) -> io::Result<PathBuf> {
+    let parent = if is_writable(RUN_DIR) { PathBuf::from(RUN_DIR) } else { env::temp_dir() };
+
+    let path = parent.join(name(purpose));
+    create_private(&path)?;
+    Ok(path)
+}
+
+#[cfg(target_os = "linux")]
+fn is_writable(dir: &str) -> bool {
+    let Ok(dir) = std::ffi::CString::new(dir) else {
+        return false;
+    };
+    // SAFETY: only checks access to a path given as a NUL-terminated string
+    unsafe { libc::access(dir.as_ptr(), libc::W_OK) == 0 }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_writable(_dir: &str) -> bool {
+    false
+}
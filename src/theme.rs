@@ -0,0 +1,89 @@
+/***
+ * Color themes
+ *
+ * Styles the TUI is drawn with. Besides the default there are presets for low
+ * vision, with white text and a bright yellow selection, and for red-green
+ * color blindness, which shows success and failure in blue and orange instead
+ * of green and red. The preset is chosen with `--theme`, or `theme` in the
+ * `[tui]` section of the configuration file.
+ ***/
+use clap::ValueEnum;
+use ratatui::style::{Color, Modifier, Style};
+
+/// Built-in themes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    /// Gray text with colored states
+    #[default]
+    Default,
+    /// White text, bold keys and a yellow selection, for low vision
+    HighContrast,
+    /// No red and green, for deuteranopia and protanopia
+    ColorBlind,
+}
+
+/// Styles of the parts of the TUI
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    /// Entries, borders and other text
+    pub text: Style,
+    /// Badges and placeholders that matter less
+    pub dim: Style,
+    /// Keys in the key hints
+    pub key: Style,
+    /// Selected entry or tab
+    pub selected: Style,
+    /// Pending BootNext, broken loaders and other things to look at
+    pub warning: Style,
+    pub error: Style,
+    pub success: Style,
+    /// Filled part of the countdown bar on the color of the rest
+    pub gauge: Style,
+}
+
+impl Preset {
+    /// Parse the `theme` setting, named like the `--theme` values
+    pub fn parse(text: &str) -> Option<Preset> {
+        Preset::from_str(text, true).ok()
+    }
+
+    pub fn theme(self) -> Theme {
+        let default = Theme {
+            text: Style::default().fg(Color::Gray),
+            dim: Style::default().fg(Color::DarkGray),
+            key: Style::default().fg(Color::Black).bg(Color::Gray).add_modifier(Modifier::BOLD),
+            selected: Style::default().add_modifier(Modifier::REVERSED),
+            warning: Style::default().fg(Color::Yellow),
+            error: Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            success: Style::default().fg(Color::Green),
+            gauge: Style::default().fg(Color::Yellow).bg(Color::DarkGray),
+        };
+
+        match self {
+            Preset::Default => default,
+            Preset::HighContrast => Theme {
+                text: Style::default().fg(Color::White),
+                dim: Style::default().fg(Color::Gray),
+                key: Style::default().fg(Color::Black).bg(Color::White).add_modifier(Modifier::BOLD),
+                selected: Style::default().fg(Color::Black).bg(Color::LightYellow).add_modifier(Modifier::BOLD),
+                warning: Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD),
+                error: Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                success: Style::default().fg(Color::LightCyan).add_modifier(Modifier::BOLD),
+                gauge: Style::default().fg(Color::LightYellow).bg(Color::Black),
+            },
+            Preset::ColorBlind => Theme {
+                selected: Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+                // Orange, from the 256 color palette
+                error: Style::default().fg(Color::Indexed(208)).add_modifier(Modifier::BOLD),
+                success: Style::default().fg(Color::LightBlue),
+                ..default
+            },
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Preset::Default.theme()
+    }
+}
@@ -0,0 +1,1237 @@
+/***
+ * TUI view
+ *
+ * Turns the TUI state into widgets and lays them out on a frame. Nothing here
+ * reads input or runs commands, so the layout can be rendered to a test
+ * backend. A header always shows the pending BootNext, and pending firmware
+ * updates and the firmware if there is room. Besides the entries, tabs show
+ * the BootOrder being edited, the settings, and a log of the actions run.
+ * Below them a pane can show the commands run and their output.
+ ***/
+use std::{borrow::Cow, time::Duration};
+
+use ratatui::{
+    layout::{Alignment, Constraint, Layout, Margin, Rect},
+    style::{Modifier, Stylize},
+    text::{Line, Span},
+    widgets::{block::{Position, Title}, Block, Clear, Gauge, List, ListDirection, ListState, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, Tabs},
+    Frame,
+};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::{duplicates::{self, Group}, efibootmgr::format_id, efivar::SecureBoot, i18n::tr, keys::{Action, KeyMap}, os::{self, Icons}, sections::{self, Section}, theme::Theme, BackgroundAction, BootTarget, BootTargets, ConfirmPrompt, DeletePrompt, OrderEditor, PasswordPrompt, PinPrompt, SettingsEditor, StatusLine};
+
+/// Smallest terminal that fits anything more than a message saying it is too small
+const MIN_WIDTH: u16 = 10;
+const MIN_HEIGHT: u16 = 2;
+
+/// Up to this size the list is drawn without borders and with fewer key hints
+const COMPACT_WIDTH: u16 = 20;
+const COMPACT_HEIGHT: u16 = 5;
+
+/// Rows of the pane with the commands run, including its border
+const COMMANDS_HEIGHT: u16 = 8;
+
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// How often the spinner and the countdown are redrawn
+pub const FRAME_INTERVAL: Duration = Duration::from_millis(80);
+
+/// Views of the TUI, switched with Tab or their number
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tab {
+    Entries,
+    Order,
+    Settings,
+    Log,
+}
+
+impl Tab {
+    pub const ALL: [Tab; 4] = [Tab::Entries, Tab::Order, Tab::Settings, Tab::Log];
+
+    fn title(self) -> &'static str {
+        match self {
+            Tab::Entries => tr!("Entries"),
+            Tab::Order => tr!("Order"),
+            Tab::Settings => tr!("Settings"),
+            Tab::Log => tr!("Log"),
+        }
+    }
+
+    fn index(self) -> usize {
+        Tab::ALL.iter().position(|tab| *tab == self).unwrap_or_default()
+    }
+
+    /// The tab to the right, wrapping around
+    pub fn next(self) -> Tab {
+        Tab::ALL[(self.index() + 1) % Tab::ALL.len()]
+    }
+
+    /// The tab to the left, wrapping around
+    pub fn previous(self) -> Tab {
+        Tab::ALL[(self.index() + Tab::ALL.len() - 1) % Tab::ALL.len()]
+    }
+}
+
+/// Time left before the TUI reboots by itself
+pub struct Countdown {
+    /// Name of the entry rebooted to
+    pub name: String,
+    pub left: Duration,
+    pub total: Duration,
+}
+
+/// Everything shown by the TUI
+pub struct View<'a> {
+    pub targets: &'a BootTargets,
+    /// Sets of duplicate entries, see `duplicates`
+    pub duplicates: &'a [Group],
+    pub keys: &'a KeyMap,
+    /// Title above the list, with spaces around it
+    pub title: &'a str,
+    /// Firmware vendor and version, see `firmware`
+    pub firmware: Option<&'a str>,
+    pub secure_boot: Option<SecureBoot>,
+    /// Devices whose firmware is updated on the next reboot, see `fwupd`
+    pub firmware_updates: &'a [String],
+    /// Device and mount point of the EFI System Partition, see `esp`
+    pub esp: Option<&'a str>,
+    /// Icons in front of the entries, see `os`
+    pub icons: Icons,
+    pub theme: Theme,
+    /// IDs of the entries rebooted to most recently, see `history`
+    pub recent: &'a [u16],
+    /// Draw a window fitting the entries in the middle of the terminal, instead of filling it
+    pub floating: bool,
+    pub tab: Tab,
+    pub order: &'a OrderEditor,
+    pub settings: &'a SettingsEditor,
+    /// Actions run so far, oldest first
+    pub log: &'a [String],
+    /// Commands run and their output, see `sudo::transcript`
+    pub commands: &'a [String],
+    pub show_commands: bool,
+    /// Network boot entries are left out of the list
+    pub network_hidden: bool,
+    pub status_line: Option<&'a StatusLine>,
+    /// Shown instead of the status line while counting down to a reboot
+    pub countdown: Option<Countdown>,
+    pub delete_prompt: Option<&'a DeletePrompt>,
+    pub confirm_prompt: Option<&'a ConfirmPrompt>,
+    pub password_prompt: Option<&'a PasswordPrompt>,
+    pub pin_prompt: Option<&'a PinPrompt>,
+    /// Text next to the spinner while a backend command runs
+    pub spinner: Option<String>,
+}
+
+/// Spinner text for an action that has been running for `elapsed`
+pub fn spinner_text(description: &str, elapsed: Duration) -> String {
+    let frame_index = (elapsed.as_millis() / FRAME_INTERVAL.as_millis()) as usize % SPINNER.len();
+    format!("{} {}", SPINNER[frame_index], tr!("{}...", description))
+}
+
+/// Rectangle of the given size centered in `area`, clamped to fit
+pub fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+
+    Rect::new(area.x + (area.width - width) / 2, area.y + (area.height - height) / 2, width, height)
+}
+
+/// Bottom title listing keys and what they do
+fn key_hints(hints: &[(&str, &str)], theme: &Theme) -> Title<'static> {
+    let mut spans = vec![Span::from(" ")];
+    for (key, action) in hints {
+        spans.push(Span::styled(key.to_string(), theme.key));
+        spans.push(Span::from(format!(" {} ", action)));
+    }
+
+    Title::from(Line::from(spans))
+        .alignment(Alignment::Center)
+        .position(Position::Bottom)
+}
+
+/// Bordered popup block with a title and key hints
+fn popup_block(title: &'static str, hints: &[(&str, &str)], theme: &Theme) -> Block<'static> {
+    let block = Block::bordered().title(key_hints(hints, theme));
+    if title.is_empty() { block } else { block.title(title.bold().into_centered_line()) }
+}
+
+/// Shorten text to at most `width` columns, ending it with an ellipsis when cut
+pub fn truncate(text: &str, width: usize) -> Cow<'_, str> {
+    if text.width() <= width {
+        return Cow::Borrowed(text);
+    }
+
+    let mut result = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if used + char_width + 1 > width {
+            break;
+        }
+        used += char_width;
+        result.push(c);
+    }
+    if width > 0 {
+        result.push('…');
+    }
+    Cow::Owned(result)
+}
+
+/// Marker in front of the current and next entry, followed by the icon of its OS if enabled
+fn entry_prefix(view: &View, target: &BootTarget) -> String {
+    let marker = if view.targets.next.is_some_and(|next| next == target.id) {
+        tr!("nxt: ")
+    } else if view.targets.current.is_some_and(|curr| curr == target.id) {
+        tr!("cur: ")
+    } else {
+        "     "
+    };
+    format!("{}{}", marker, view.icons.prefix(os::detect(target)))
+}
+
+/// Badges after the name of inactive, duplicate or broken entries, and their
+/// place in the BootOrder being edited
+fn entry_badges(view: &View, target: &BootTarget) -> Vec<Span<'static>> {
+    let theme = &view.theme;
+    let mut spans = vec![];
+    // While the order is rearranged from the list, show where each entry ends up
+    if let Some(position) = view.order.order.iter().position(|id| *id == target.id).filter(|_| view.order.modified) {
+        spans.push(Span::styled(tr!(" (boot #{})", position + 1), theme.warning));
+    }
+    match duplicates::group_of(view.duplicates, target.id) {
+        Some(group) if group.keep == target.id && group.extras.len() == 1 => spans.push(Span::styled(tr!(" (+1 duplicate)"), theme.dim)),
+        Some(group) if group.keep == target.id => spans.push(Span::styled(tr!(" (+{} duplicates)", group.extras.len()), theme.dim)),
+        Some(group) => spans.push(Span::styled(tr!(" (duplicate of {})", format_id(group.keep)), theme.dim)),
+        None => (),
+    }
+    if !target.active {
+        spans.push(Span::styled(tr!(" (inactive)"), theme.dim));
+    }
+    if target.loader.needs_attention() {
+        spans.push(Span::styled(format!(" [!] {}", target.loader.describe()), theme.warning).bold());
+    }
+    spans
+}
+
+/// Columns left for the name of an entry in a list `width` columns wide
+fn name_width(view: &View, target: &BootTarget, width: usize) -> usize {
+    let badges: usize = entry_badges(view, target).iter().map(Span::width).sum();
+    width.saturating_sub(entry_prefix(view, target).width() + badges).max(2)
+}
+
+/// Whether the name of an entry is cut short in a list `width` columns wide
+pub fn is_truncated(view: &View, target: &BootTarget, width: usize) -> bool {
+    target.label().width() > name_width(view, target, width)
+}
+
+/// The full name of an entry if it is truncated in a list `width` columns
+/// wide, or its own name if it is listed by the system it starts, and the
+/// disk it boots from, or None if there is none of them
+fn details_line(view: &View, target: &BootTarget, width: usize) -> Option<Line<'static>> {
+    let mut spans = vec![];
+    if is_truncated(view, target, width) {
+        spans.push(Span::from(target.label().to_string()));
+    } else if target.os_name.is_some() {
+        // The name the entry really has, which is listed as the system it starts
+        spans.push(Span::from(target.name.clone()));
+    }
+    if let Some(device) = &target.device {
+        if !spans.is_empty() {
+            spans.push(Span::from("  "));
+        }
+        spans.push(Span::styled(device.clone(), view.theme.dim));
+    }
+    (!spans.is_empty()).then(|| Line::from(spans))
+}
+
+/// Rows of the list, as the index of the entry on each, or the section whose
+/// heading is on it. Headings are left out while all entries are systems.
+pub fn entry_rows(targets: &BootTargets) -> Vec<Result<usize, Section>> {
+    let sections: Vec<Section> = targets.targets.iter().map(sections::classify).collect();
+    if sections.iter().all(|section| *section == Section::Systems) {
+        return (0..sections.len()).map(Ok).collect();
+    }
+
+    let mut rows = vec![];
+    for (index, section) in sections.iter().enumerate() {
+        if index == 0 || sections[index - 1] != *section {
+            rows.push(Err(*section));
+        }
+        rows.push(Ok(index));
+    }
+    rows
+}
+
+/// One line per row, marking the current and next entry, and inactive,
+/// duplicate or broken ones. Names are truncated to fit `width` columns,
+/// keeping the badges.
+pub fn entry_lines(view: &View, width: usize) -> Vec<Line<'static>> {
+    entry_rows(view.targets).into_iter().map(|row| {
+        let target = match row {
+            Ok(index) => &view.targets.targets[index],
+            Err(section) => return Line::styled(format!("── {} ", section.heading()), view.theme.dim),
+        };
+        let name = truncate(target.label(), name_width(view, target, width));
+
+        let mut spans = vec![Span::from(format!("{}{}", entry_prefix(view, target), name))];
+        spans.extend(entry_badges(view, target));
+
+        Line::from(spans)
+    }).collect::<Vec<Line>>()
+}
+
+/// Render the list, whose selected entry and scroll offset are kept as entry
+/// indexes but drawn on rows that include the section headings. Returns the
+/// number of rows and the first row shown.
+fn render_entries(frame: &mut Frame, list: List<'static>, view: &View, area: Rect, list_state: &mut ListState) -> (usize, usize) {
+    let rows = entry_rows(view.targets);
+    let row_of = |index: usize| rows.iter().position(|row| *row == Ok(index));
+
+    // Like List itself would, keep selecting the last entry past the end, as after select_last()
+    let last = view.targets.targets.len().saturating_sub(1);
+    if let Some(selected) = list_state.selected().filter(|selected| *selected > last) {
+        list_state.select(Some(selected.min(last)).filter(|_| !view.targets.targets.is_empty()));
+    }
+
+    // Scrolled to the first entry of a section, its heading is shown too, also
+    // when the list scrolls up to the selected entry
+    let with_heading = |row: usize| if row > 0 && rows[row - 1].is_err() { row - 1 } else { row };
+    let selected = list_state.selected().and_then(row_of);
+    let offset = row_of(list_state.offset()).map(with_heading).unwrap_or(0);
+    let offset = selected.filter(|selected| *selected <= offset).map(with_heading).unwrap_or(offset);
+    let mut row_state = ListState::default().with_selected(selected).with_offset(offset);
+    frame.render_stateful_widget(list, area, &mut row_state);
+
+    let first_entry = rows.iter().skip(row_state.offset()).find_map(|row| row.ok()).unwrap_or(0);
+    *list_state.offset_mut() = first_entry;
+    (rows.len(), row_state.offset())
+}
+
+/// Keys and what they do, for the bottom of the list
+fn list_hints(view: &View) -> Vec<(String, &'static str)> {
+    let keys = view.keys;
+    let mut hints = vec![
+        (keys.pair_label(Action::Up, Action::Down), tr!("Select")),
+        (keys.label(Action::Reboot), tr!("Reboot")),
+        (keys.label(Action::SetNext), tr!("Set next")),
+        (keys.label(Action::SetDefault), tr!("Default")),
+        (keys.label(Action::ToggleActive), tr!("Active")),
+        (keys.label(Action::Delete), tr!("Delete")),
+        (keys.label(Action::Refresh), tr!("Refresh")),
+    ];
+    if view.targets.next.is_some() {
+        hints.push((keys.label(Action::ClearNext), tr!("Clear next")));
+    }
+    if view.order.modified {
+        hints.push((String::from("s"), tr!("Save order")));
+    }
+    if !view.duplicates.is_empty() {
+        hints.push((keys.label(Action::RemoveDuplicates), tr!("Remove duplicates")));
+    }
+    if view.network_hidden {
+        hints.push((keys.label(Action::ToggleNetwork), tr!("Show network")));
+    } else if view.targets.targets.iter().any(|target| sections::classify(target) == Section::Network) {
+        hints.push((keys.label(Action::ToggleNetwork), tr!("Hide network")));
+    }
+    hints.push((keys.label(Action::ToggleCommands), tr!("Commands")));
+    hints.push((keys.label(Action::Quit), tr!("Quit")));
+    hints
+}
+
+pub fn entry_list(view: &View, width: usize) -> List<'static> {
+    let hints = list_hints(view);
+    let hints: Vec<(&str, &str)> = hints.iter().map(|(key, action)| (key.as_str(), *action)).collect();
+    let block = Block::bordered()
+        .style(view.theme.text)
+        .title(Span::styled(truncate(view.title, width).into_owned(), view.theme.text).bold().into_centered_line())
+        .title(key_hints(&hints, &view.theme));
+
+    plain_list(view, width).block(block)
+}
+
+/// The entries without a border
+fn plain_list(view: &View, width: usize) -> List<'static> {
+    List::new(entry_lines(view, width))
+        .style(view.theme.text)
+        .highlight_style(view.theme.selected)
+        .repeat_highlight_symbol(true)
+        .direction(ListDirection::TopToBottom)
+}
+
+/// The most important keys, with the first key of each, for the compact layout
+fn compact_hints(keys: &KeyMap, theme: &Theme) -> Line<'static> {
+    let hints = [(Action::Reboot, tr!("Reboot")), (Action::SetNext, tr!("Set next")), (Action::Quit, tr!("Quit"))];
+    let spans: Vec<Span> = hints.iter()
+        .flat_map(|(action, text)| [Span::styled(keys.short_label(*action), theme.key), Span::from(format!(" {} ", text))])
+        .collect();
+    Line::from(spans)
+}
+
+pub fn status<'a>(status: &'a StatusLine, theme: &Theme) -> Paragraph<'a> {
+    let style = if status.error { theme.error } else { theme.success };
+    Paragraph::new(status.text.as_str()).style(style)
+}
+
+/// Bar emptying as the countdown runs out, with the seconds left on it
+pub fn countdown_gauge(countdown: &Countdown, theme: &Theme) -> Gauge<'static> {
+    let ratio = if countdown.total.is_zero() { 0.0 } else { countdown.left.as_secs_f64() / countdown.total.as_secs_f64() };
+    let label = tr!("Rebooting to \"{}\" in {} seconds, press any key to cancel", countdown.name, countdown.left.as_millis().div_ceil(1000));
+
+    Gauge::default()
+        .ratio(ratio.clamp(0.0, 1.0))
+        .label(Span::from(label).bold())
+        .use_unicode(true)
+        .gauge_style(theme.gauge)
+}
+
+/// Whether there is a message or countdown for the status line
+fn has_status(view: &View) -> bool {
+    view.status_line.is_some() || view.countdown.is_some()
+}
+
+/// Draw the countdown or the message in the status line, returning whether there was one
+fn draw_status(frame: &mut Frame, view: &View, area: Rect) -> bool {
+    if let Some(countdown) = &view.countdown {
+        frame.render_widget(countdown_gauge(countdown, &view.theme), area);
+    } else if let Some(status_line) = view.status_line {
+        frame.render_widget(status(status_line, &view.theme), area);
+    }
+    has_status(view)
+}
+
+/// Typed confirmation popup for deleting the entry named `name`
+pub fn delete_popup<'a>(prompt: &DeletePrompt, name: &'a str, theme: &Theme) -> Paragraph<'a> {
+    // The name is highlighted wherever the translation puts it
+    let question = tr!("Delete {}? This cannot be undone.");
+    let (before, after) = question.split_once("{}").unwrap_or((question, ""));
+    let text = vec![
+        Line::from(vec![before.into(), name.bold(), after.into()]),
+        Line::from(tr!("Type the name or ID of the entry to confirm:")),
+        Line::from(format!("> {}", prompt.input)),
+        if prompt.mismatch { Line::styled(tr!("Does not match the selected entry"), theme.warning) } else { Line::default() },
+    ];
+
+    Paragraph::new(text).block(popup_block(tr!(" Delete entry "), &[("Enter", tr!("Confirm")), ("Esc", tr!("Cancel"))], theme))
+}
+
+/// Confirmation popup listing the reasons to hesitate, with the size it needs
+pub fn confirm_popup<'a>(prompt: &'a ConfirmPrompt, theme: &Theme) -> (Paragraph<'a>, u16, u16) {
+    let mut text: Vec<Line> = prompt.lines.iter().map(|line| Line::from(line.as_str())).collect();
+    text.push(Line::default());
+    text.push(Line::from(prompt.question.bold()));
+
+    let width = text.iter().map(|line| line.width()).max().unwrap_or(0) as u16 + 4;
+    let height = text.len() as u16 + 2;
+
+    (Paragraph::new(text).block(popup_block(tr!(" Are you sure? "), &[("y", tr!("Continue")), ("n/Esc", tr!("Cancel"))], theme)), width.max(40), height)
+}
+
+/// Password entry popup for retrying through sudo
+pub fn password_popup(prompt: &PasswordPrompt, theme: &Theme) -> Paragraph<'static> {
+    let text = vec![
+        Line::from(tr!("This action requires root privileges.")),
+        Line::from(tr!("Enter your password to retry it with sudo:")),
+        Line::from(format!("> {}", "*".repeat(prompt.input.chars().count()))),
+        if prompt.rejected { Line::styled(tr!("Password was not accepted"), theme.warning) } else { Line::default() },
+    ];
+
+    Paragraph::new(text).block(popup_block(tr!(" Password required "), &[("Enter", tr!("Retry")), ("Esc", tr!("Cancel"))], theme))
+}
+
+/// PIN entry popup before rebooting or setting the next boot
+pub fn pin_popup(prompt: &PinPrompt, theme: &Theme) -> Paragraph<'static> {
+    let text = vec![
+        Line::from(match prompt.action {
+            BackgroundAction::Reboot => tr!("Enter the PIN to reboot to \"{}\":", prompt.target.name),
+            _ => tr!("Enter the PIN to boot \"{}\" next:", prompt.target.name),
+        }),
+        Line::from(format!("> {}", "*".repeat(prompt.input.chars().count()))),
+        if prompt.rejected { Line::styled(tr!("Wrong PIN"), theme.warning) } else { Line::default() },
+    ];
+
+    Paragraph::new(text).block(popup_block(tr!(" PIN required "), &[("Enter", tr!("Continue")), ("Esc", tr!("Cancel"))], theme))
+}
+
+pub fn spinner_popup<'a>(text: &'a str, theme: &Theme) -> Paragraph<'a> {
+    Paragraph::new(text).block(popup_block("", &[("Esc", tr!("Cancel"))], theme))
+}
+
+/// Rows taken by the recent entries above the list
+pub fn recent_height(recent: &[u16]) -> u16 {
+    if recent.is_empty() { 0 } else { recent.len() as u16 + 2 }
+}
+
+/// Recent entries, with the key that selects them
+pub fn recent_list(targets: &BootTargets, recent: &[u16], width: usize, theme: &Theme) -> Paragraph<'static> {
+    let lines: Vec<Line> = recent.iter().enumerate()
+        .filter_map(|(i, id)| targets.by_id(*id).map(|target| (i, target)))
+        .map(|(i, target)| Line::from(vec![
+            Span::styled(format!("Alt+{}", i + 1), theme.key),
+            Span::from(format!(" {}", truncate(target.label(), width.saturating_sub(6)))),
+        ]))
+        .collect();
+
+    Paragraph::new(lines).block(Block::bordered().style(theme.text).title(tr!(" Recent ").bold().into_centered_line()))
+}
+
+/// Line above the tabs with the pending BootNext, standing out when one is set
+fn header(targets: &BootTargets, theme: &Theme) -> Line<'static> {
+    match targets.next {
+        Some(id) => {
+            let name = targets.by_id(id).map(|target| target.label().to_string()).unwrap_or(format_id(id));
+            Line::styled(tr!("Next boot: {}", tr!("{} (one-time)", name)), theme.warning).bold()
+        }
+        None => Line::styled(tr!("Next boot: {}", tr!("default order")), theme.text),
+    }
+}
+
+/// Secure Boot state for the header, standing out when it isn't enforced yet
+fn secure_boot_span(secure_boot: SecureBoot, theme: &Theme) -> Span<'static> {
+    let style = match secure_boot {
+        SecureBoot::Enabled => theme.success,
+        SecureBoot::Disabled => theme.dim,
+        SecureBoot::SetupMode => theme.warning,
+    };
+    Span::styled(tr!("Secure Boot {}", secure_boot.describe()), style)
+}
+
+/// The pending BootNext, with pending firmware updates, the Secure Boot state
+/// and the firmware on the right as far as they fit
+fn draw_header(frame: &mut Frame, view: &View, area: Rect) {
+    let header = header(view.targets, &view.theme);
+
+    let mut right: Vec<Span> = vec![];
+    if !view.firmware_updates.is_empty() {
+        right.push(Span::styled(tr!("Firmware update on reboot"), view.theme.warning).bold());
+    }
+    if let Some(secure_boot) = view.secure_boot {
+        if !right.is_empty() {
+            right.push(Span::from("  "));
+        }
+        right.push(secure_boot_span(secure_boot, &view.theme));
+    }
+    if let Some(firmware) = view.firmware {
+        if !right.is_empty() {
+            right.push(Span::from("  "));
+        }
+        right.push(Span::styled(firmware.to_string(), view.theme.dim));
+    }
+    // Drop the firmware, then the Secure Boot state, then the firmware update, before cutting the pending BootNext short
+    let fits = |spans: &[Span]| header.width() + spans.iter().map(Span::width).sum::<usize>() + 2 <= area.width as usize;
+    while !right.is_empty() && !fits(&right) {
+        right.pop();
+        if right.last().is_some_and(|span| span.content == "  ") {
+            right.pop();
+        }
+    }
+
+    let right = Line::from(right);
+    let [next_area, right_area] = Layout::horizontal([Constraint::Min(0), Constraint::Length(right.width() as u16)]).areas(area);
+    frame.render_widget(Paragraph::new(header), next_area);
+    frame.render_widget(Paragraph::new(right), right_area);
+}
+
+/// Tab names, numbered by the key that switches to them
+fn tab_titles() -> Vec<String> {
+    Tab::ALL.iter().enumerate().map(|(i, tab)| format!("{} {}", i + 1, tab.title())).collect()
+}
+
+/// Row of tabs above the full layout
+fn tab_bar(tab: Tab, theme: &Theme) -> Tabs<'static> {
+    Tabs::new(tab_titles())
+        .select(tab.index())
+        .style(theme.text)
+        .highlight_style(theme.selected.add_modifier(Modifier::BOLD))
+}
+
+/// Title of the Order, Settings or Log tab
+fn tab_title(view: &View) -> &'static str {
+    match view.tab {
+        Tab::Order if view.order.modified => tr!(" BootOrder (not saved) "),
+        Tab::Order => tr!(" BootOrder "),
+        Tab::Settings if view.settings.modified => tr!(" Settings (not saved) "),
+        Tab::Settings => tr!(" Settings "),
+        _ => tr!(" Log "),
+    }
+}
+
+/// Keys and what they do on the Order, Settings or Log tab
+fn tab_hints(view: &View) -> Vec<(String, &'static str)> {
+    let keys = view.keys;
+    let mut hints = match view.tab {
+        Tab::Order => vec![
+            (keys.pair_label(Action::Up, Action::Down), tr!("Select")),
+            (String::from("+/-"), tr!("Move")),
+            (String::from("s"), tr!("Save")),
+            (keys.label(Action::Refresh), tr!("Discard")),
+        ],
+        Tab::Settings => vec![
+            (String::from("+/-"), tr!("Timeout")),
+            (String::from("s"), tr!("Save")),
+            (keys.label(Action::Refresh), tr!("Discard")),
+        ],
+        _ => vec![],
+    };
+    hints.push((keys.label(Action::ToggleCommands), tr!("Commands")));
+    hints.push((keys.label(Action::Quit), tr!("Quit")));
+    hints
+}
+
+/// Contents of the Order, Settings or Log tab, scrolled to fit `height` rows
+fn tab_content(view: &View, width: usize, height: usize) -> Paragraph<'static> {
+    let targets = view.targets;
+    let (lines, scroll) = match view.tab {
+        Tab::Order => {
+            let lines = view.order.order.iter().enumerate().map(|(i, id)| {
+                let name = targets.by_id(*id).map(|target| target.label()).unwrap_or(tr!("(no such entry)"));
+                let line = Line::from(format!("{:>2}. {} {}", i + 1, format_id(*id), truncate(name, width.saturating_sub(9))));
+                if i == view.order.selected { line.patch_style(view.theme.selected) } else { line }
+            }).collect();
+            // Keep the selected entry in view
+            (lines, (view.order.selected + 1).saturating_sub(height))
+        }
+        Tab::Settings => {
+            let describe = |id: Option<u16>, none: &'static str| id.map(|id| targets.describe(id)).unwrap_or(String::from(none));
+            let lines = vec![
+                Line::from(tr!("Timeout:      {}", view.settings.timeout.map(|t| tr!("{} seconds", t)).unwrap_or(String::from(tr!("unset"))))),
+                Line::from(tr!("Default:      {}", describe(targets.order.first().copied(), tr!("none")))),
+                Line::from(tr!("Current boot: {}", describe(targets.current, tr!("unknown")))),
+                Line::from(tr!("Next boot:    {}", describe(targets.next, tr!("default order")))),
+                Line::from(tr!("ESP:          {}", view.esp.unwrap_or(tr!("not found")))),
+            ];
+            (lines, 0)
+        }
+        _ if view.log.is_empty() => (vec![Line::styled(tr!("Nothing done yet"), view.theme.dim)], 0),
+        // Newest at the bottom, like a terminal
+        _ => (view.log.iter().map(|line| Line::from(line.clone())).collect(), view.log.len().saturating_sub(height)),
+    };
+
+    Paragraph::new(lines).style(view.theme.text).scroll((scroll.min(u16::MAX as usize) as u16, 0))
+}
+
+/// Rows taken by the pane with the commands run below the tabs
+fn commands_height(view: &View) -> u16 {
+    if view.show_commands { COMMANDS_HEIGHT } else { 0 }
+}
+
+/// The commands run and their output, scrolled to the newest at the bottom
+pub fn commands_pane(commands: &[String], height: usize, theme: &Theme) -> Paragraph<'static> {
+    let lines: Vec<Line> = if commands.is_empty() {
+        vec![Line::styled(tr!("No commands run yet"), theme.dim)]
+    } else {
+        commands.iter().map(|line| if line.starts_with("$ ") { Line::from(line.clone()).bold() } else { Line::from(line.clone()) }).collect()
+    };
+    let scroll = commands.len().saturating_sub(height).min(u16::MAX as usize) as u16;
+
+    Paragraph::new(lines)
+        .style(theme.text)
+        .scroll((scroll, 0))
+        .block(Block::bordered().style(theme.text).title(tr!(" Commands ").bold().into_centered_line()))
+}
+
+/// Centered area fitting the entries, their badges and the key hints, and the status line if any
+fn floating_area(view: &View, area: Rect) -> Rect {
+    let targets = view.targets;
+    let entries = targets.targets.iter()
+        .map(|target| entry_prefix(view, target).width() + target.label().width() + entry_badges(view, target).iter().map(Span::width).sum::<usize>());
+    let recent = view.recent.iter().filter_map(|id| targets.by_id(*id)).map(|target| target.label().width() + 6);
+    let hints = list_hints(view);
+    let hints: Vec<(&str, &str)> = hints.iter().map(|(key, action)| (key.as_str(), *action)).collect();
+    let hints = key_hints(&hints, &view.theme).content.width();
+
+    let tabs = tab_titles().iter().map(|title| title.width() + 3).sum::<usize>();
+
+    let width = entries.chain(recent).chain([hints, tabs, header(targets, &view.theme).width(), view.title.width()]).max().unwrap_or_default() + 2;
+    let height = 2 + entry_rows(targets).len() + 2 + recent_height(view.recent) as usize + commands_height(view) as usize + has_status(view) as usize;
+    centered_rect(width.min(u16::MAX as usize) as u16, height.min(u16::MAX as usize) as u16, area)
+}
+
+/// Whether an area is too small for borders and the full key hints
+fn is_compact(area: Rect) -> bool {
+    area.width <= COMPACT_WIDTH || area.height <= COMPACT_HEIGHT
+}
+
+/// Area the TUI is drawn in within the terminal
+fn tui_area(view: &View, area: Rect) -> Rect {
+    if view.floating { floating_area(view, area) } else { area }
+}
+
+/// Entries that fit in the list in a terminal of the given size, for moving a page at a time
+pub fn page_size(view: &View, area: Rect) -> usize {
+    let area = tui_area(view, area);
+    let rows = if is_compact(area) {
+        // The status line takes the place of the key hints
+        1
+    } else {
+        // The header, the tab bar, the borders, the recent entries and the commands run
+        4 + recent_height(view.recent) + commands_height(view) + has_status(view) as u16
+    };
+    area.height.saturating_sub(rows).max(1) as usize
+}
+
+/// Draw the whole TUI
+pub fn draw(frame: &mut Frame, view: &View, list_state: &mut ListState) {
+    let area = tui_area(view, frame.size());
+    if frame.size().width < MIN_WIDTH || frame.size().height < MIN_HEIGHT {
+        frame.render_widget(Paragraph::new(truncate(tr!("Terminal too small"), frame.size().width as usize).into_owned()), frame.size());
+        return;
+    }
+
+    match (is_compact(area), view.tab) {
+        (true, Tab::Entries) => draw_compact(frame, view, list_state, area),
+        (true, _) => draw_compact_tab(frame, view, area),
+        (false, tab) => {
+            let [header_area, tabs_area, body_area, commands_area] = Layout::vertical([
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Min(0),
+                Constraint::Length(commands_height(view)),
+            ]).areas(area);
+            draw_header(frame, view, header_area);
+            frame.render_widget(tab_bar(tab, &view.theme), tabs_area);
+            if view.show_commands {
+                frame.render_widget(commands_pane(view.commands, commands_area.height.saturating_sub(2) as usize, &view.theme), commands_area);
+            }
+            if tab == Tab::Entries {
+                draw_full(frame, view, list_state, body_area);
+            } else {
+                draw_tab(frame, view, body_area);
+            }
+        }
+    }
+    draw_popups(frame, view, list_state, area);
+}
+
+/// The Order, Settings or Log tab in a bordered box with key hints, above a status line if needed
+fn draw_tab(frame: &mut Frame, view: &View, area: Rect) {
+    let [content_area, status_area] = Layout::vertical([
+        Constraint::Min(0),
+        Constraint::Length(if has_status(view) { 1 } else { 0 }),
+    ]).areas(area);
+
+    let hints = tab_hints(view);
+    let hints: Vec<(&str, &str)> = hints.iter().map(|(key, action)| (key.as_str(), *action)).collect();
+    let block = Block::bordered()
+        .style(view.theme.text)
+        .title(Span::styled(tab_title(view), view.theme.text).bold().into_centered_line())
+        .title(key_hints(&hints, &view.theme));
+
+    let content = tab_content(view, content_area.width.saturating_sub(2) as usize, content_area.height.saturating_sub(2) as usize);
+    frame.render_widget(content.block(block), content_area);
+
+    draw_status(frame, view, status_area);
+}
+
+/// The Order, Settings or Log tab without borders, above a line with a message or the most important keys
+fn draw_compact_tab(frame: &mut Frame, view: &View, area: Rect) {
+    let [content_area, bottom_area] = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(area);
+
+    frame.render_widget(tab_content(view, content_area.width as usize, content_area.height as usize), content_area);
+
+    if !draw_status(frame, view, bottom_area) {
+        let spans: Vec<Span> = tab_hints(view).into_iter()
+            .flat_map(|(key, text)| [Span::styled(key, view.theme.key), Span::from(format!(" {} ", text))])
+            .collect();
+        frame.render_widget(Paragraph::new(Line::from(spans)), bottom_area);
+    }
+}
+
+/// Entries without borders, above a line with a message, the full name of
+/// the selected entry or the most important keys
+fn draw_compact(frame: &mut Frame, view: &View, list_state: &mut ListState, area: Rect) {
+    let width = area.width as usize;
+    let [list_area, bottom_area] = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(area);
+
+    render_entries(frame, plain_list(view, width), view, list_area, list_state);
+
+    let selected = list_state.selected().and_then(|index| view.targets.targets.get(index));
+    let full_name = selected.filter(|target| is_truncated(view, target, width)).map(|target| target.label());
+    if !draw_status(frame, view, bottom_area) {
+        match full_name {
+            Some(name) => frame.render_widget(Paragraph::new(name).style(view.theme.text), bottom_area),
+            None => frame.render_widget(Paragraph::new(compact_hints(view.keys, &view.theme)), bottom_area),
+        }
+    }
+}
+
+/// Recent entries and the bordered list with key hints, above a status line if needed
+fn draw_full(frame: &mut Frame, view: &View, list_state: &mut ListState, area: Rect) {
+    let list_width = area.width.saturating_sub(2) as usize;
+
+    // Details of the selected entry are shown below the list, unless there is a message
+    let selected = list_state.selected().and_then(|index| view.targets.targets.get(index));
+    let details = selected.and_then(|target| details_line(view, target, list_width));
+
+    let [recent_area, list_area, status_area] = Layout::vertical([
+        Constraint::Length(recent_height(view.recent)),
+        Constraint::Min(0),
+        Constraint::Length(if has_status(view) || details.is_some() { 1 } else { 0 }),
+    ]).areas(area);
+
+    if !view.recent.is_empty() {
+        frame.render_widget(recent_list(view.targets, view.recent, list_width, &view.theme), recent_area);
+    }
+    let (count, offset) = render_entries(frame, entry_list(view, list_width), view, list_area, list_state);
+
+    // Show there is more above or below on the right border, when entries don't fit
+    let visible = list_area.height.saturating_sub(2) as usize;
+    if count > visible {
+        // The content is the possible scroll offsets, so the thumb reaches the end with the last entry
+        let mut scrollbar_state = ScrollbarState::new(count - visible + 1).viewport_content_length(visible).position(offset);
+        let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight).begin_symbol(None).end_symbol(None);
+        frame.render_stateful_widget(scrollbar, list_area.inner(Margin { vertical: 1, horizontal: 0 }), &mut scrollbar_state);
+    }
+
+    if let (false, Some(details)) = (draw_status(frame, view, status_area), details) {
+        frame.render_widget(Paragraph::new(details).style(view.theme.text), status_area);
+    }
+}
+
+fn draw_popups(frame: &mut Frame, view: &View, list_state: &ListState, area: Rect) {
+    if let Some(prompt) = view.delete_prompt {
+        let name = list_state.selected()
+            .and_then(|index| view.targets.targets.get(index))
+            .map(|target| target.name.as_str())
+            .unwrap_or_default();
+
+        let popup = centered_rect(60, 6, area);
+        frame.render_widget(Clear, popup);
+        frame.render_widget(delete_popup(prompt, name, &view.theme), popup);
+    }
+
+    if let Some(prompt) = view.confirm_prompt {
+        let (paragraph, width, height) = confirm_popup(prompt, &view.theme);
+        let popup = centered_rect(width, height, area);
+        frame.render_widget(Clear, popup);
+        frame.render_widget(paragraph, popup);
+    }
+
+    if let Some(prompt) = view.password_prompt {
+        let popup = centered_rect(60, 6, area);
+        frame.render_widget(Clear, popup);
+        frame.render_widget(password_popup(prompt, &view.theme), popup);
+    }
+
+    if let Some(prompt) = view.pin_prompt {
+        let popup = centered_rect(60, 5, area);
+        frame.render_widget(Clear, popup);
+        frame.render_widget(pin_popup(prompt, &view.theme), popup);
+    }
+
+    if let Some(text) = &view.spinner {
+        let popup = centered_rect(text.chars().count() as u16 + 4, 3, area);
+        frame.render_widget(Clear, popup);
+        frame.render_widget(spinner_popup(text, &view.theme), popup);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::LazyLock;
+
+    use ratatui::{backend::TestBackend, style::Color, Terminal};
+
+    use super::*;
+    use crate::{parse_boot_targets, theme::Preset, verify::LoaderStatus};
+
+    fn targets() -> BootTargets {
+        let mut targets = parse_boot_targets("BootCurrent: 0001\n\
+            BootNext: 0000\n\
+            Boot0000* Windows Boot Manager\n\
+            Boot0001* ubuntu\n\
+            Boot0002  Old kernel\n\
+            Boot0003* Fedora\n");
+        targets.targets[3].loader = LoaderStatus::Missing;
+        targets
+    }
+
+    static KEYS: LazyLock<KeyMap> = LazyLock::new(KeyMap::default);
+    static ORDER: OrderEditor = OrderEditor { order: Vec::new(), selected: 0, modified: false };
+    static SETTINGS: SettingsEditor = SettingsEditor { timeout: None, modified: false };
+
+    fn view(targets: &BootTargets) -> View<'_> {
+        View {
+            targets, duplicates: &[], keys: &KEYS, title: " reboot-to @ pc ", firmware: None, secure_boot: None, firmware_updates: &[], esp: None, icons: Icons::None, theme: Theme::default(), recent: &[], floating: false, tab: Tab::Entries, order: &ORDER, settings: &SETTINGS, log: &[],
+            commands: &[], show_commands: false, network_hidden: false,
+            status_line: None, countdown: None, delete_prompt: None, confirm_prompt: None, password_prompt: None, pin_prompt: None, spinner: None,
+        }
+    }
+
+    /// Render the view and return the text on screen
+    fn render(view: &View, width: u16, height: u16) -> Vec<String> {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).expect("Test terminal should be created");
+        let mut list_state = ListState::default().with_selected(Some(0));
+        terminal.draw(|frame| draw(frame, view, &mut list_state)).expect("Test terminal should draw");
+
+        let buffer = terminal.backend().buffer();
+        buffer.content().chunks(buffer.area.width as usize)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn renders_entry_list() {
+        let mut targets = targets();
+
+        assert_eq!(render(&view(&targets), 60, 9), vec![
+            "Next boot: Windows Boot Manager (one-time)                  ",
+            " 1 Entries │ 2 Order │ 3 Settings │ 4 Log                   ",
+            "┌───────────────────── reboot-to @ pc ─────────────────────┐",
+            "│nxt: Windows Boot Manager                                 │",
+            "│cur: ubuntu                                               │",
+            "│     Old kernel (inactive)                                │",
+            "│     Fedora [!] MISSING loader                            │",
+            "│                                                          │",
+            "└ Up/Down Select Enter Reboot n Set next d Default a Active┘",
+        ]);
+
+        targets.next = None;
+        let view = View { firmware: Some("LENOVO N32ET86W (1.62), 64-bit UEFI"), secure_boot: Some(SecureBoot::Enabled), ..view(&targets) };
+        assert_eq!(render(&view, 90, 9)[0], "Next boot: default order          Secure Boot enabled  LENOVO N32ET86W (1.62), 64-bit UEFI");
+        assert_eq!(render(&view, 60, 9)[0], "Next boot: default order                 Secure Boot enabled");
+        assert_eq!(render(&view, 40, 9)[0].trim_end(), "Next boot: default order");
+
+        let updates = [String::from("System Firmware")];
+        let view = View { firmware_updates: &updates, ..view };
+        assert_eq!(render(&view, 60, 9)[0], "Next boot: default order           Firmware update on reboot");
+        assert_eq!(render(&view, 85, 9)[0], "Next boot: default order               Firmware update on reboot  Secure Boot enabled");
+    }
+
+    #[test]
+    fn renders_scrollbar_when_entries_do_not_fit() {
+        let mut targets = targets();
+        targets.targets.push(BootTarget { id: 0x0004, name: String::from("Arch"), ..targets.targets[1].clone() });
+        targets.targets.push(BootTarget { id: 0x0005, name: String::from("Shell"), ..targets.targets[1].clone() });
+
+        assert_eq!(render(&view(&targets), 40, 8), vec![
+            "Next boot: Windows Boot Manager (one-tim",
+            " 1 Entries │ 2 Order │ 3 Settings │ 4 Lo",
+            "┌─────────── reboot-to @ pc ───────────┐",
+            "│nxt: Windows Boot Manager             █",
+            "│cur: ubuntu                           █",
+            "│     Old kernel (inactive)            █",
+            "│     Fedora [!] MISSING loader        ║",
+            "└ Up/Down Select Enter Reboot n Set nex┘",
+        ]);
+    }
+
+    #[test]
+    fn renders_compact_layout_in_small_terminals() {
+        let targets = targets();
+
+        assert_eq!(render(&view(&targets), 40, 5), vec![
+            "nxt: Windows Boot Manager               ",
+            "cur: ubuntu                             ",
+            "     Old kernel (inactive)              ",
+            "     Fedora [!] MISSING loader          ",
+            "Enter Reboot n Set next Esc Quit        ",
+        ]);
+        assert_eq!(render(&view(&targets), 8, 1), vec!["Termina…"]);
+    }
+
+    #[test]
+    fn truncates_by_display_width() {
+        assert_eq!(truncate("ubuntu", 6), "ubuntu");
+        assert_eq!(truncate("Windows Boot Manager", 8), "Windows…");
+        assert_eq!(truncate("日本語のOS", 6), "日本…");
+    }
+
+    #[test]
+    fn truncates_long_names_and_shows_the_full_name() {
+        let targets = targets();
+
+        assert_eq!(render(&view(&targets), 24, 9), vec![
+            "Next boot: Windows Boot ",
+            " 1 Entries │ 2 Order │ 3",
+            "┌─── reboot-to @ pc ───┐",
+            "│nxt: Windows Boot Man…│",
+            "│cur: ubuntu           │",
+            "│     Old k… (inactive)│",
+            "│     F… [!] MISSING lo│",
+            "└ Up/Down Select Enter ┘",
+            "Windows Boot Manager    ",
+        ]);
+    }
+
+    #[test]
+    fn shows_the_disk_of_the_selected_entry() {
+        let mut targets = targets();
+        targets.targets[0].device = Some(String::from("Samsung SSD 980 PRO 1TB, nvme0n1p1"));
+
+        assert_eq!(render(&view(&targets), 60, 10)[9], "Samsung SSD 980 PRO 1TB, nvme0n1p1                          ");
+        assert_eq!(render(&view(&targets), 24, 9)[8], "Windows Boot Manager  Sa");
+    }
+
+    #[test]
+    fn lists_entries_by_the_system_they_start() {
+        let mut targets = targets();
+        targets.targets[0].name = String::from("Linux Boot Manager");
+        targets.targets[0].os_name = Some(String::from("Fedora Linux 40"));
+
+        let screen = render(&view(&targets), 60, 10);
+        assert_eq!(screen[3], "│nxt: Fedora Linux 40                                      │");
+        assert_eq!(screen[9], "Linux Boot Manager                                          ");
+    }
+
+    #[test]
+    fn lists_entries_under_section_headings() {
+        let mut targets = parse_boot_targets("BootCurrent: 0001\n\
+            Boot0001* ubuntu\tHD(1,GPT,0f1c2d3e-4b5a-6978-8a9b-0c1d2e3f4a5b,0x800,0x100000)/File(\\EFI\\ubuntu\\shimx64.efi)\n\
+            Boot0002* UEFI PXE IPv4\tPciRoot(0x0)/Pci(0x1c,0x0)/MAC(001122334455,0)/IPv4(0.0.0.00.0.0.0,0,0)\n");
+        sections::sort(&mut targets);
+
+        let screen = render(&view(&targets), 40, 10);
+        assert_eq!(screen[3..7], [
+            "│── Operating systems                  │",
+            "│cur: ubuntu                           │",
+            "│── Network boot                       │",
+            "│     UEFI PXE IPv4                    │",
+        ]);
+    }
+
+    #[test]
+    fn marks_duplicates() {
+        let targets = targets();
+        let groups = [Group { keep: 0x0001, extras: vec![0x0002] }];
+        let view = View { duplicates: &groups, ..view(&targets) };
+
+        let screen = render(&view, 60, 9);
+        assert_eq!(screen[4..6], [
+            "│cur: ubuntu (+1 duplicate)                                │",
+            "│     Old kernel (duplicate of 0001) (inactive)            │",
+        ]);
+        assert!(render(&view, 120, 9)[8].contains(" c Remove duplicates "));
+    }
+
+    #[test]
+    fn marks_order_being_edited() {
+        let targets = targets();
+        let order = OrderEditor { order: vec![0x0003, 0x0001], selected: 0, modified: true };
+        let view = View { order: &order, ..view(&targets) };
+
+        let screen = render(&view, 60, 9);
+        assert_eq!(screen[4..7], [
+            "│cur: ubuntu (boot #2)                                     │",
+            "│     Old kernel (inactive)                                │",
+            "│     Fedora (boot #1) [!] MISSING loader                  │",
+        ]);
+    }
+
+    #[test]
+    fn renders_ascii_icons() {
+        let targets = targets();
+        let view = View { icons: Icons::Ascii, ..view(&targets) };
+
+        let screen = render(&view, 40, 9);
+        assert_eq!(screen[3..7], [
+            "│nxt: [win] Windows Boot Manager       │",
+            "│cur: [ubu] ubuntu                     │",
+            "│           Old kernel (inactive)      │",
+            "│     [fed] Fedora [!] MISSING loader  │",
+        ]);
+    }
+
+    #[test]
+    fn renders_recent_entries() {
+        let targets = targets();
+        let view = View { recent: &[0x0003, 0x0000], ..view(&targets) };
+
+        assert_eq!(render(&view, 30, 12)[2..7], [
+            "┌────────── Recent ──────────┐",
+            "│Alt+1 Fedora                │",
+            "│Alt+2 Windows Boot Manager  │",
+            "└────────────────────────────┘",
+            "┌────── reboot-to @ pc ──────┐",
+        ]);
+    }
+
+    #[test]
+    fn renders_floating_window() {
+        let targets = targets();
+        let view = View { floating: true, ..view(&targets) };
+
+        let screen = render(&view, 130, 10);
+        assert_eq!(screen[0], " ".repeat(130));
+        assert_eq!(&screen[1..5], [
+            "     Next boot: Windows Boot Manager (one-time)                                                                                   ",
+            "      1 Entries │ 2 Order │ 3 Settings │ 4 Log                                                                                    ",
+            "     ┌────────────────────────────────────────────────── reboot-to @ pc ───────────────────────────────────────────────────┐      ",
+            "     │nxt: Windows Boot Manager                                                                                            │      ",
+        ]);
+        assert_eq!(screen[8], "     └ Up/Down Select Enter Reboot n Set next d Default a Active Del/D Delete r Refresh x Clear next o Commands Esc/q Quit ┘      ");
+    }
+
+    #[test]
+    fn renders_order_tab() {
+        let targets = targets();
+        let order = OrderEditor { order: vec![0x0003, 0x0001, 0x0009], selected: 1, modified: true };
+        let view = View { tab: Tab::Order, order: &order, ..view(&targets) };
+
+        assert_eq!(render(&view, 44, 7), vec![
+            "Next boot: Windows Boot Manager (one-time)  ",
+            " 1 Entries │ 2 Order │ 3 Settings │ 4 Log   ",
+            "┌───────── BootOrder (not saved) ──────────┐",
+            "│ 1. 0003 Fedora                           │",
+            "│ 2. 0001 ubuntu                           │",
+            "│ 3. 0009 (no such entry)                  │",
+            "└ Up/Down Select +/- Move s Save r Discard ┘",
+        ]);
+    }
+
+    #[test]
+    fn renders_commands_pane() {
+        let targets = targets();
+        let commands = [String::from("$ efibootmgr --bootnext 0003"), String::from("  BootNext: 0003"), String::from("  exit status: 0")];
+        let view = View { commands: &commands, show_commands: true, ..view(&targets) };
+
+        assert_eq!(render(&view, 40, 14)[6..11], [
+            "┌────────────── Commands ──────────────┐",
+            "│$ efibootmgr --bootnext 0003          │",
+            "│  BootNext: 0003                      │",
+            "│  exit status: 0                      │",
+            "│                                      │",
+        ]);
+    }
+
+    #[test]
+    fn renders_status_line() {
+        let targets = targets();
+        let status_line = StatusLine { text: String::from("Deleted boot entry \"Fedora\""), error: false };
+        let view = View { status_line: Some(&status_line), ..view(&targets) };
+
+        let screen = render(&view, 60, 8);
+        assert_eq!(screen[7], "Deleted boot entry \"Fedora\"                                 ");
+        assert!(screen[6].starts_with("└"));
+    }
+
+    #[test]
+    fn renders_countdown_gauge() {
+        let targets = targets();
+        let countdown = Countdown { name: String::from("Fedora"), left: Duration::from_millis(2500), total: Duration::from_secs(5) };
+        let view = View { countdown: Some(countdown), ..view(&targets) };
+
+        let mut terminal = Terminal::new(TestBackend::new(70, 8)).expect("Test terminal should be created");
+        terminal.draw(|frame| draw(frame, &view, &mut ListState::default())).expect("Test terminal should draw");
+        let buffer = terminal.backend().buffer();
+
+        let row: String = (0..70).map(|x| buffer.get(x, 7).symbol()).collect();
+        assert_eq!(row, "█████Rebooting to \"Fedora\" in 3 seconds, press any key to cancel      ");
+        // Half the time is left, so half the bar is filled
+        assert_eq!(buffer.get(34, 7).bg, Color::Yellow);
+        assert_eq!(buffer.get(36, 7).bg, Color::DarkGray);
+    }
+
+    #[test]
+    fn applies_theme() {
+        let targets = targets();
+        let status_line = StatusLine { text: String::from("Could not delete"), error: true };
+        let view = View { status_line: Some(&status_line), theme: Preset::ColorBlind.theme(), ..view(&targets) };
+
+        let mut terminal = Terminal::new(TestBackend::new(60, 8)).expect("Test terminal should be created");
+        terminal.draw(|frame| draw(frame, &view, &mut ListState::default().with_selected(Some(0)))).expect("Test terminal should draw");
+        let buffer = terminal.backend().buffer();
+
+        // Errors are orange instead of red
+        assert_eq!(buffer.get(0, 7).fg, Color::Indexed(208));
+
+        let view = View { theme: Preset::HighContrast.theme(), ..view };
+        terminal.draw(|frame| draw(frame, &view, &mut ListState::default().with_selected(Some(0)))).expect("Test terminal should draw");
+        let buffer = terminal.backend().buffer();
+        assert_eq!(buffer.get(1, 3).bg, Color::LightYellow);
+        assert_eq!(buffer.get(1, 4).fg, Color::White);
+    }
+
+    #[test]
+    fn renders_delete_popup() {
+        let targets = targets();
+        let prompt = DeletePrompt { input: String::from("Win"), mismatch: true };
+        let view = View { delete_prompt: Some(&prompt), ..view(&targets) };
+
+        assert_eq!(render(&view, 60, 8)[1..7], [
+            "┌────────────────────── Delete entry ──────────────────────┐",
+            "│Delete Windows Boot Manager? This cannot be undone.       │",
+            "│Type the name or ID of the entry to confirm:              │",
+            "│> Win                                                     │",
+            "│Does not match the selected entry                         │",
+            "└──────────────── Enter Confirm Esc Cancel ────────────────┘",
+        ]);
+    }
+
+    #[test]
+    fn renders_confirm_popup() {
+        let targets = targets();
+        let prompt = ConfirmPrompt {
+            action: BackgroundAction::Reboot,
+            target: targets.targets[0].clone(),
+            lines: vec![String::from("alice is logged in on pts/1")],
+            question: "Continue anyway?",
+        };
+        let view = View { confirm_prompt: Some(&prompt), ..view(&targets) };
+
+        assert_eq!(render(&view, 60, 12)[3..9], [
+            "│nxt: Wind┌─────────── Are you sure? ────────────┐         │",
+            "│cur: ubun│alice is logged in on pts/1           │         │",
+            "│     Old │                                      │         │",
+            "│     Fedo│Continue anyway?                      │         │",
+            "│         └────── y Continue n/Esc Cancel ───────┘         │",
+            "│                                                          │",
+        ]);
+    }
+
+    #[test]
+    fn masks_password() {
+        let targets = targets();
+        let prompt = PasswordPrompt {
+            action: BackgroundAction::SetNext,
+            target: targets.targets[0].clone(),
+            input: String::from("hunter2"),
+            rejected: false,
+        };
+        let view = View { password_prompt: Some(&prompt), ..view(&targets) };
+
+        let screen = render(&view, 60, 8).join("\n");
+        assert!(screen.contains("> *******"));
+        assert!(!screen.contains("hunter2"));
+    }
+
+    #[test]
+    fn asks_for_pin() {
+        let targets = targets();
+        let prompt = PinPrompt {
+            action: BackgroundAction::Reboot,
+            target: targets.targets[0].clone(),
+            input: String::from("1234"),
+            rejected: true,
+        };
+        let view = View { pin_prompt: Some(&prompt), ..view(&targets) };
+
+        let screen = render(&view, 60, 8).join("\n");
+        assert!(screen.contains("PIN to reboot to"));
+        assert!(screen.contains("> ****"));
+        assert!(screen.contains("Wrong PIN"));
+        assert!(!screen.contains("1234"));
+    }
+
+    #[test]
+    fn renders_spinner() {
+        let targets = targets();
+        let view = View { spinner: Some(spinner_text("Setting next boot to \"ubuntu\"", Duration::ZERO)), ..view(&targets) };
+
+        assert_eq!(render(&view, 60, 11)[4..7], [
+            "│cur: ubunt┌────────────────────────────────────┐          │",
+            "│     Old k│⠋ Setting next boot to \"ubuntu\"...  │          │",
+            "│     Fedor└──────────── Esc Cancel ────────────┘          │",
+        ]);
+    }
+
+    #[test]
+    fn fits_tiny_terminals() {
+        let targets = targets();
+        let prompt = DeletePrompt { input: String::new(), mismatch: false };
+        let view = View { delete_prompt: Some(&prompt), ..view(&targets) };
+
+        assert_eq!(render(&view, 10, 3).len(), 3);
+    }
+}
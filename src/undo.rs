@@ -0,0 +1,108 @@
+/***
+ * Undo
+ *
+ * Before BootNext or BootOrder is changed, both are saved as they were last
+ * read, in memory and in `undo` next to the history file, in the format of
+ * efibootmgr's own output. Undoing puts them back, saving the values it
+ * replaces in turn, so undoing twice redoes the change.
+ ***/
+use std::{fs, io, sync::Mutex};
+
+use crate::{demo, efibootmgr::{format_id, parse_id}, history, BootTargets};
+
+/// BootNext and BootOrder at one point in time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub next: Option<u16>,
+    pub order: Vec<u16>,
+}
+
+/// Values of the boot variables when they were last read
+static SEEN: Mutex<Option<Snapshot>> = Mutex::new(None);
+
+/// Values before the last change, in this run
+static SAVED: Mutex<Option<Snapshot>> = Mutex::new(None);
+
+impl Snapshot {
+    fn of(targets: &BootTargets) -> Snapshot {
+        Snapshot { next: targets.next, order: targets.order.clone() }
+    }
+}
+
+/// Parse the undo file, which needs at least the BootOrder line
+pub fn parse(text: &str) -> Option<Snapshot> {
+    let mut next = None;
+    let mut order = None;
+    for line in text.lines() {
+        match line.split_once(": ") {
+            Some(("BootNext", id)) => next = Some(parse_id(id)?),
+            Some(("BootOrder", "")) => order = Some(vec![]),
+            Some(("BootOrder", ids)) => order = Some(ids.split(',').map(parse_id).collect::<Option<Vec<u16>>>()?),
+            _ => (),
+        }
+    }
+    Some(Snapshot { next, order: order? })
+}
+
+pub fn format(snapshot: &Snapshot) -> String {
+    let order = snapshot.order.iter().map(|id| format_id(*id)).collect::<Vec<String>>().join(",");
+    match snapshot.next {
+        Some(next) => format!("BootNext: {}\nBootOrder: {}\n", format_id(next), order),
+        None => format!("BootOrder: {}\n", order),
+    }
+}
+
+/// Note the boot variables just read, to save them before they are changed
+pub fn observe(targets: &BootTargets) {
+    *SEEN.lock().expect("undo lock should not be poisoned") = Some(Snapshot::of(targets));
+}
+
+fn write(snapshot: &Snapshot) -> io::Result<()> {
+    let path = history::state_file("undo").ok_or(io::Error::new(io::ErrorKind::NotFound, "neither XDG_STATE_HOME nor HOME is set"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, format(snapshot))
+}
+
+/// Save the boot variables as last read, before changing them. Made-up entries
+/// are only kept in memory, and failing to write the file is only worth a warning.
+pub fn checkpoint() {
+    let Some(seen) = SEEN.lock().expect("undo lock should not be poisoned").clone() else {
+        return;
+    };
+
+    if !demo::is_enabled() {
+        if let Err(e) = write(&seen) {
+            log::warn!("could not save the boot variables for undo: {}", e);
+        }
+    }
+    *SAVED.lock().expect("undo lock should not be poisoned") = Some(seen);
+}
+
+/// Values before the last change, from this run or an earlier one
+pub fn last() -> Option<Snapshot> {
+    if let Some(saved) = SAVED.lock().expect("undo lock should not be poisoned").clone() {
+        return Some(saved);
+    }
+    if demo::is_enabled() {
+        return None;
+    }
+    parse(&fs::read_to_string(history::state_file("undo")?).ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_what_it_writes() {
+        let snapshot = Snapshot { next: Some(0x0003), order: vec![0x0001, 0x0000] };
+        assert_eq!(format(&snapshot), "BootNext: 0003\nBootOrder: 0001,0000\n");
+        assert_eq!(parse(&format(&snapshot)), Some(snapshot));
+
+        assert_eq!(parse("BootOrder: \n"), Some(Snapshot { next: None, order: vec![] }));
+        assert_eq!(parse("BootNext: 0003\n"), None);
+        assert_eq!(parse("BootOrder: 0001,zz\n"), None);
+    }
+}
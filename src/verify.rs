@@ -0,0 +1,451 @@
+/***
+ * Loader verification
+ *
+ * Resolves the partition and loader path of a boot entry from its device path,
+ * and checks whether the referenced EFI binary actually exists on the ESP.
+ * With Secure Boot enabled, loaders without an Authenticode signature are
+ * flagged too, as the firmware will most likely refuse to start them.
+ ***/
+use std::{collections::{HashMap, HashSet}, fs, io::Read, path::{Path, PathBuf}, process::Command, sync::Mutex};
+
+use crate::{demo, disk, efivar::{self, SecureBoot}, esp::{self, Esp}, hibernation, i18n::tr, os::{self, Os}, os_release::{self, OsRelease}, parser::DevicePath, tempdir, BootTarget, BootTargets};
+
+/// Result of checking whether an entry's loader is present.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoaderStatus {
+    /// Not verified (yet)
+    Unchecked,
+    /// Entry does not point at a file on a partition (network boot, firmware app, ...)
+    NotApplicable,
+    /// Loader binary was found
+    Present,
+    /// Loader binary was found, but is not signed while Secure Boot is enabled
+    Unsigned,
+    /// Partition was found, loader binary was not
+    Missing,
+    /// Partition referenced by the entry is on no disk that is present, as
+    /// when it was unplugged or died
+    NoDisk,
+    /// Verification could not be performed, with reason
+    Unknown(String),
+}
+
+impl LoaderStatus {
+    pub fn is_broken(&self) -> bool {
+        matches!(self, LoaderStatus::Missing | LoaderStatus::NoDisk)
+    }
+
+    /// Whether the entry is unlikely to boot, because it is broken or won't pass Secure Boot
+    pub fn needs_attention(&self) -> bool {
+        self.is_broken() || *self == LoaderStatus::Unsigned
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            LoaderStatus::Unchecked => String::from(tr!("unchecked")),
+            LoaderStatus::NotApplicable => String::from(tr!("n/a")),
+            LoaderStatus::Present => String::from(tr!("ok")),
+            LoaderStatus::Unsigned => String::from(tr!("unsigned loader")),
+            LoaderStatus::Missing => String::from(tr!("MISSING loader")),
+            LoaderStatus::NoDisk => String::from(tr!("disk not present")),
+            LoaderStatus::Unknown(reason) => tr!("unknown ({})", reason),
+        }
+    }
+}
+
+/// Partition GUID and loader path extracted from a device path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoaderLocation {
+    pub partition: u32,
+    pub partuuid: String,
+    pub path: String,
+}
+
+/// Extract the GPT partition number, GUID and file path from a device path, e.g.
+/// `HD(1,GPT,0f1c...,0x800,0x32000)/File(\EFI\Microsoft\Boot\bootmgfw.efi)`
+pub fn loader_location(device_path: &DevicePath) -> Option<LoaderLocation> {
+    let (partition, partuuid) = device_path.gpt_partition()?;
+    let path = device_path.file()?.trim_end_matches('.').to_string();
+
+    Some(LoaderLocation { partition, partuuid: partuuid.to_lowercase(), path })
+}
+
+/// Resolve a partition GUID to its block device, e.g. `/dev/nvme0n1p1`
+pub fn partition_device(partuuid: &str) -> Option<PathBuf> {
+    fs::canonicalize(Path::new("/dev/disk/by-partuuid").join(partuuid.to_lowercase())).ok()
+}
+
+/// Find the disk a partition block device belongs to, e.g. `/dev/nvme0n1`
+pub fn parent_disk(partition: &Path) -> Option<PathBuf> {
+    let name = partition.file_name()?;
+    let sys = fs::canonicalize(Path::new("/sys/class/block").join(name)).ok()?;
+    let disk = sys.parent()?.file_name()?;
+
+    Some(Path::new("/dev").join(disk))
+}
+
+/// Look up a path relative to `root`, matching each component case-insensitively
+/// the way FAT would.
+fn find_case_insensitive(root: &Path, efi_path: &str) -> Option<PathBuf> {
+    let mut current = root.to_path_buf();
+
+    for component in efi_path.split('\\').filter(|c| !c.is_empty()) {
+        let exact = current.join(component);
+        if exact.exists() {
+            current = exact;
+            continue;
+        }
+
+        let entry = fs::read_dir(&current).ok()?
+            .filter_map(|e| e.ok())
+            .find(|e| e.file_name().to_string_lossy().eq_ignore_ascii_case(component))?;
+        current = entry.path();
+    }
+
+    Some(current)
+}
+
+/// Find where a block device is mounted, if anywhere.
+pub fn find_mount_point(device: &Path) -> Option<PathBuf> {
+    let mounts = fs::read_to_string("/proc/self/mounts").ok()?;
+
+    mounts.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let source = fields.next()?;
+        let target = fields.next()?;
+
+        let source = fs::canonicalize(source).ok()?;
+        if source == device {
+            // Mount points escape spaces as \040
+            Some(PathBuf::from(target.replace("\\040", " ")))
+        } else {
+            None
+        }
+    })
+}
+
+/// Whether a PE image has an Authenticode signature, going by the size of
+/// its certificate table. None if the file is not a PE image.
+fn is_signed(path: &Path) -> Option<bool> {
+    // The headers are well within the first few KiB
+    let mut header = vec![];
+    fs::File::open(path).ok()?.take(4096).read_to_end(&mut header).ok()?;
+
+    let u16_at = |offset: usize| header.get(offset..offset + 2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]));
+    let u32_at = |offset: usize| header.get(offset..offset + 4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+
+    if header.get(0..2)? != b"MZ" {
+        return None;
+    }
+    let pe = u32_at(0x3c)? as usize;
+    if header.get(pe..pe + 4)? != b"PE\0\0" {
+        return None;
+    }
+
+    // The data directories follow the optional header fields, which are longer for PE32+
+    let optional = pe + 24;
+    let directories = match u16_at(optional)? {
+        0x10b => optional + 96,
+        0x20b => optional + 112,
+        _ => return None,
+    };
+    let count = u32_at(directories - 4)?;
+    if count <= 4 {
+        return Some(false);
+    }
+
+    // The fifth directory is the certificate table
+    Some(u32_at(directories + 4 * 8 + 4)? > 0)
+}
+
+/// Filesystems a Linux root file system can be on, with the mount option that
+/// keeps the kernel from replaying their journal. A read-only mount would
+/// replay it otherwise, changing the filesystem under a system that is
+/// hibernated or did not shut down cleanly.
+const ROOT_FILESYSTEMS: [(&str, &str); 7] = [
+    ("ext4", "noload"),
+    ("btrfs", "rescue=nologreplay"),
+    ("xfs", "norecovery"),
+    ("f2fs", "norecovery"),
+    ("bcachefs", "norecovery"),
+    ("ext3", "noload"),
+    ("ext2", ""),
+];
+
+/// Options for mounting a partition for a moment
+const MOUNT_OPTIONS: &str = "ro,nosuid,nodev,noexec";
+
+/// Linux found on each partition looked at, kept for as long as reboot-to
+/// runs so that refreshing the entries doesn't mount them all again. Keyed by
+/// the partition's GUID too, as another disk may get the same device name.
+static PROBED: Mutex<Option<HashMap<PartitionKey, Option<OsRelease>>>> = Mutex::new(None);
+
+/// Device and GUID of a partition
+type PartitionKey = (PathBuf, Option<String>);
+
+/// Options for mounting a partition with the given filesystem to look for
+/// Linux, or None if Linux is not installed on such filesystems
+fn probe_options(filesystem: &str) -> Option<String> {
+    let (_, no_replay) = ROOT_FILESYSTEMS.iter().find(|(name, _)| *name == filesystem)?;
+    Some(if no_replay.is_empty() { String::from(MOUNT_OPTIONS) } else { format!("{},{}", MOUNT_OPTIONS, no_replay) })
+}
+
+/// Filesystem on a partition, from udev or else blkid
+fn filesystem(partition: &Path) -> Option<String> {
+    if let Some(filesystem) = disk::udev_property(partition, "ID_FS_TYPE") {
+        return Some(filesystem);
+    }
+    let output = Command::new("blkid").args(["-p", "-s", "TYPE", "-o", "value"]).arg(partition).output().ok()?;
+    let filesystem = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !filesystem.is_empty()).then_some(filesystem)
+}
+
+/// Verifies loaders, mounting unmounted partitions read-only when needed. Any
+/// temporary mounts are removed again when the verifier is dropped.
+pub struct Verifier {
+    roots: HashMap<String, Result<PathBuf, LoaderStatus>>,
+    temp_mounts: Vec<PathBuf>,
+    /// Check signatures, as the firmware only starts signed loaders
+    secure_boot: bool,
+    esp: Option<Esp>,
+    /// Partition GUIDs on the disks present, listed when first needed, see `disk`
+    present: Option<Option<HashSet<String>>>,
+    /// Whether a swap partition holds a hibernation image, found when first needed
+    hibernated: Option<bool>,
+}
+
+impl Verifier {
+    pub fn new() -> Verifier {
+        Verifier { roots: HashMap::new(), temp_mounts: vec![], secure_boot: efivar::secure_boot() == Some(SecureBoot::Enabled), esp: None, present: None, hibernated: None }
+    }
+
+    /// Look up loaders on the ESP under its mount point
+    pub fn with_esp(mut self, esp: Option<Esp>) -> Verifier {
+        self.esp = esp;
+        self
+    }
+
+    fn mount_temporarily(&mut self, device: &Path, filesystem: Option<&str>, options: &str) -> Result<PathBuf, LoaderStatus> {
+        let dir = tempdir::create("mount").map_err(|e| LoaderStatus::Unknown(tr!("cannot create mount point: {}", e)))?;
+
+        let mut command = Command::new("mount");
+        if let Some(filesystem) = filesystem {
+            command.args(["-t", filesystem]);
+        }
+        let status = command
+            .args(["-o", options])
+            .arg(device)
+            .arg(&dir)
+            .output();
+
+        match status {
+            Ok(output) if output.status.success() => {
+                self.temp_mounts.push(dir.clone());
+                Ok(dir)
+            }
+            _ => {
+                let _ = fs::remove_dir(&dir);
+                Err(LoaderStatus::Unknown(String::from(tr!("partition not mounted and could not mount it"))))
+            }
+        }
+    }
+
+    /// Directory the given partition block device is (or got temporarily) mounted at
+    pub fn device_root(&mut self, device: &Path) -> Result<PathBuf, LoaderStatus> {
+        match find_mount_point(device) {
+            Some(mount_point) => Ok(mount_point),
+            None => self.mount_temporarily(device, None, MOUNT_OPTIONS),
+        }
+    }
+
+    /// Linux installed on a partition, mounting it if needed. Only partitions
+    /// with a filesystem Linux is installed on are mounted, without replaying
+    /// their journal, and none while a hibernation image waits in swap, as the
+    /// system it belongs to may be on them.
+    fn probe(&mut self, partition: &Path) -> Option<OsRelease> {
+        let key = (partition.to_path_buf(), disk::udev_property(partition, "ID_PART_ENTRY_UUID"));
+        let mut probed = PROBED.lock().expect("probed partitions lock should not be poisoned");
+        if let Some(cached) = probed.get_or_insert_with(HashMap::new).get(&key) {
+            return cached.clone();
+        }
+
+        let system = match find_mount_point(partition) {
+            Some(mount_point) => os_release::read(&mount_point),
+            None if *self.hibernated.get_or_insert_with(|| !hibernation::linux_images().is_empty()) => {
+                log::info!("not mounting {} to look for Linux, a hibernation image is in swap", partition.display());
+                None
+            }
+            None => filesystem(partition)
+                .and_then(|filesystem| Some((probe_options(&filesystem)?, filesystem)))
+                .and_then(|(options, filesystem)| self.mount_temporarily(partition, Some(&filesystem), &options).ok())
+                .and_then(|root| os_release::read(&root)),
+        };
+        probed.get_or_insert_with(HashMap::new).insert(key, system.clone());
+        system
+    }
+
+    /// Linux installed on the disk the loader is on. With several of them,
+    /// the one whose directory on the ESP has the loader.
+    pub fn installed_os(&mut self, location: &LoaderLocation) -> Option<OsRelease> {
+        let loader_partition = partition_device(&location.partuuid)?;
+        let disk = parent_disk(&loader_partition)?;
+
+        let mut systems: Vec<OsRelease> = vec![];
+        for partition in disk::partitions(&disk).iter().filter(|partition| **partition != loader_partition) {
+            if let Some(system) = self.probe(partition).filter(|system| !systems.contains(system)) {
+                systems.push(system);
+            }
+        }
+
+        match systems.as_slice() {
+            [system] => Some(system.clone()),
+            _ => {
+                let path = location.path.to_lowercase();
+                systems.into_iter().find(|system| path.contains(&format!("\\{}\\", system.id)))
+            }
+        }
+    }
+
+    /// Windows started by a boot manager, like "Windows 11"
+    pub fn windows_release(&mut self, location: &LoaderLocation) -> Option<String> {
+        let root = self.partition_root(&location.partuuid).ok()?;
+        find_case_insensitive(&root, &location.path).and_then(|path| os_release::windows(&path))
+    }
+
+    /// Whether the partition is known to be on none of the disks present
+    fn is_absent(&mut self, partuuid: &str) -> bool {
+        self.present.get_or_insert_with(disk::present_partuuids).as_ref().is_some_and(|present| !present.contains(partuuid))
+    }
+
+    fn partition_root(&mut self, partuuid: &str) -> Result<PathBuf, LoaderStatus> {
+        if let Some(cached) = self.roots.get(partuuid) {
+            return cached.clone();
+        }
+
+        let esp_mount_point = match &self.esp {
+            Some(Esp { partuuid: Some(esp), mount_point: Some(mount_point), .. }) if esp == partuuid => Some(mount_point.clone()),
+            _ => None,
+        };
+        let root = match (esp_mount_point, partition_device(partuuid)) {
+            (Some(mount_point), _) => Ok(mount_point),
+            (None, Some(device)) => self.device_root(&device),
+            (None, None) if self.is_absent(partuuid) => Err(LoaderStatus::NoDisk),
+            (None, None) => Err(LoaderStatus::Unknown(String::from(tr!("partition has no device node")))),
+        };
+
+        self.roots.insert(partuuid.to_string(), root.clone());
+        root
+    }
+
+    pub fn verify(&mut self, target: &BootTarget) -> LoaderStatus {
+        let Some(device_path) = target.device_path.as_ref() else {
+            return LoaderStatus::NotApplicable;
+        };
+        let location = match loader_location(device_path) {
+            Some(location) => location,
+            // Entries for removable media have no file, the firmware looks for \EFI\BOOT\BOOTX64.EFI,
+            // but their disk is still worth checking
+            None => return match device_path.gpt_partition() {
+                Some((_, partuuid)) if self.is_absent(&partuuid.to_lowercase()) => LoaderStatus::NoDisk,
+                _ => LoaderStatus::NotApplicable,
+            },
+        };
+
+        let root = match self.partition_root(&location.partuuid) {
+            Ok(root) => root,
+            Err(status) => return status,
+        };
+
+        match find_case_insensitive(&root, &location.path) {
+            Some(path) if path.is_file() && self.secure_boot && is_signed(&path) == Some(false) => LoaderStatus::Unsigned,
+            Some(path) if path.is_file() => LoaderStatus::Present,
+            _ => LoaderStatus::Missing,
+        }
+    }
+}
+
+impl Drop for Verifier {
+    fn drop(&mut self) {
+        for dir in self.temp_mounts.iter() {
+            let _ = Command::new("umount").arg(dir).output();
+            let _ = fs::remove_dir(dir);
+        }
+    }
+}
+
+/// Whether the partition of an entry is on none of the disks present, for
+/// when its loader doesn't need checking
+pub fn disk_missing(target: &BootTarget) -> bool {
+    // Demo entries point at partitions that do not exist
+    if demo::is_enabled() {
+        return false;
+    }
+
+    let Some((_, partuuid)) = target.device_path.as_ref().and_then(DevicePath::gpt_partition) else {
+        return false;
+    };
+    let partuuid = partuuid.to_lowercase();
+    partition_device(&partuuid).is_none() && disk::present_partuuids().is_some_and(|present| !present.contains(&partuuid))
+}
+
+/// Name Windows gives its boot entry, whatever the version
+const WINDOWS_BOOT_MANAGER: &str = "Windows Boot Manager";
+
+/// Whether to name an entry after the Linux found on the other partitions of
+/// its disk, which may mean mounting them. Entries with a name of their own,
+/// like "Fedora" or "ubuntu", keep it.
+fn names_installed_os(target: &BootTarget) -> bool {
+    matches!(os::detect(target), None | Some(Os::Linux)) && os_release::is_vague(&target.name)
+}
+
+/// Verify the loaders of all targets, storing the result on each target.
+/// With `name_systems`, vaguely named entries are also given the name of the
+/// system they start, mounting partitions to find it if needed.
+pub fn verify_targets(targets: &mut BootTargets, name_systems: bool) {
+    // Demo entries point at partitions that do not exist
+    if demo::is_enabled() {
+        return;
+    }
+
+    let mut verifier = Verifier::new().with_esp(esp::find(targets));
+
+    for target in targets.targets.iter_mut() {
+        target.loader = verifier.verify(target);
+        let partition = target.device_path.as_ref()
+            .and_then(DevicePath::gpt_partition)
+            .and_then(|(_, partuuid)| partition_device(partuuid));
+        target.device = partition.as_deref().and_then(disk::describe_partition);
+        target.removable = partition.as_deref().is_some_and(disk::is_removable);
+        if !name_systems {
+            continue;
+        }
+        let Some(location) = target.device_path.as_ref().and_then(loader_location) else {
+            continue;
+        };
+        target.os_name = match os::detect(target) {
+            _ if names_installed_os(target) => verifier.installed_os(&location).map(|system| system.name),
+            Some(Os::Windows) if target.name.eq_ignore_ascii_case(WINDOWS_BOOT_MANAGER) => verifier.windows_release(&location),
+            _ => None,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_only_vaguely_named_entries() {
+        let path = "HD(1,GPT,0f1c2d3e-4b5a-6978-8a9b-0c1d2e3f4a5b,0x800,0x100000)/File";
+        let targets = crate::parse_boot_targets(&format!("BootOrder: 0001\n\
+            Boot0001* Linux Boot Manager\t{path}(\\EFI\\systemd\\systemd-bootx64.efi)\n\
+            Boot0002* UEFI OS\t{path}(\\EFI\\BOOT\\BOOTX64.EFI)\n\
+            Boot0003* Fedora\t{path}(\\EFI\\fedora\\shimx64.efi)\n\
+            Boot0004* ubuntu\t{path}(\\EFI\\ubuntu\\shimx64.efi)\n\
+            Boot0005* My Linux\t{path}(\\EFI\\BOOT\\BOOTX64.EFI)\n\
+            Boot0006* Windows Boot Manager\t{path}(\\EFI\\Microsoft\\Boot\\bootmgfw.efi)\n"));
+
+        let named: Vec<bool> = targets.targets.iter().map(names_installed_os).collect();
+        assert_eq!(named, [true, true, false, false, false, false]);
+    }
+}
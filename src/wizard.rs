@@ -0,0 +1,228 @@
+/***
+ * Boot entry creation wizard
+ *
+ * Guides the user through picking a disk, partition, loader and label, and
+ * then creates the boot entry using efibootmgr.
+ ***/
+use std::{fs, path::{Path, PathBuf}};
+
+use crate::{confirm, efibootmgr::{self, check, NewEntry}, i18n::tr, prompt, verify::Verifier, BootTargets, Exit};
+
+struct Disk {
+    device: PathBuf,
+    model: String,
+    size: u64,
+}
+
+struct Partition {
+    device: PathBuf,
+    number: u32,
+    size: u64,
+}
+
+fn read_sys(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+fn human_size(sectors: u64) -> String {
+    let mut size = sectors as f64 * 512.0;
+    for unit in ["B", "KiB", "MiB", "GiB"] {
+        if size < 1024.0 {
+            return format!("{:.1} {}", size, unit);
+        }
+        size /= 1024.0;
+    }
+    format!("{:.1} TiB", size)
+}
+
+fn list_disks() -> Vec<Disk> {
+    let mut disks: Vec<Disk> = fs::read_dir("/sys/block").into_iter().flatten()
+        .filter_map(|e| e.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if ["loop", "ram", "zram", "dm-", "sr", "md"].iter().any(|prefix| name.starts_with(prefix)) {
+                return None;
+            }
+
+            Some(Disk {
+                device: Path::new("/dev").join(&name),
+                model: read_sys(&entry.path().join("device/model")).unwrap_or_default(),
+                size: read_sys(&entry.path().join("size")).and_then(|s| s.parse().ok()).unwrap_or(0),
+            })
+        })
+        .collect();
+
+    disks.sort_by(|a, b| a.device.cmp(&b.device));
+    disks
+}
+
+fn list_partitions(disk: &Path) -> Vec<Partition> {
+    let name = disk.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+
+    let mut partitions: Vec<Partition> = fs::read_dir(Path::new("/sys/block").join(name)).into_iter().flatten()
+        .filter_map(|e| e.ok())
+        .filter_map(|entry| {
+            let number = read_sys(&entry.path().join("partition"))?.parse().ok()?;
+            Some(Partition {
+                device: Path::new("/dev").join(entry.file_name()),
+                number,
+                size: read_sys(&entry.path().join("size")).and_then(|s| s.parse().ok()).unwrap_or(0),
+            })
+        })
+        .collect();
+
+    partitions.sort_by_key(|p| p.number);
+    partitions
+}
+
+/// Recursively find all .efi files below `dir`, as paths relative to `root`
+fn find_loaders(root: &Path, dir: &Path, found: &mut Vec<String>) {
+    for entry in fs::read_dir(dir).into_iter().flatten().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            find_loaders(root, &path, found);
+        } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("efi")) {
+            if let Ok(relative) = path.strip_prefix(root) {
+                found.push(format!("\\{}", relative.to_string_lossy().replace('/', "\\")));
+            }
+        }
+    }
+}
+
+/// Ask to pick one of `count` numbered options, re-asking until the answer is valid
+fn choose(question: &str, count: usize) -> Option<usize> {
+    loop {
+        let answer = prompt(question)?;
+        match answer.parse::<usize>() {
+            Ok(n) if (1..=count).contains(&n) => return Some(n - 1),
+            _ => println!("{}", tr!("Please enter a number between 1 and {}", count)),
+        }
+    }
+}
+
+fn choose_disk() -> Option<Disk> {
+    let mut disks = list_disks();
+    if disks.is_empty() {
+        eprintln!("{}", tr!("No disks found"));
+        return None;
+    }
+
+    println!("{}", tr!("Disks:"));
+    for (i, disk) in disks.iter().enumerate() {
+        println!("  {}) {} \t {} \t {}", i + 1, disk.device.display(), human_size(disk.size), disk.model);
+    }
+
+    let index = choose(tr!("Disk number:"), disks.len())?;
+    Some(disks.swap_remove(index))
+}
+
+fn choose_partition(disk: &Disk) -> Option<Partition> {
+    let mut partitions = list_partitions(&disk.device);
+    if partitions.is_empty() {
+        eprintln!("{}", tr!("No partitions found on {}", disk.device.display()));
+        return None;
+    }
+
+    println!("{}", tr!("Partitions on {}:", disk.device.display()));
+    for (i, partition) in partitions.iter().enumerate() {
+        println!("  {}) {} \t {} \t {}", i + 1, partition.device.display(), tr!("partition {}", partition.number), human_size(partition.size));
+    }
+
+    let index = choose(tr!("Partition number (usually the EFI System Partition):"), partitions.len())?;
+    Some(partitions.swap_remove(index))
+}
+
+fn choose_loader(partition: &Partition) -> Option<String> {
+    let mut verifier = Verifier::new();
+    let mut loaders = vec![];
+
+    match verifier.device_root(&partition.device) {
+        Ok(root) => find_loaders(&root, &root, &mut loaders),
+        Err(status) => println!("{}", tr!("Cannot browse {}: {}", partition.device.display(), status.describe())),
+    }
+    loaders.sort();
+
+    if !loaders.is_empty() {
+        println!("{}", tr!("EFI loaders on {}:", partition.device.display()));
+        for (i, loader) in loaders.iter().enumerate() {
+            println!("  {}) {}", i + 1, loader);
+        }
+    }
+
+    loop {
+        let answer = prompt(tr!("Loader (number from the list, or a path like \\EFI\\name\\loader.efi):"))?;
+
+        if let Ok(n) = answer.parse::<usize>() {
+            if let Some(loader) = loaders.get(n.wrapping_sub(1)) {
+                return Some(loader.clone());
+            }
+            println!("{}", tr!("Please enter a number between 1 and {}", loaders.len()));
+            continue;
+        }
+
+        let loader = answer.replace('/', "\\");
+        if !loader.starts_with('\\') {
+            println!("{}", tr!("Loader paths must be absolute, starting with \\"));
+            continue;
+        }
+        if !loaders.is_empty() && !loaders.iter().any(|l| l.eq_ignore_ascii_case(&loader)) && !confirm(tr!("This file was not found on the partition, use it anyway?")) {
+            continue;
+        }
+
+        return Some(loader);
+    }
+}
+
+fn choose_label(targets: &BootTargets) -> Option<String> {
+    loop {
+        let label = prompt(tr!("Label:"))?;
+
+        if let Err(e) = efibootmgr::check_label(&label) {
+            println!("{}", e);
+            continue;
+        }
+        if targets.targets.iter().any(|target| target.name == label) && !confirm(tr!("An entry with this label already exists, use it anyway?")) {
+            continue;
+        }
+
+        return Some(label);
+    }
+}
+
+/// Run the wizard, returning how it ended
+pub fn run(targets: &BootTargets) -> Exit {
+    let disk = match choose_disk() { Some(disk) => disk, None => return Exit::Failure };
+    let partition = match choose_partition(&disk) { Some(partition) => partition, None => return Exit::Failure };
+    let loader = match choose_loader(&partition) { Some(loader) => loader, None => return Exit::Failure };
+    let label = match choose_label(targets) { Some(label) => label, None => return Exit::Failure };
+
+    println!();
+    println!("{}", tr!("Disk:      {}", disk.device.display()));
+    println!("{}", tr!("Partition: {}", partition.number));
+    println!("{}", tr!("Loader:    {}", loader));
+    println!("{}", tr!("Label:     {}", label));
+    if !confirm(tr!("Create this boot entry?")) {
+        return Exit::Cancelled;
+    }
+
+    let result = check(efibootmgr::create_entry(&NewEntry {
+        id: None,
+        disk: &disk.device,
+        partition: partition.number,
+        loader: &loader,
+        label: &label,
+        optional_data: None,
+        create_only: false,
+    }));
+
+    match result {
+        Ok(()) => {
+            println!("{}", tr!("Created boot entry \"{}\"", label));
+            Exit::Success
+        }
+        Err(e) => {
+            eprintln!("{}", tr!("Could not create boot entry: {}", e));
+            Exit::from_command(&e, Exit::EfibootmgrFailed)
+        }
+    }
+}